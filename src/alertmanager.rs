@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::message::{InteractiveCard, LarkMessage};
+
+/// The body Prometheus Alertmanager posts to a webhook receiver.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertmanagerPayload {
+    /// `firing` or `resolved`.
+    pub status: String,
+    #[serde(default)]
+    pub group_labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub common_labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub common_annotations: BTreeMap<String, String>,
+    #[serde(rename = "externalURL", default)]
+    pub external_url: Option<String>,
+    pub alerts: Vec<Alert>,
+}
+
+/// One alert of an [`AlertmanagerPayload`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    pub status: String,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub annotations: BTreeMap<String, String>,
+    #[serde(default)]
+    pub starts_at: Option<String>,
+    #[serde(default)]
+    pub ends_at: Option<String>,
+    #[serde(rename = "generatorURL", default)]
+    pub generator_url: Option<String>,
+}
+
+impl Alert {
+    fn is_resolved(&self) -> bool {
+        self.status == "resolved"
+    }
+}
+
+impl AlertmanagerPayload {
+    pub fn is_resolved(&self) -> bool {
+        self.status == "resolved"
+    }
+
+    /// `[FIRING:2] alertname=HighLatency job=api`: the status, the number of
+    /// alerts, the group labels and then the other common labels.
+    pub fn title(&self) -> String {
+        let firing = self
+            .alerts
+            .iter()
+            .filter(|alert| !alert.is_resolved())
+            .count();
+        let (status, count) = if self.is_resolved() {
+            ("RESOLVED", self.alerts.len())
+        } else {
+            ("FIRING", firing)
+        };
+        let labels = self.group_labels.iter().chain(
+            self.common_labels
+                .iter()
+                .filter(|(name, _)| !self.group_labels.contains_key(*name)),
+        );
+        let labels: Vec<String> = labels
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        format!("[{}:{}] {}", status, count, labels.join(" "))
+            .trim_end()
+            .to_string()
+    }
+
+    /// Header color: green once resolved, otherwise by the most severe `severity` label.
+    pub fn color(&self) -> &'static str {
+        if self.is_resolved() {
+            return "green";
+        }
+        let severities = self
+            .alerts
+            .iter()
+            .filter(|alert| !alert.is_resolved())
+            .filter_map(|alert| alert.labels.get("severity"))
+            .chain(self.common_labels.get("severity"));
+        severities
+            .map(|severity| severity_color(severity))
+            .min_by_key(|(rank, _)| *rank)
+            .map_or("red", |(_, color)| color)
+    }
+
+    /// A card with a header for the group and one section per alert.
+    pub fn to_card(&self) -> InteractiveCard {
+        let mut summary = Vec::new();
+        if let Some(text) = self
+            .common_annotations
+            .get("summary")
+            .or_else(|| self.common_annotations.get("description"))
+        {
+            summary.push(text.clone());
+        }
+        let firing = self
+            .alerts
+            .iter()
+            .filter(|alert| !alert.is_resolved())
+            .count();
+        summary.push(format!(
+            "**{}** firing, **{}** resolved",
+            firing,
+            self.alerts.len() - firing
+        ));
+        if let Some(url) = &self.external_url {
+            summary.push(format!("[Alertmanager]({})", url));
+        }
+
+        let mut card = InteractiveCard::new(self.title(), self.color(), summary.join("\n"));
+        for alert in &self.alerts {
+            card = card.with_section(alert_section(alert, &self.common_labels));
+        }
+        card
+    }
+
+    pub fn to_message(&self) -> LarkMessage {
+        LarkMessage::card(self.to_card())
+    }
+}
+
+/// Rank (lower is more severe) and header color of a `severity` label.
+fn severity_color(severity: &str) -> (u8, &'static str) {
+    match severity.to_ascii_lowercase().as_str() {
+        "critical" | "page" | "emergency" => (0, "red"),
+        "error" | "major" => (1, "orange"),
+        "warning" | "warn" | "minor" => (2, "yellow"),
+        "info" | "none" => (3, "blue"),
+        _ => (1, "orange"),
+    }
+}
+
+fn alert_section(alert: &Alert, common_labels: &BTreeMap<String, String>) -> String {
+    let name = alert
+        .labels
+        .get("alertname")
+        .map_or("alert", String::as_str);
+    let status = if alert.is_resolved() {
+        "✅ RESOLVED"
+    } else {
+        "🔥 FIRING"
+    };
+    let mut lines = vec![format!("{} **{}**", status, name)];
+
+    for key in ["summary", "description"] {
+        if let Some(text) = alert.annotations.get(key) {
+            lines.push(text.clone());
+        }
+    }
+    let labels: Vec<String> = alert
+        .labels
+        .iter()
+        .filter(|(label, value)| *label != "alertname" && common_labels.get(*label) != Some(value))
+        .map(|(label, value)| format!("{}={}", label, value))
+        .collect();
+    if !labels.is_empty() {
+        lines.push(format!("Labels: {}", labels.join(", ")));
+    }
+    if let Some(starts_at) = &alert.starts_at {
+        lines.push(format!("Started: {}", starts_at));
+    }
+    if alert.is_resolved()
+        && let Some(ends_at) = &alert.ends_at
+    {
+        lines.push(format!("Resolved: {}", ends_at));
+    }
+    if let Some(url) = &alert.generator_url {
+        lines.push(format!("[Source]({})", url));
+    }
+    lines.join("\n")
+}
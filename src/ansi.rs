@@ -0,0 +1,89 @@
+/// Remove terminal escape sequences from `text`, as written by colored CI output.
+///
+/// CSI sequences (colors, cursor movement, erasing), OSC sequences (window
+/// titles, hyperlinks) and other two-byte escapes are dropped, as is an
+/// incomplete sequence at the end. A line redrawn after carriage returns, like
+/// a progress bar, keeps only its last state. Other text, brackets included,
+/// is left as it is.
+///
+/// ```
+/// use lark_notifier::strip_ansi;
+///
+/// // cargo
+/// assert_eq!(
+///     strip_ansi("\x1b[1m\x1b[32m   Compiling\x1b[0m foo v0.1.0\n\x1b[0m\x1b[1m\x1b[31merror[E0308]\x1b[0m\x1b[1m: mismatched types\x1b[0m"),
+///     "   Compiling foo v0.1.0\nerror[E0308]: mismatched types"
+/// );
+/// // pytest, with a progress line redrawn in place and cursor movement
+/// assert_eq!(
+///     strip_ansi("tests/a.py \x1b[32m.\x1b[0m\x1b[31mF\x1b[0m [ 50%]\r\x1b[2Ktests/a.py .F [100%]\n\x1b[1A\x1b[31m1 failed\x1b[0m"),
+///     "tests/a.py .F [100%]\n1 failed"
+/// );
+/// // an OSC 8 hyperlink, and a sequence cut off at the end
+/// assert_eq!(strip_ansi("\x1b]8;;https://example.com\x07docs\x1b]8;;\x07 [ok]\x1b[3"), "docs [ok]");
+/// // a download bar that ends on a carriage return, and Windows line endings
+/// assert_eq!(strip_ansi(" 10%\r 60%\r100%\r\r\ndone\r\n"), "100%\ndone\n");
+/// ```
+pub fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    // Where the current line starts in `out`, so a carriage return can rewind to it.
+    let mut line_start = 0;
+    // Set by a carriage return; the line is only rewound once something overwrites it.
+    let mut rewind = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameter and intermediate bytes, then one final byte.
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC (and the other string sequences): until BEL or ESC \.
+                Some(']' | 'P' | '_' | '^' | 'X') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' {
+                            if chars.peek() == Some(&'\\') {
+                                chars.next();
+                            }
+                            break;
+                        }
+                    }
+                }
+                // Character set selection takes one more byte.
+                Some('(' | ')' | '*' | '+') => {
+                    chars.next();
+                }
+                _ => {}
+            },
+            // The 8-bit form of CSI.
+            '\u{9b}' => {
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            '\r' => rewind = true,
+            '\n' => {
+                out.push('\n');
+                line_start = out.len();
+                rewind = false;
+            }
+            _ => {
+                if rewind {
+                    out.truncate(line_start);
+                    rewind = false;
+                }
+                out.push(c);
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,860 @@
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{ArgAction, Args, ValueEnum};
+use clap_complete::Shell;
+use lark_notifier::{
+    CardButton, CiFooter, DEFAULT_MAX_BYTES, KeywordPattern, Locale, NotifierError, Profile, Queue,
+    Region, SignVersion,
+};
+use reqwest::Url;
+use serde_json::Value;
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Args)]
+pub(crate) struct ExecArgs {
+    #[command(flatten)]
+    pub(crate) exec: ExecOptions,
+
+    #[command(flatten)]
+    pub(crate) send: SendArgs,
+}
+
+/// The command to run for `exec` and how much of its output to send.
+#[derive(Args, Clone)]
+pub(crate) struct ExecOptions {
+    /// Lines of output to send, from the end; stdout and stderr are interleaved
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    pub(crate) tail_lines: usize,
+
+    /// The command and its arguments, after `--`
+    #[arg(last = true, required = true, value_name = "COMMAND")]
+    pub(crate) command: Vec<String>,
+}
+
+#[derive(Args)]
+pub(crate) struct HeartbeatArgs {
+    #[command(flatten)]
+    pub(crate) heartbeat: HeartbeatOptions,
+
+    #[command(flatten)]
+    pub(crate) send: SendArgs,
+}
+
+/// How often `heartbeat` sends and what.
+#[derive(Args, Clone)]
+pub(crate) struct HeartbeatOptions {
+    /// Time between heartbeats, e.g. `30s`, `15m`, `2h` or `1h30m`
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub(crate) interval: Duration,
+
+    /// Run this shell command before each heartbeat and send its output as the content
+    #[arg(long, value_name = "COMMAND", conflicts_with_all = ["content", "content_file", "markdown_file", "stdin"])]
+    pub(crate) content_cmd: Option<String>,
+
+    /// Stop after this many heartbeats
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub(crate) max_count: Option<u64>,
+}
+
+#[derive(Args)]
+pub(crate) struct CompletionsArgs {
+    /// Shell to generate completions for
+    #[arg(value_enum)]
+    pub(crate) shell: Shell,
+}
+
+#[cfg(all(feature = "journal", target_os = "linux"))]
+#[derive(Args)]
+pub(crate) struct JournalArgs {
+    #[command(flatten)]
+    pub(crate) journal: JournalOptions,
+
+    #[command(flatten)]
+    pub(crate) send: SendArgs,
+}
+
+/// What to follow in the journal and how often to send.
+#[cfg(all(feature = "journal", target_os = "linux"))]
+#[derive(Args, Clone)]
+pub(crate) struct JournalOptions {
+    /// Only entries of this systemd unit (repeatable)
+    #[arg(long)]
+    pub(crate) unit: Vec<String>,
+
+    /// Only entries at this priority or more severe, e.g. `err` or `warning` (as journalctl -p)
+    #[arg(long)]
+    pub(crate) priority: Option<String>,
+
+    /// Only entries whose message matches this regular expression
+    #[arg(long, value_name = "REGEX", value_parser = regex::Regex::new)]
+    pub(crate) grep: Option<regex::Regex>,
+
+    /// Collect entries for this many seconds before sending them as one message
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    pub(crate) interval: u64,
+}
+
+#[derive(Args)]
+pub(crate) struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8787")]
+    pub(crate) listen: SocketAddr,
+
+    /// TOML config file; every profile is served at `/<profile>`, `default` also at `/`
+    #[arg(long)]
+    pub(crate) config: Option<PathBuf>,
+
+    /// Retries on connection errors, 429 and 5xx responses
+    #[arg(long, default_value_t = 3)]
+    pub(crate) retries: u32,
+
+    /// Timeout in seconds for each request to Lark
+    #[arg(long, visible_alias = "timeout", default_value_t = 10)]
+    pub(crate) timeout_secs: u64,
+
+    /// Most requests per minute to Lark across all routes; 0 for no limit
+    #[arg(long, value_name = "N_PER_MIN", default_value_t = 60)]
+    pub(crate) rate_limit: u32,
+
+    /// Accept any http(s) webhook URL, e.g. a self-hosted gateway or a test server
+    #[arg(long)]
+    pub(crate) allow_insecure_url: bool,
+
+    /// Log every request to Lark to stderr; repeat (-vv) to include HTTP client internals
+    #[arg(short, long, action = ArgAction::Count)]
+    pub(crate) verbose: u8,
+}
+
+#[derive(Args)]
+pub(crate) struct ValidateConfigArgs {
+    /// Config file to check (default: ~/.config/lark-notifier/config.toml)
+    #[arg(long)]
+    pub(crate) config: Option<PathBuf>,
+
+    /// Accept any http(s) webhook URL, e.g. a self-hosted gateway or a test server
+    #[arg(long)]
+    pub(crate) allow_insecure_url: bool,
+}
+
+#[derive(Args, Clone)]
+pub(crate) struct SendArgs {
+    /// Set by the `test` subcommand to report reachability instead of delivery.
+    #[arg(skip)]
+    pub(crate) connectivity_test: bool,
+
+    /// Emoji for `--level`, resolved against the config file.
+    #[arg(skip)]
+    pub(crate) level_emoji: Option<String>,
+
+    /// The profile's title prefix, for a title taken from the content.
+    #[arg(skip)]
+    pub(crate) title_prefix: Option<String>,
+
+    /// Set by the `journal` subcommand.
+    #[cfg(all(feature = "journal", target_os = "linux"))]
+    #[arg(skip)]
+    pub(crate) journal: Option<JournalOptions>,
+
+    /// Set by the `exec` subcommand.
+    #[arg(skip)]
+    pub(crate) exec: Option<ExecOptions>,
+
+    /// Set by the `heartbeat` subcommand.
+    #[arg(skip)]
+    pub(crate) heartbeat: Option<HeartbeatOptions>,
+
+    /// Set by the `flush` subcommand.
+    #[arg(skip)]
+    pub(crate) flush: bool,
+
+    /// Build details read from the CI environment for `--github` or `--gitlab`.
+    #[arg(skip)]
+    pub(crate) ci_footer: Option<CiFooter>,
+
+    /// Print the signed JSON payload instead of sending it
+    #[arg(long)]
+    pub(crate) dry_run: bool,
+
+    /// Print how the signature for this timestamp is computed from the secret, and exit
+    #[arg(long, value_name = "TIMESTAMP", hide = true)]
+    pub(crate) verify_sign: Option<u64>,
+
+    /// Sign with this Unix timestamp (seconds) instead of the current time
+    #[arg(
+        long,
+        value_name = "UNIX_SECS",
+        hide = true,
+        conflicts_with = "time_offset_secs"
+    )]
+    pub(crate) timestamp: Option<u64>,
+
+    /// Accept a --timestamp that is zero or far in the future
+    #[arg(long, hide = true, requires = "timestamp")]
+    pub(crate) force: bool,
+
+    /// How the signature is computed; Lark and Feishu webhooks take hmac-sha256
+    #[arg(
+        long,
+        default_value_t = SignVersion::default(),
+        value_parser = PossibleValuesParser::new(SignVersion::ALL.map(SignVersion::name))
+            .map(|version| version.parse::<SignVersion>().expect("a possible value"))
+    )]
+    pub(crate) sign_version: SignVersion,
+
+    /// Before sending signed messages, compare the local clock with the webhook server's
+    #[arg(long)]
+    pub(crate) check_clock: bool,
+
+    /// Warn when --check-clock finds the clock more than this many seconds off
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = 300,
+        requires = "check_clock"
+    )]
+    pub(crate) max_clock_skew: u64,
+
+    /// Add this many seconds (negative to subtract) to the local clock when signing,
+    /// for a clock that is off and cannot be fixed with NTP
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = 0,
+        allow_negative_numbers = true
+    )]
+    pub(crate) time_offset_secs: i64,
+
+    /// Log the request and response to stderr; repeat (-vv) to include HTTP client internals
+    #[arg(short, long, action = ArgAction::Count)]
+    pub(crate) verbose: u8,
+
+    /// Print nothing but errors; the exit status still tells whether the send worked
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub(crate) quiet: bool,
+
+    /// TOML config file with named profiles [default: ~/.config/lark-notifier/config.toml]
+    #[arg(long)]
+    pub(crate) config: Option<PathBuf>,
+
+    /// Profile in the config file to use [default: default]
+    #[arg(long)]
+    pub(crate) profile: Option<String>,
+
+    /// Lark webhook URL, repeatable or comma separated; `url|secret` sets a per-webhook secret
+    /// (if not provided, use LARK_WEBHOOK_URL env var)
+    #[arg(short, long, value_delimiter = ',')]
+    pub(crate) webhook_url: Vec<String>,
+
+    /// Lark app secret for signed messages, or `@-` to read it from stdin
+    /// (if not provided, use --secret-file or LARK_SECRET env var)
+    #[arg(short, long)]
+    pub(crate) secret: Option<String>,
+
+    /// Read the secret from a file so it does not show up in the process list
+    #[arg(long, conflicts_with = "secret")]
+    pub(crate) secret_file: Option<PathBuf>,
+
+    /// Per-webhook secrets, aligned by index with the webhook URLs
+    #[arg(long, value_delimiter = ',')]
+    pub(crate) secrets: Vec<String>,
+
+    /// Message title (required for post and card messages)
+    #[arg(short, long)]
+    pub(crate) title: Option<String>,
+
+    /// Without --title, use the first line of the content as the title and the rest as the body
+    #[arg(long, conflicts_with = "raw_json")]
+    pub(crate) title_from_content: bool,
+
+    /// Message content (use `-` to read from stdin)
+    #[arg(short, long)]
+    pub(crate) content: Option<String>,
+
+    /// Read message content from stdin (same as `--content -`)
+    #[arg(long, conflicts_with_all = ["content", "content_file"])]
+    pub(crate) stdin: bool,
+
+    /// Send one message per line of a JSONL file (`-` for stdin); each line is
+    /// `{"title": "...", "content": "...", "keywords": ["..."]}`
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["content", "content_file", "stdin", "template"])]
+    pub(crate) batch: Option<PathBuf>,
+
+    /// Send each line read from stdin as its own message until EOF or Ctrl-C
+    #[arg(
+        long,
+        conflicts_with_all = ["content", "content_file", "stdin", "batch", "template", "raw_json", "markdown_file"]
+    )]
+    pub(crate) pipe: bool,
+
+    /// Combine up to this many stdin lines into one message
+    #[arg(long, value_name = "N", default_value_t = 1, requires = "pipe")]
+    pub(crate) pipe_batch: usize,
+
+    /// Send combined lines after waiting this long for more, even if --pipe-batch is not reached
+    #[arg(long, value_name = "SECS", requires = "pipe")]
+    pub(crate) pipe_interval: Option<u64>,
+
+    /// How many batch messages to send at once
+    #[arg(long, default_value_t = 1, requires = "batch")]
+    pub(crate) concurrency: usize,
+
+    /// Build the title and content from a template file or a built-in (deploy, alert)
+    #[arg(long, value_name = "NAME|FILE", conflicts_with_all = ["content", "content_file", "stdin"])]
+    pub(crate) template: Option<String>,
+
+    /// Set a template variable (repeatable)
+    #[arg(long = "var", value_name = "KEY=VALUE", requires = "template")]
+    pub(crate) vars: Vec<String>,
+
+    /// Post a JSON object from a file (`-` for stdin) verbatim, adding only the signature
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = [
+            "title", "content", "content_file", "stdin", "batch", "template", "keywords", "keyword",
+            "keywords_file", "keyword_link", "keyword_regex", "at", "at_all", "at_mobile", "image_key", "share_chat_id", "markdown",
+            "markdown_file"
+        ]
+    )]
+    pub(crate) raw_json: Option<PathBuf>,
+
+    /// Read message content from a file; each line becomes a paragraph
+    #[arg(long, conflicts_with = "content")]
+    pub(crate) content_file: Option<PathBuf>,
+
+    /// Keywords to highlight (comma separated); `keyword=url` makes a link
+    #[arg(short, long)]
+    pub(crate) keywords: Option<String>,
+
+    /// Read keywords from a file, one per line (`keyword=url` allowed; blank lines and `#` comments skipped)
+    #[arg(long, value_name = "PATH")]
+    pub(crate) keywords_file: Option<PathBuf>,
+
+    /// A keyword to highlight, optionally as `keyword=url` (repeatable, no comma splitting)
+    #[arg(long)]
+    pub(crate) keyword: Vec<String>,
+
+    /// Link a keyword to a URL, e.g. `error=https://wiki/err` (repeatable)
+    #[arg(long, value_name = "KEYWORD=URL")]
+    pub(crate) keyword_link: Vec<String>,
+
+    /// Highlight every match of a regex (repeatable); `PATTERN=URL` links matches,
+    /// with `$0`, `$1` or `${name}` in the URL replaced by the match or its groups
+    #[arg(long, value_name = "PATTERN[=URL]")]
+    pub(crate) keyword_regex: Vec<KeywordPattern>,
+
+    /// Mention a user by open_id (repeatable)
+    #[arg(long, visible_alias = "mention")]
+    pub(crate) at: Vec<String>,
+
+    /// Mention everyone in the chat
+    #[arg(long, visible_alias = "mention-all")]
+    pub(crate) at_all: bool,
+
+    /// Mention a user by the mobile number on their account (repeatable), looked
+    /// up through the open API (needs --app-id and --app-secret, or --tenant-token)
+    #[arg(long, value_name = "NUMBER", value_parser = parse_mobile, conflicts_with_all = ["batch", "pipe"])]
+    pub(crate) at_mobile: Vec<String>,
+
+    /// Put mentions before the content instead of on their own final line
+    #[arg(long)]
+    pub(crate) at_inline: bool,
+
+    /// Style the non-keyword text of post messages (repeatable)
+    #[arg(long, value_enum)]
+    pub(crate) style: Vec<TextStyle>,
+
+    /// Match keywords case-insensitively (Unicode case folding), keeping the content's casing
+    #[arg(long, visible_alias = "keywords-ignore-case")]
+    pub(crate) keyword_ci: bool,
+
+    /// Append the repository, ref, commit, actor and run link from GitHub Actions
+    #[arg(long)]
+    pub(crate) github: bool,
+
+    /// Append the project, ref, commit, job, user and pipeline link from GitLab CI
+    #[arg(long, conflicts_with = "github")]
+    pub(crate) gitlab: bool,
+
+    /// Make bare http(s) URLs in post and card content clickable
+    #[arg(long)]
+    pub(crate) autolink: bool,
+
+    /// Render the content as Markdown (links, lists, headings) in post messages
+    #[arg(long)]
+    pub(crate) markdown: bool,
+
+    /// Read the content from a Markdown file and render it (implies --markdown)
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["content", "content_file", "stdin", "template", "batch"])]
+    pub(crate) markdown_file: Option<PathBuf>,
+
+    /// Keep post content in a single paragraph instead of one paragraph per line
+    #[arg(long, conflicts_with_all = ["markdown", "markdown_file"])]
+    pub(crate) no_split_lines: bool,
+
+    /// Start a new paragraph at this separator instead of at every line break, e.g. `||`
+    #[arg(long, value_name = "SEP", conflicts_with_all = ["markdown", "markdown_file", "no_split_lines"])]
+    pub(crate) paragraph_sep: Option<String>,
+
+    /// Remove terminal colors and other escape sequences from the content
+    /// (always done for content read from stdin)
+    #[arg(long)]
+    pub(crate) strip_ansi: bool,
+
+    /// Replace `{{NAME}}` in the title and content with the environment variable NAME
+    #[arg(long)]
+    pub(crate) interpolate_env: bool,
+
+    /// Fail instead of leaving `{{NAME}}` as is when NAME is not set
+    #[arg(long, requires = "interpolate_env")]
+    pub(crate) strict_interpolate: bool,
+
+    /// Image key of an already uploaded image (for `--msg-type image`)
+    #[arg(long, conflicts_with_all = ["content", "content_file", "stdin", "keywords", "keyword", "keyword_link"])]
+    pub(crate) image_key: Option<String>,
+
+    /// Local image to upload through the open API and send as an image message,
+    /// or to add to the post when there is content (needs --app-id and --app-secret,
+    /// or --tenant-token)
+    #[arg(
+        long,
+        visible_alias = "image",
+        value_name = "PATH",
+        conflicts_with_all = ["image_key", "raw_json", "batch", "pipe"]
+    )]
+    pub(crate) image_file: Option<PathBuf>,
+
+    /// Send as the app's bot to this group chat (`oc_...`) through the open API
+    /// instead of a webhook (needs --app-id and --app-secret)
+    #[arg(
+        long,
+        value_name = "CHAT_ID",
+        conflicts_with_all = ["webhook_url", "secret", "secret_file", "secrets", "batch", "pipe", "check_clock"]
+    )]
+    pub(crate) chat_id: Option<String>,
+
+    /// Queue messages a webhook cannot be reached for in this directory, and send
+    /// queued ones first (also LARK_QUEUE_DIR)
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["chat_id", "batch", "pipe"])]
+    pub(crate) queue_dir: Option<PathBuf>,
+
+    /// Drop queued messages older than this, e.g. `24h` or `7d`
+    #[arg(long, value_name = "DURATION", default_value = "24h", value_parser = parse_duration)]
+    pub(crate) queue_max_age: Duration,
+
+    /// Keep at most this many queued messages, dropping the oldest
+    #[arg(long, value_name = "N", default_value_t = Queue::DEFAULT_MAX_COUNT)]
+    pub(crate) queue_max_count: usize,
+
+    /// App id for --image-file and --chat-id [env: LARK_APP_ID]
+    #[arg(long)]
+    pub(crate) app_id: Option<String>,
+
+    /// App secret for --image-file and --chat-id [env: LARK_APP_SECRET]
+    #[arg(long)]
+    pub(crate) app_secret: Option<String>,
+
+    /// Already obtained tenant access token, instead of --app-id and --app-secret
+    /// [env: LARK_TENANT_ACCESS_TOKEN]
+    #[arg(long, value_name = "TOKEN", conflicts_with_all = ["app_id", "app_secret"])]
+    pub(crate) tenant_token: Option<String>,
+
+    /// Open API base URL for --image-file and --chat-id [default: the region's host]
+    #[arg(long, value_name = "URL")]
+    pub(crate) api_base_url: Option<String>,
+
+    /// Message format
+    #[arg(short, long, visible_alias = "msg-type", value_enum, default_value_t = Format::Post)]
+    pub(crate) format: Format,
+
+    /// Largest JSON payload to send; see --on-oversize
+    #[arg(long, default_value_t = DEFAULT_MAX_BYTES)]
+    pub(crate) max_bytes: usize,
+
+    /// What to do with a message larger than --max-bytes
+    #[arg(long, value_enum, default_value_t = Oversize::Error)]
+    pub(crate) on_oversize: Oversize,
+
+    /// How to report the result on stdout
+    #[arg(long, value_enum, default_value_t = Output::Human)]
+    pub(crate) output: Output,
+
+    /// Open chat id (`oc_...`) of the group to share (for `--msg-type share-chat`)
+    #[arg(long, conflicts_with_all = ["content", "content_file", "stdin", "keywords", "keyword", "keyword_link", "image_key"])]
+    pub(crate) share_chat_id: Option<String>,
+
+    /// Retries on connection errors, 429 and 5xx responses
+    #[arg(long, default_value_t = 3)]
+    pub(crate) retries: u32,
+
+    /// Base delay in milliseconds for exponential retry backoff
+    #[arg(long, default_value_t = 500)]
+    pub(crate) retry_base_ms: u64,
+
+    /// Most requests per minute across all webhooks, retries included; 0 for no limit
+    #[arg(long, value_name = "N_PER_MIN", default_value_t = 60)]
+    pub(crate) rate_limit: u32,
+
+    /// Timeout in seconds for the whole request
+    #[arg(long, visible_alias = "timeout", default_value_t = 10)]
+    pub(crate) timeout_secs: u64,
+
+    /// Timeout in seconds for establishing the connection [default: --timeout]
+    #[arg(long)]
+    pub(crate) connect_timeout: Option<u64>,
+
+    /// Proxy URL (http://, https:// or socks5://); overrides HTTPS_PROXY/ALL_PROXY
+    #[arg(long)]
+    pub(crate) proxy: Option<String>,
+
+    /// Close pooled connections to Lark after this many idle seconds [default: 90]
+    #[arg(long, value_name = "SECS")]
+    pub(crate) pool_idle_secs: Option<u64>,
+
+    /// Accept any http(s) webhook URL, e.g. a self-hosted gateway or a test server
+    #[arg(long)]
+    pub(crate) allow_insecure_url: bool,
+
+    /// Deployment the webhooks belong to: cn (Feishu) or intl (Lark)
+    /// [default: detected from the webhook host]
+    #[arg(
+        long,
+        value_parser = PossibleValuesParser::new(["cn", "intl"]).map(|region| region.parse::<Region>().expect("a possible value"))
+    )]
+    pub(crate) region: Option<Region>,
+
+    /// Ignore proxies configured in the environment
+    #[arg(long, conflicts_with = "proxy")]
+    pub(crate) no_proxy: bool,
+
+    /// Also trust the root certificates in this PEM file, e.g. a TLS-inspecting
+    /// proxy's CA (also LARK_CA_CERT)
+    #[arg(long, value_name = "PEM_PATH")]
+    pub(crate) ca_cert: Option<PathBuf>,
+
+    /// Header color for card messages [default: blue, or the color of --level]
+    #[arg(long, visible_alias = "card-header-color", value_enum)]
+    pub(crate) card_color: Option<CardColor>,
+
+    /// Severity: prefixes post titles with an emoji and colors card headers
+    #[arg(long, value_enum)]
+    pub(crate) level: Option<Level>,
+
+    /// Post locales to fill (comma separated: zh_cn, en_us, ja_jp)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "zh_cn",
+        value_parser = PossibleValuesParser::new(["zh_cn", "en_us", "ja_jp"]).map(|locale| locale.parse::<Locale>().expect("a possible value"))
+    )]
+    pub(crate) locale: Vec<Locale>,
+
+    /// Post language shorthand; overrides --locale
+    #[arg(long, value_enum, conflicts_with = "locale")]
+    pub(crate) lang: Option<Lang>,
+
+    /// English title for the en_us locale (defaults to --title)
+    #[arg(long)]
+    pub(crate) title_en: Option<String>,
+
+    /// English content for the en_us locale (defaults to the main content)
+    #[arg(long)]
+    pub(crate) content_en: Option<String>,
+
+    /// Footer note shown at the bottom of card messages
+    #[arg(long)]
+    pub(crate) card_footer: Option<String>,
+
+    /// Send a card built in the Lark card builder, by its template id
+    #[arg(
+        long,
+        value_name = "TEMPLATE_ID",
+        conflicts_with_all = ["title", "content", "content_file", "markdown_file", "stdin", "image_key", "image_file", "share_chat_id"]
+    )]
+    pub(crate) card_template: Option<String>,
+
+    /// Set a --card-template variable, as `KEY=VALUE` (repeatable); values that
+    /// parse as JSON, like numbers and arrays, are sent as JSON, others as strings
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_card_var)]
+    pub(crate) card_var: Vec<(String, Value)>,
+
+    /// Add a button that opens a URL to card messages, as `Label=https://...` (repeatable)
+    #[arg(long, value_name = "LABEL=URL", value_parser = parse_button)]
+    pub(crate) button: Vec<CardButton>,
+
+    /// Send the content as a code block in a card, keeping indentation and line breaks
+    #[arg(long, conflicts_with_all = ["markdown", "markdown_file", "raw_json"])]
+    pub(crate) code: bool,
+
+    /// Language of the --code block, for syntax highlighting where Lark supports it
+    #[arg(long, value_name = "LANG", requires = "code")]
+    pub(crate) code_lang: Option<String>,
+
+    /// Send the content of a post verbatim, one row per line with indentation kept
+    /// and no keyword highlighting, e.g. for stack traces
+    #[arg(long, conflicts_with_all = ["code", "markdown", "markdown_file", "raw_json", "paragraph_sep", "no_split_lines"])]
+    pub(crate) code_block: bool,
+}
+
+/// Parse a `Label=URL` button, rejecting labels that are empty and URLs that are not http(s).
+fn parse_button(spec: &str) -> Result<CardButton, String> {
+    let (label, url) = spec
+        .split_once('=')
+        .ok_or_else(|| format!("expected LABEL=URL, got '{}'", spec))?;
+    if label.trim().is_empty() {
+        return Err(format!("button '{}' has no label", spec));
+    }
+    match Url::parse(url.trim()) {
+        Ok(parsed) if parsed.scheme() == "https" || parsed.scheme() == "http" => {
+            Ok(CardButton::new(label.trim(), url.trim()))
+        }
+        _ => Err(format!(
+            "button '{}' needs an http(s) URL, got '{}'",
+            label.trim(),
+            url
+        )),
+    }
+}
+
+/// Check an `--at-mobile` number loosely: digits with an optional leading `+`
+/// and any spaces, dashes, dots or parentheses, which are dropped.
+fn parse_mobile(spec: &str) -> Result<String, String> {
+    let number: String = spec
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')'))
+        .collect();
+    let digits = number.strip_prefix('+').unwrap_or(&number);
+    if (5..=15).contains(&digits.len()) && digits.chars().all(|c| c.is_ascii_digit()) {
+        Ok(number)
+    } else {
+        Err(format!(
+            "'{}' is not a mobile number; expected digits with an optional +country code, like +8613800000000",
+            spec
+        ))
+    }
+}
+
+/// Parse a duration like `30s`, `15m`, `2h` or `1h30m` (units `ms`, `s`, `m`,
+/// `h` and `d`); a bare number is seconds.
+fn parse_duration(spec: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "'{}' is not a duration; expected e.g. 30s, 15m, 2h or 1h30m",
+            spec
+        )
+    };
+    let spec = spec.trim();
+    let mut total = Duration::ZERO;
+    let mut rest = spec;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let count: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+        let unit = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let part = match &rest[..unit] {
+            "" if digits == spec.len() => Duration::from_secs(count),
+            "ms" => Duration::from_millis(count),
+            "s" => Duration::from_secs(count),
+            "m" => Duration::from_secs(count.saturating_mul(60)),
+            "h" => Duration::from_secs(count.saturating_mul(60 * 60)),
+            "d" => Duration::from_secs(count.saturating_mul(24 * 60 * 60)),
+            _ => return Err(invalid()),
+        };
+        total = total.saturating_add(part);
+        rest = &rest[unit..];
+    }
+    if total.is_zero() {
+        return Err(format!("'{}' is not a duration longer than zero", spec));
+    }
+    Ok(total)
+}
+
+/// Parse a `KEY=VALUE` card variable, keeping a VALUE that is valid JSON as JSON.
+fn parse_card_var(spec: &str) -> Result<(String, Value), String> {
+    let (key, value) = spec
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", spec))?;
+    if key.trim().is_empty() {
+        return Err(format!("card variable '{}' has no name", spec));
+    }
+    let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+    Ok((key.trim().to_string(), value))
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Oversize {
+    /// Fail, naming the payload size and the limit
+    Error,
+    /// Cut the message off, ending it with "(truncated, N bytes omitted)"
+    Truncate,
+    /// Send posts as several messages, "(1/3)" and so on
+    Split,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Output {
+    /// Human-readable status lines
+    Human,
+    /// One JSON object per target (JSON Lines)
+    Json,
+    /// A single JSON array with one object per target
+    JsonArray,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Format {
+    /// Rich text post with title and keyword highlighting
+    Post,
+    /// Plain text message
+    Text,
+    /// Interactive card with a colored header and markdown body
+    Card,
+    /// Image message referencing an uploaded image key
+    Image,
+    /// Share a group chat card
+    ShareChat,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Lang {
+    #[value(name = "zh_cn")]
+    ZhCn,
+    #[value(name = "en_us")]
+    EnUs,
+    /// Both zh_cn and en_us
+    Both,
+}
+
+impl Lang {
+    pub(crate) fn locales(self) -> Vec<Locale> {
+        match self {
+            Lang::ZhCn => vec![Locale::ZhCn],
+            Lang::EnUs => vec![Locale::EnUs],
+            Lang::Both => vec![Locale::ZhCn, Locale::EnUs],
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub(crate) enum TextStyle {
+    Bold,
+    Italic,
+    Underline,
+    LineThrough,
+}
+
+impl TextStyle {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            TextStyle::Bold => "bold",
+            TextStyle::Italic => "italic",
+            TextStyle::Underline => "underline",
+            TextStyle::LineThrough => "lineThrough",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Level {
+    Info,
+    Warning,
+    Error,
+    Critical,
+}
+
+impl Level {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Warning => "warning",
+            Level::Error => "error",
+            Level::Critical => "critical",
+        }
+    }
+
+    pub(crate) fn emoji(self) -> &'static str {
+        match self {
+            Level::Info => "ℹ️",
+            Level::Warning => "⚠️",
+            Level::Error => "🟠",
+            Level::Critical => "🔴",
+        }
+    }
+
+    pub(crate) fn color(self) -> CardColor {
+        match self {
+            Level::Info => CardColor::Blue,
+            Level::Warning => CardColor::Yellow,
+            Level::Error => CardColor::Orange,
+            Level::Critical => CardColor::Red,
+        }
+    }
+
+    /// The title emoji, as overridden by `profile`; `None` if set to be empty.
+    pub(crate) fn emoji_for(self, profile: &Profile) -> Option<String> {
+        let emoji = profile
+            .levels
+            .get(self.as_str())
+            .and_then(|style| style.emoji.clone());
+        Some(emoji.unwrap_or_else(|| self.emoji().to_string())).filter(|emoji| !emoji.is_empty())
+    }
+
+    /// The card header color, as overridden by `profile`.
+    pub(crate) fn color_for(self, profile: &Profile) -> Result<CardColor, NotifierError> {
+        match profile
+            .levels
+            .get(self.as_str())
+            .and_then(|style| style.color.as_deref())
+        {
+            Some(color) => CardColor::from_str(color, true).map_err(|_| {
+                NotifierError::Config(format!(
+                    "Invalid color '{}' for level {} in config",
+                    color,
+                    self.as_str()
+                ))
+            }),
+            None => Ok(self.color()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub(crate) enum CardColor {
+    Blue,
+    Wathet,
+    Turquoise,
+    Green,
+    Yellow,
+    Orange,
+    Red,
+    Carmine,
+    Violet,
+    Purple,
+    Indigo,
+    Grey,
+}
+
+impl CardColor {
+    pub(crate) fn template(self) -> &'static str {
+        match self {
+            CardColor::Blue => "blue",
+            CardColor::Wathet => "wathet",
+            CardColor::Turquoise => "turquoise",
+            CardColor::Green => "green",
+            CardColor::Yellow => "yellow",
+            CardColor::Orange => "orange",
+            CardColor::Red => "red",
+            CardColor::Carmine => "carmine",
+            CardColor::Violet => "violet",
+            CardColor::Purple => "purple",
+            CardColor::Indigo => "indigo",
+            CardColor::Grey => "grey",
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use futures::future::join_all;
+use futures::stream::{self, StreamExt};
+use lark_notifier::{LarkMessage, LarkNotifier, NotifierError};
+use serde::Deserialize;
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process;
+
+use crate::args::{Level, Output, SendArgs};
+use crate::compose::{build_message, fit_message};
+use crate::report::{Outcome, exit_code, print_outcomes, quiet, report, usage};
+use crate::send::deliver;
+
+/// One message in a `--batch` file.
+#[derive(Deserialize)]
+struct BatchEntry {
+    title: Option<String>,
+    content: String,
+    /// Overrides `--keywords` for this message.
+    keywords: Option<Vec<String>>,
+}
+
+/// Send every line of a JSONL batch file, reusing the notifiers' client.
+/// Failed lines are reported and skipped; the exit status reflects the first one.
+pub(crate) async fn run_batch(
+    args: SendArgs,
+    keywords: Vec<String>,
+    notifiers: &[LarkNotifier],
+    path: &Path,
+) -> Result<(), NotifierError> {
+    let text = if path == Path::new("-") {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text).map(|_| text)
+    } else {
+        fs::read_to_string(path)
+    }
+    .map_err(|source| NotifierError::Io {
+        context: format!("Failed to read batch file {}", path.display()),
+        source,
+    })?;
+
+    let jobs: Vec<(usize, Result<Vec<LarkMessage>, NotifierError>)> = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let messages = serde_json::from_str::<BatchEntry>(line)
+                .map_err(|e| usage(format!("Invalid batch entry: {}", e)))
+                .and_then(|entry| {
+                    let mut line_args = args.clone();
+                    line_args.title = entry.title.or(line_args.title);
+                    line_args.content = Some(entry.content);
+                    build_message(
+                        line_args,
+                        entry.keywords.unwrap_or_else(|| keywords.clone()),
+                    )
+                })
+                .and_then(|message| fit_message(message, args.max_bytes, args.on_oversize));
+            (index + 1, messages)
+        })
+        .collect();
+
+    if args.dry_run {
+        for (line, messages) in &jobs {
+            for notifier in notifiers {
+                eprintln!(
+                    "Line {}: would POST to {}",
+                    line,
+                    notifier.masked_webhook_url()
+                );
+                for message in messages
+                    .as_ref()
+                    .map_err(|err| usage(format!("line {}: {}", line, err)))?
+                {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&notifier.prepare(message.clone())?)?
+                    );
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    let results: Vec<_> = stream::iter(jobs)
+        .map(|(line, messages)| async move {
+            let results = match messages {
+                Ok(messages) => join_all(
+                    notifiers
+                        .iter()
+                        .map(|notifier| deliver(notifier, &messages)),
+                )
+                .await
+                .into_iter()
+                .zip(notifiers.iter().map(Some))
+                .map(|((result, latency), notifier)| (notifier, result, Some(latency)))
+                .collect(),
+                Err(err) => vec![(None, Err(err), None)],
+            };
+            (line, results)
+        })
+        .buffered(args.concurrency.max(1))
+        .collect()
+        .await;
+
+    let fan_out = notifiers.len() > 1;
+    let (mut sent, mut failed) = (0, 0);
+    let mut first_error = None;
+    let mut outcomes = Vec::new();
+    for (line, results) in &results {
+        for (notifier, result, latency) in results {
+            match args.output {
+                Output::Human => {
+                    let target = match notifier {
+                        Some(notifier) if fan_out => {
+                            format!("line {} {}", line, notifier.masked_webhook_url())
+                        }
+                        _ => format!("line {}", line),
+                    };
+                    report(Some(&target), result);
+                }
+                Output::Json | Output::JsonArray => {
+                    let target = notifier.map(LarkNotifier::masked_webhook_url);
+                    let mut outcome = Outcome::new(target, *latency, &result.as_ref());
+                    outcome.level = args.level.map(Level::as_str);
+                    outcome.line = Some(*line);
+                    outcomes.push(outcome);
+                }
+            }
+            match result {
+                Ok(_) => sent += 1,
+                Err(err) => {
+                    failed += 1;
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+    }
+    print_outcomes(args.output, &outcomes);
+
+    if args.output == Output::Human && !quiet() {
+        println!(
+            "Sent {} of {} messages ({} failed)",
+            sent,
+            sent + failed,
+            failed
+        );
+    }
+    if let Some(err) = first_error {
+        process::exit(exit_code(err));
+    }
+    Ok(())
+}
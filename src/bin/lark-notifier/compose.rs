@@ -0,0 +1,516 @@
+use lark_notifier::{
+    Highlighter, InteractiveCard, LarkMessage, LarkPost, LarkPostContent, LarkTextContent, Locale,
+    NotifierError, Template, elements_to_lark_md, interpolate_env, markdown_to_rows, payload_size,
+    split_message, strip_ansi, truncate_message,
+};
+use serde_json::Map;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::path::Path;
+
+use crate::args::{CardColor, Format, Oversize, SendArgs};
+use crate::report::usage;
+
+fn read_stdin() -> Result<String, NotifierError> {
+    if io::stdin().is_terminal() {
+        return Err(usage(
+            "Refusing to read content from a terminal; pipe it into stdin instead",
+        ));
+    }
+
+    let mut content = String::new();
+    io::stdin()
+        .read_to_string(&mut content)
+        .map_err(|source| NotifierError::Io {
+            context: "Failed to read stdin".to_string(),
+            source,
+        })?;
+
+    if content.trim().is_empty() {
+        return Err(usage("No content received on stdin"));
+    }
+
+    Ok(content.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Read one keyword spec per line, skipping blank lines and `#` comments.
+pub(crate) fn read_keywords_file(path: &Path) -> Result<Vec<String>, NotifierError> {
+    let content = fs::read_to_string(path).map_err(|source| NotifierError::Io {
+        context: format!("Failed to read keywords file {}", path.display()),
+        source,
+    })?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+fn read_content_file(path: &Path) -> Result<String, NotifierError> {
+    let bytes = fs::read(path).map_err(|source| NotifierError::Io {
+        context: format!("Failed to read content file {}", path.display()),
+        source,
+    })?;
+
+    let content = match String::from_utf8(bytes) {
+        Ok(content) => content,
+        Err(e) => {
+            warning!(
+                "{} is not valid UTF-8, replacing invalid bytes",
+                path.display()
+            );
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        }
+    };
+
+    if content.trim().is_empty() {
+        return Err(usage(format!("Content file {} is empty", path.display())));
+    }
+
+    Ok(content)
+}
+
+/// Load a `--raw-json` payload from a file, or stdin for `-`.
+pub(crate) fn read_raw_json(path: &Path) -> Result<LarkMessage, NotifierError> {
+    let json = if path == Path::new("-") {
+        read_stdin()?
+    } else {
+        fs::read_to_string(path).map_err(|source| NotifierError::Io {
+            context: format!("Failed to read raw JSON file {}", path.display()),
+            source,
+        })?
+    };
+    let value = serde_json::from_str(&json)
+        .map_err(|e| NotifierError::InvalidPayload(format!("{}: {}", path.display(), e)))?;
+    LarkMessage::raw(value)
+}
+
+/// Render the template `name` (a built-in or a file) with `KEY=VALUE` variables.
+pub(crate) fn render_template(
+    name: &str,
+    vars: &[String],
+) -> Result<(String, String), NotifierError> {
+    let path = Path::new(name);
+    let template = match Template::builtin(name) {
+        _ if path.exists() => Template::load(path)?,
+        Some(template) => template,
+        None => {
+            let builtins: Vec<&str> = Template::builtin_names().collect();
+            return Err(NotifierError::Template(format!(
+                "Template '{}' is neither a file nor a built-in ({})",
+                name,
+                builtins.join(", ")
+            )));
+        }
+    };
+
+    let mut values = BTreeMap::new();
+    for var in vars {
+        let (key, value) = var
+            .split_once('=')
+            .ok_or_else(|| usage(format!("--var expects KEY=VALUE, got '{}'", var)))?;
+        values.insert(key.trim().to_string(), value.to_string());
+    }
+    template.render(&values)
+}
+
+/// Read a secret from `reader`, dropping trailing whitespace and newlines.
+pub(crate) fn read_secret(mut reader: impl Read, source: &str) -> Result<String, NotifierError> {
+    let mut secret = String::new();
+    reader
+        .read_to_string(&mut secret)
+        .map_err(|e| NotifierError::Io {
+            context: format!("Failed to read secret from {}", source),
+            source: e,
+        })?;
+
+    let secret = secret.trim_end();
+    if secret.is_empty() {
+        return Err(usage(format!("Secret from {} is empty", source)));
+    }
+    Ok(secret.to_string())
+}
+
+/// Add `at` elements for `mentions`, on their own final row or before the first row's content.
+fn add_mentions(rows: &mut Vec<Vec<LarkTextContent>>, mentions: &[String], inline: bool) {
+    if mentions.is_empty() {
+        return;
+    }
+
+    let mut elements = Vec::new();
+    for user_id in mentions {
+        elements.push(LarkTextContent::at(user_id));
+        elements.push(LarkTextContent::text(" "));
+    }
+
+    if inline {
+        if rows.is_empty() {
+            rows.push(Vec::new());
+        }
+        rows[0].splice(0..0, elements);
+    } else {
+        elements.pop();
+        rows.push(elements);
+    }
+}
+
+/// Split `content` into its first non-blank line, as a title, and the rest.
+/// A Markdown heading loses its `#`s.
+pub(crate) fn title_from_content(content: &str, markdown: bool) -> Option<(String, String)> {
+    let content = content.trim_start();
+    let (first, rest) = content.split_once('\n').unwrap_or((content, ""));
+    let mut title = first.trim();
+    if markdown {
+        title = title.trim_start_matches('#').trim_start();
+    }
+    if title.is_empty() {
+        return None;
+    }
+    Some((
+        title.to_string(),
+        rest.trim_start_matches(['\r', '\n']).to_string(),
+    ))
+}
+
+/// Whether content was given, as opposed to a content-less message like an image.
+pub(crate) fn has_content(args: &SendArgs) -> bool {
+    args.content.is_some()
+        || args.content_file.is_some()
+        || args.markdown_file.is_some()
+        || args.stdin
+}
+
+pub(crate) fn build_message(
+    mut args: SendArgs,
+    keywords: Vec<String>,
+) -> Result<LarkMessage, NotifierError> {
+    let highlighter = Highlighter::parse(&keywords)
+        .patterns(args.keyword_regex.clone())
+        .ignore_case(args.keyword_ci)
+        .autolink(args.autolink)
+        .style(
+            args.style
+                .iter()
+                .map(|style| style.as_str().to_string())
+                .collect(),
+        );
+
+    if let Some(template_id) = args.card_template {
+        let mut variables = Map::new();
+        for (key, value) in args.card_var {
+            if variables.insert(key.clone(), value).is_some() {
+                warning!(
+                    "--card-var {} is given more than once; the last value is used",
+                    key
+                );
+            }
+        }
+        return Ok(LarkMessage::card_template(template_id, variables));
+    }
+    if !args.card_var.is_empty() {
+        return Err(usage("--card-var requires --card-template"));
+    }
+
+    // An uploaded --image-file is added to a post when there is content to go with it.
+    let image_post = args.image_file.is_some() && has_content(&args);
+    if args.format == Format::Image || (args.image_file.is_some() && !image_post) {
+        let image_key = args
+            .image_key
+            .ok_or_else(|| usage("--image-key is required for image messages"))?;
+        return Ok(LarkMessage::image(image_key));
+    }
+    if args.image_key.is_some() && !image_post {
+        return Err(usage("--image-key requires --msg-type image"));
+    }
+
+    if args.format == Format::ShareChat {
+        let chat_id = args
+            .share_chat_id
+            .ok_or_else(|| usage("--share-chat-id is required for share-chat messages"))?;
+        if !chat_id.starts_with("oc_") || chat_id.len() == "oc_".len() {
+            return Err(usage(format!(
+                "Invalid share chat id '{}': expected an open chat id like oc_xxx",
+                chat_id
+            )));
+        }
+        return Ok(LarkMessage::share_chat(chat_id));
+    }
+    if args.share_chat_id.is_some() {
+        return Err(usage("--share-chat-id requires --msg-type share-chat"));
+    }
+
+    let mut mentions: Vec<String> = args.at.clone();
+    if args.at_all {
+        mentions.push("all".to_string());
+    }
+
+    let markdown = args.markdown || args.markdown_file.is_some();
+    let content = match (args.content, args.content_file.or(args.markdown_file)) {
+        _ if args.stdin => strip_ansi(&read_stdin()?),
+        (Some(content), _) if content == "-" => strip_ansi(&read_stdin()?),
+        (Some(content), _) if args.strip_ansi => strip_ansi(&content),
+        (Some(content), _) => content,
+        (None, Some(path)) if args.strip_ansi => strip_ansi(&read_content_file(&path)?),
+        (None, Some(path)) => read_content_file(&path)?,
+        (None, None) => return Err(usage("--content, --content-file or --stdin is required")),
+    };
+    // Substitute before highlighting, so keywords match the final text.
+    let content = if args.interpolate_env {
+        let strict = args.strict_interpolate;
+        for text in [&mut args.title, &mut args.title_en, &mut args.content_en]
+            .into_iter()
+            .flatten()
+        {
+            *text = interpolate_env(text, strict)?;
+        }
+        interpolate_env(&content, strict)?
+    } else {
+        content
+    };
+
+    if !args.button.is_empty() && args.format != Format::Card {
+        warning!("--button is only used in card messages");
+    }
+
+    let format = match args.format {
+        format if !args.code => format,
+        Format::Post | Format::Card => Format::Card,
+        _ => return Err(usage("--code cannot be combined with --format text")),
+    };
+    if args.code_block && format != Format::Post {
+        return Err(usage(
+            "--code-block is for post messages; use --code for cards",
+        ));
+    }
+    if args.code_block && !highlighter.is_empty() {
+        warning!("--keywords is not applied inside --code-block content");
+    }
+    let (title, content) = match args.title {
+        None if args.title_from_content && format != Format::Text => {
+            let (title, body) = title_from_content(&content, markdown).ok_or_else(|| {
+                usage("--title-from-content needs content with a non-empty first line")
+            })?;
+            (
+                Some(format!(
+                    "{}{}",
+                    args.title_prefix.as_deref().unwrap_or_default(),
+                    title
+                )),
+                body,
+            )
+        }
+        title => (title, content),
+    };
+    let message = match format {
+        Format::Post => {
+            let mut title = title.ok_or_else(|| {
+                usage("--title (or --title-from-content) is required for post messages")
+            })?;
+            if let Some(emoji) = &args.level_emoji {
+                title = format!("{} {}", emoji, title);
+            }
+            let render = |content: &str| {
+                let mut rows = if args.code_block {
+                    code_rows(content)
+                } else if markdown {
+                    markdown_to_rows(content, &highlighter)
+                } else if args.no_split_lines {
+                    vec![highlighter.highlight(content)]
+                } else if let Some(sep) =
+                    args.paragraph_sep.as_deref().filter(|sep| !sep.is_empty())
+                {
+                    highlighter.highlight_each(content.split(sep).map(str::trim))
+                } else {
+                    highlighter.highlight_lines(content)
+                };
+                if let Some(image_key) = &args.image_key {
+                    rows.push(vec![LarkTextContent::image(image_key)]);
+                }
+                if let Some(footer) = &args.ci_footer {
+                    rows.push(footer.row());
+                }
+                add_mentions(&mut rows, &mentions, args.at_inline);
+                rows
+            };
+
+            let locales = match args.lang {
+                Some(lang) => lang.locales(),
+                None => args.locale,
+            };
+            let has_english = args.title_en.is_some() || args.content_en.is_some();
+            if has_english && !locales.contains(&Locale::EnUs) {
+                return Err(usage("--title-en/--content-en require the en_us locale"));
+            }
+
+            let mut post = LarkPost::default();
+            for locale in locales {
+                let localized = match locale {
+                    Locale::EnUs if has_english => LarkPostContent {
+                        title: args.title_en.clone().unwrap_or_else(|| title.clone()),
+                        content: render(args.content_en.as_deref().unwrap_or(&content)),
+                    },
+                    _ => LarkPostContent {
+                        title: title.clone(),
+                        content: render(&content),
+                    },
+                };
+                post.set(locale, localized);
+            }
+            LarkMessage::from_post(post)
+        }
+        Format::Text => {
+            if !highlighter.is_empty() {
+                warning!("--keywords is ignored for text messages");
+            }
+            if !args.style.is_empty() {
+                warning!("--style is ignored for text messages");
+            }
+            let mut content = match &args.level_emoji {
+                Some(emoji) => format!("{} {}", emoji, content),
+                None => content,
+            };
+            if let Some(footer) = &args.ci_footer {
+                content = format!("{}\n{}", content, footer.to_text());
+            }
+            let tags: Vec<String> = mentions
+                .iter()
+                .map(|id| format!("<at user_id=\"{}\"></at>", id))
+                .collect();
+            match (tags.is_empty(), args.at_inline) {
+                (true, _) => LarkMessage::text(content),
+                (false, true) => LarkMessage::text(format!("{} {}", tags.join(" "), content)),
+                (false, false) => LarkMessage::text(format!("{}\n{}", content, tags.join(" "))),
+            }
+        }
+        Format::Card => {
+            let title = title.ok_or_else(|| {
+                usage("--title (or --title-from-content) is required for card messages")
+            })?;
+            if !args.style.is_empty() {
+                warning!("--style is ignored for card messages");
+            }
+            let tags: Vec<String> = mentions
+                .iter()
+                .map(|id| format!("<at id={}></at>", id))
+                .collect();
+            let card_color = args.card_color.unwrap_or(CardColor::Blue);
+            let card = |mut markdown: String| {
+                if let Some(footer) = &args.ci_footer {
+                    markdown = format!("{}\n{}", markdown, elements_to_lark_md(&footer.row()));
+                }
+                let markdown = match (tags.is_empty(), args.at_inline) {
+                    (true, _) => markdown,
+                    (false, true) => format!("{} {}", tags.join(" "), markdown),
+                    (false, false) => format!("{}\n{}", markdown, tags.join(" ")),
+                };
+                let mut card = InteractiveCard::new(title.clone(), card_color.template(), markdown)
+                    .with_buttons(args.button.clone());
+                if let Some(footer) = &args.card_footer {
+                    card = card.with_note(footer.clone());
+                }
+                LarkMessage::card(card)
+            };
+
+            if args.code {
+                if !highlighter.is_empty() {
+                    warning!("--keywords is not applied inside --code blocks");
+                }
+                let lang = args.code_lang.as_deref().unwrap_or("");
+                let code = content.trim_end_matches(['\r', '\n']);
+                let code = truncate_middle(code, |code| {
+                    payload_size(&card(fence_code(code, lang))) <= args.max_bytes
+                });
+                card(fence_code(&code, lang))
+            } else {
+                let highlighter = highlighter.style(Vec::new());
+                card(elements_to_lark_md(&highlighter.highlight(&content)))
+            }
+        }
+        Format::Image | Format::ShareChat => unreachable!("content-less messages are built above"),
+    };
+
+    Ok(message)
+}
+
+/// Apply `on_oversize` to a message whose payload exceeds `max_bytes`, failing
+/// if any message to send is still too large.
+pub(crate) fn fit_message(
+    message: LarkMessage,
+    max_bytes: usize,
+    on_oversize: Oversize,
+) -> Result<Vec<LarkMessage>, NotifierError> {
+    let messages = match on_oversize {
+        Oversize::Error => vec![message],
+        Oversize::Truncate => vec![truncate_message(message, max_bytes)],
+        Oversize::Split => split_message(message, max_bytes),
+    };
+    match messages
+        .iter()
+        .map(payload_size)
+        .find(|&size| size > max_bytes)
+    {
+        Some(size) => Err(NotifierError::TooLarge {
+            size,
+            max: max_bytes,
+        }),
+        None => Ok(messages),
+    }
+}
+
+/// One plain row per line of `code`. Leading spaces and tabs become
+/// non-breaking spaces, which Lark does not strip from the start of a row.
+fn code_rows(code: &str) -> Vec<Vec<LarkTextContent>> {
+    code.trim_end_matches(['\r', '\n'])
+        .lines()
+        .map(|line| {
+            let body = line.trim_start_matches([' ', '\t']);
+            let indent: String = line[..line.len() - body.len()]
+                .chars()
+                .map(|c| {
+                    if c == '\t' {
+                        "\u{a0}".repeat(4)
+                    } else {
+                        "\u{a0}".to_string()
+                    }
+                })
+                .collect();
+            let line = format!("{}{}", indent, body.trim_end());
+            if line.is_empty() {
+                Vec::new()
+            } else {
+                vec![LarkTextContent::text(line)]
+            }
+        })
+        .collect()
+}
+
+/// Wrap `code` in a Markdown fence longer than any run of backticks inside it.
+fn fence_code(code: &str, lang: &str) -> String {
+    let longest = code.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{fence}{lang}\n{code}\n{fence}")
+}
+
+/// Drop lines from the middle of `code` until `fits` accepts it, marking the gap.
+fn truncate_middle(code: &str, fits: impl Fn(&str) -> bool) -> String {
+    if fits(code) {
+        return code.to_string();
+    }
+    let lines: Vec<&str> = code.lines().collect();
+    let keeping = |keep: usize| {
+        let marker = format!("... {} lines omitted ...", lines.len() - keep);
+        let tail = lines.len() - keep / 2;
+        let mut kept: Vec<&str> = lines[..keep.div_ceil(2)].to_vec();
+        kept.push(&marker);
+        kept.extend_from_slice(&lines[tail..]);
+        kept.join("\n")
+    };
+    let keep = (0..lines.len())
+        .collect::<Vec<_>>()
+        .partition_point(|&keep| fits(&keeping(keep)));
+    warning!("code block is too large, omitting lines from the middle");
+    keeping(keep.saturating_sub(1))
+}
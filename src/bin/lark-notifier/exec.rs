@@ -0,0 +1,170 @@
+use lark_notifier::{NotifierError, Tail, strip_ansi};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::process::{self, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::args::{CardColor, ExecOptions, SendArgs};
+use crate::compose::has_content;
+use crate::report::{EXIT_FAILURE, usage};
+
+/// Run the `exec` command, passing its output through, and fill in the title and
+/// content reporting how it went. Returns its exit status; a command killed by a
+/// signal gets 128 plus the signal number, and one that cannot be started 127
+/// (not found) or 126, as in shells.
+pub(crate) async fn run_command(
+    args: &mut SendArgs,
+    options: &ExecOptions,
+) -> Result<i32, NotifierError> {
+    if has_content(args) {
+        return Err(usage(
+            "exec sends the command's output; it cannot be combined with --content and the like",
+        ));
+    }
+    let command_line: Vec<String> = options.command.iter().map(|arg| shell_quote(arg)).collect();
+    let command_line = command_line.join(" ");
+    let tail = RefCell::new(Tail::new(options.tail_lines));
+    let started = Instant::now();
+    let spawned = tokio::process::Command::new(&options.command[0])
+        .args(&options.command[1..])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let status = match spawned {
+        Ok(mut child) => {
+            let stdout = child.stdout.take().expect("command stdout is piped");
+            let stderr = child.stderr.take().expect("command stderr is piped");
+            // Both streams are drained before waiting, so a chatty command never blocks on a full pipe.
+            let (_, _, status) = tokio::join!(
+                pump(stdout, io::stdout(), &tail),
+                pump(stderr, io::stderr(), &tail),
+                child.wait(),
+            );
+            match status {
+                Ok(status) => exit_status(status),
+                Err(err) => {
+                    warning!("failed to wait for {}: {}", options.command[0], err);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        Err(err) => {
+            let message = format!("failed to start {}: {}", options.command[0], err);
+            eprintln!("Error: {}", message);
+            tail.borrow_mut().push(message.as_bytes());
+            if err.kind() == io::ErrorKind::NotFound {
+                127
+            } else {
+                126
+            }
+        }
+    };
+    let elapsed = started.elapsed();
+
+    let (emoji, outcome, color) = match status {
+        0 => ("✅", "Succeeded in", CardColor::Green),
+        _ => ("❌", "Failed after", CardColor::Red),
+    };
+    let title = match args.title.take() {
+        Some(title) => title,
+        None => format!(
+            "{}{}",
+            args.title_prefix.take().unwrap_or_default(),
+            command_line
+        ),
+    };
+    args.title = Some(format!("{} {}", emoji, title));
+    args.card_color.get_or_insert(color);
+
+    let tail = tail.into_inner();
+    let mut content = format!(
+        "{} {} (exit code {})\n$ {}",
+        outcome,
+        format_elapsed(elapsed),
+        status,
+        command_line
+    );
+    let shown = tail.lines().count() as u64;
+    if shown > 0 {
+        if shown == tail.total() {
+            content.push_str("\n\nOutput:");
+        } else {
+            content.push_str(&format!(
+                "\n\nLast {} of {} lines of output:",
+                shown,
+                tail.total()
+            ));
+        }
+        for line in tail.lines() {
+            content.push('\n');
+            content.push_str(&strip_ansi(line));
+        }
+    }
+    args.content = Some(content);
+    Ok(status)
+}
+
+/// Copy `reader` to `out` as it arrives, adding each line to `tail`. Lines are
+/// only kept up to what `tail` stores, so memory stays bounded.
+async fn pump(reader: impl AsyncRead + Unpin, mut out: impl Write, tail: &RefCell<Tail>) {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    let keep = |line: &mut Vec<u8>, bytes: &[u8]| {
+        // One byte over the limit, so the tail knows the line was cut.
+        let room = (Tail::MAX_LINE_BYTES + 1).saturating_sub(line.len());
+        line.extend_from_slice(&bytes[..bytes.len().min(room)]);
+    };
+    loop {
+        let buf = match reader.fill_buf().await {
+            Ok([]) | Err(_) => break,
+            Ok(buf) => buf,
+        };
+        // The command's output still goes where it would have; a closed stdout is not its problem.
+        let _ = out.write_all(buf).and_then(|_| out.flush());
+        let mut rest = buf;
+        while let Some(end) = rest.iter().position(|&byte| byte == b'\n') {
+            keep(&mut line, &rest[..end]);
+            tail.borrow_mut().push(&line);
+            line.clear();
+            rest = &rest[end + 1..];
+        }
+        keep(&mut line, rest);
+        let read = buf.len();
+        reader.consume(read);
+    }
+    if !line.is_empty() {
+        tail.borrow_mut().push(&line);
+    }
+}
+
+/// `arg` as it would be typed in a shell: single-quoted unless it is plain.
+fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// The shell-style exit status of a finished command.
+pub(crate) fn exit_status(status: process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal;
+    }
+    status.code().unwrap_or(EXIT_FAILURE)
+}
+
+/// A duration for people: `850ms`, `12.3s`, `4m 05s` or `2h 03m`.
+pub(crate) fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    match secs {
+        0 => format!("{}ms", elapsed.as_millis()),
+        1..60 => format!("{:.1}s", elapsed.as_secs_f64()),
+        60..3600 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
@@ -0,0 +1,129 @@
+use lark_notifier::{LarkNotifier, NotifierError};
+
+use std::process::{self, Stdio};
+use std::time::Instant;
+
+use crate::args::{HeartbeatOptions, Output, SendArgs};
+use crate::compose::{build_message, has_content};
+use crate::exec::{exit_status, format_elapsed};
+use crate::report::{exit_code, quiet, usage};
+use crate::send::{Tally, send_tallied};
+use crate::serve::shutdown_signal;
+
+/// Send a heartbeat every `--interval` for the `heartbeat` subcommand, the first
+/// right away, until SIGINT, SIGTERM or `--max-count`. Each one is built anew,
+/// so it is signed afresh; without content it says how long it has been
+/// running. Failed sends are reported and skipped, and the exit status
+/// reflects the first failure.
+pub(crate) async fn run_heartbeat(
+    args: SendArgs,
+    keywords: Vec<String>,
+    notifiers: &[LarkNotifier],
+    options: HeartbeatOptions,
+) -> Result<(), NotifierError> {
+    if args.pipe || args.batch.is_some() || args.stdin || args.content.as_deref() == Some("-") {
+        return Err(usage(
+            "heartbeat builds each message itself and cannot read stdin or --batch",
+        ));
+    }
+    if args.image_file.is_some() || !args.at_mobile.is_empty() || args.queue_dir.is_some() {
+        return Err(usage(
+            "--image-file, --at-mobile and --queue-dir are not supported by heartbeat",
+        ));
+    }
+    let default_content = !has_content(&args) && options.content_cmd.is_none();
+    // Fail now on arguments that could never make a message.
+    let mut probe = args.clone();
+    probe.content = probe.content.or_else(|| Some("heartbeat".to_string()));
+    build_message(probe, keywords.clone())?;
+
+    let started = Instant::now();
+    let mut ticker = tokio::time::interval(options.interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut shutdown = tokio::spawn(shutdown_signal());
+    let mut count = 0;
+    let mut tally = Tally::default();
+    while options.max_count.is_none_or(|max| count < max) {
+        tokio::select! {
+            biased;
+            _ = &mut shutdown => break,
+            _ = ticker.tick() => {}
+        }
+        count += 1;
+        let mut beat = args.clone();
+        if let Some(command) = &options.content_cmd {
+            beat.content = Some(content_from_command(command).await);
+            beat.strip_ansi = true;
+        } else if default_content {
+            beat.content = Some(format!(
+                "Heartbeat {}, running for {}",
+                count,
+                format_elapsed(started.elapsed())
+            ));
+        }
+        let failed = tally.failed;
+        send_tallied(beat, &keywords, notifiers, &mut tally).await?;
+        if tally.failed > failed && options.max_count.is_none_or(|max| count < max) {
+            warning!(
+                "Heartbeat {} was not delivered; trying again in {}",
+                count,
+                format_elapsed(options.interval)
+            );
+        }
+    }
+
+    if args.output == Output::Human && !args.dry_run && !quiet() {
+        println!(
+            "Sent {} of {} heartbeats ({} failed)",
+            tally.sent,
+            tally.sent + tally.failed,
+            tally.failed
+        );
+    }
+    if let Some(err) = tally.first_error {
+        process::exit(exit_code(&err));
+    }
+    Ok(())
+}
+
+/// The output of `command` run by the shell, for `--content-cmd`; its stderr
+/// passes through. A command that fails is warned about, and one that cannot
+/// be run is sent as the content.
+async fn content_from_command(command: &str) -> String {
+    #[cfg(unix)]
+    let mut shell = tokio::process::Command::new("sh");
+    #[cfg(unix)]
+    shell.arg("-c");
+    #[cfg(not(unix))]
+    let mut shell = tokio::process::Command::new("cmd");
+    #[cfg(not(unix))]
+    shell.arg("/C");
+    match shell
+        .arg(command)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .await
+    {
+        Ok(output) => {
+            if !output.status.success() {
+                warning!(
+                    "--content-cmd exited with status {}",
+                    exit_status(output.status)
+                );
+            }
+            let content = String::from_utf8_lossy(&output.stdout)
+                .trim_end()
+                .to_string();
+            if content.is_empty() {
+                "(no output)".to_string()
+            } else {
+                content
+            }
+        }
+        Err(err) => {
+            warning!("--content-cmd could not be run: {}", err);
+            format!("--content-cmd could not be run: {}", err)
+        }
+    }
+}
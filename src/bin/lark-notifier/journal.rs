@@ -0,0 +1,120 @@
+use lark_notifier::{JournalEntry, LarkNotifier, NotifierError};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use std::process::{self};
+use std::time::Duration;
+
+use crate::args::{JournalOptions, Output, SendArgs};
+use crate::report::{exit_code, quiet};
+use crate::send::{Tally, send_piped};
+
+/// How long to wait before restarting journalctl after it exits.
+const JOURNAL_RESTART_DELAY: Duration = Duration::from_secs(5);
+
+/// Follow the journal for the `journal` subcommand, sending the matching entries
+/// collected every `--interval`. journalctl is restarted after the last entry
+/// seen if it exits, e.g. when journald restarts; Ctrl-C sends what is pending.
+pub(crate) async fn run_journal(
+    mut args: SendArgs,
+    keywords: Vec<String>,
+    notifiers: &[LarkNotifier],
+    options: JournalOptions,
+) -> Result<(), NotifierError> {
+    if args.title.is_none() {
+        let units = if options.unit.is_empty() {
+            "all units".to_string()
+        } else {
+            options.unit.join(", ")
+        };
+        args.title = Some(format!("Journal: {}", units));
+    }
+
+    let interval = Duration::from_secs(options.interval.max(1));
+    let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+    let mut cursor: Option<String> = None;
+    let mut pending: Vec<String> = Vec::new();
+    let mut tally = Tally::default();
+    let interrupted = tokio::signal::ctrl_c();
+    tokio::pin!(interrupted);
+
+    'follow: loop {
+        let mut child = spawn_journalctl(&options, cursor.as_deref())?;
+        let stdout = child.stdout.take().expect("journalctl stdout is piped");
+        let mut lines = BufReader::new(stdout).lines();
+        loop {
+            tokio::select! {
+                line = lines.next_line() => match line {
+                    Ok(Some(line)) => {
+                        let Some(entry) = JournalEntry::parse(&line) else { continue };
+                        cursor = entry.cursor.clone().or(cursor);
+                        if options.grep.as_ref().is_none_or(|grep| grep.is_match(&entry.message)) {
+                            pending.push(entry.to_line());
+                        }
+                    }
+                    Ok(None) | Err(_) => break,
+                },
+                _ = ticker.tick(), if !pending.is_empty() => {
+                    send_piped(&args, &keywords, notifiers, std::mem::take(&mut pending), &mut tally).await?;
+                }
+                _ = &mut interrupted => {
+                    let _ = child.kill().await;
+                    break 'follow;
+                }
+            }
+        }
+
+        let status = child.wait().await;
+        warning!(
+            "journalctl exited ({}), restarting in {:?}",
+            status.map_or_else(|e| e.to_string(), |status| status.to_string()),
+            JOURNAL_RESTART_DELAY
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(JOURNAL_RESTART_DELAY) => {}
+            _ = &mut interrupted => break 'follow,
+        }
+    }
+    if !pending.is_empty() {
+        send_piped(&args, &keywords, notifiers, pending, &mut tally).await?;
+    }
+
+    if args.output == Output::Human && !args.dry_run && !quiet() {
+        println!(
+            "Sent {} of {} messages ({} failed)",
+            tally.sent,
+            tally.sent + tally.failed,
+            tally.failed
+        );
+    }
+    if let Some(err) = tally.first_error {
+        process::exit(exit_code(&err));
+    }
+    Ok(())
+}
+
+/// Start `journalctl -f -o json`, resuming after `cursor` or with new entries only.
+fn spawn_journalctl(
+    options: &JournalOptions,
+    cursor: Option<&str>,
+) -> Result<tokio::process::Child, NotifierError> {
+    let mut command = tokio::process::Command::new("journalctl");
+    command.args(["--follow", "--output=json", "--no-pager"]);
+    match cursor {
+        Some(cursor) => command.arg(format!("--after-cursor={}", cursor)),
+        None => command.arg("--lines=0"),
+    };
+    for unit in &options.unit {
+        command.arg(format!("--unit={}", unit));
+    }
+    if let Some(priority) = &options.priority {
+        command.arg(format!("--priority={}", priority));
+    }
+    command
+        .stdout(process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|source| NotifierError::Io {
+            context: "Failed to run journalctl".to_string(),
+            source,
+        })
+}
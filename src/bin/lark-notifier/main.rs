@@ -0,0 +1,169 @@
+#[macro_use]
+mod report;
+mod args;
+mod batch;
+mod compose;
+mod exec;
+mod heartbeat;
+#[cfg(all(feature = "journal", target_os = "linux"))]
+mod journal;
+mod pipe;
+mod profile;
+mod send;
+mod serve;
+
+use clap::{CommandFactory, Parser, Subcommand};
+
+use std::env;
+use std::io;
+use std::process;
+use std::sync::atomic::Ordering;
+
+#[cfg(all(feature = "journal", target_os = "linux"))]
+use crate::args::JournalArgs;
+use crate::args::{
+    CompletionsArgs, ExecArgs, Format, HeartbeatArgs, Level, Output, SendArgs, ServeArgs,
+    ValidateConfigArgs,
+};
+use crate::profile::validate_config;
+use crate::report::{Outcome, QUIET, exit_code, init_tracing, name_flags, print_outcomes};
+use crate::send::run;
+use crate::serve::serve;
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    send: SendArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Send a notification (the default when no subcommand is given)
+    Send(SendArgs),
+    /// Send an interactive card (same as `send --format card`)
+    Card(SendArgs),
+    /// Send a connectivity test message and report whether the webhook and secret are accepted
+    Test(SendArgs),
+    /// Check the config file and the webhook URLs of every profile
+    ValidateConfig(ValidateConfigArgs),
+    /// Accept notifications as JSON over local HTTP and forward them to Lark
+    Serve(ServeArgs),
+    /// Follow the systemd journal and send matching entries, batched
+    ///
+    /// Run it as a service, e.g. in /etc/systemd/system/lark-journal.service:
+    ///
+    ///   [Service]
+    ///   Environment=LARK_WEBHOOK_URL=https://open.feishu.cn/open-apis/bot/v2/hook/xxx
+    ///   ExecStart=/usr/local/bin/lark-notifier journal --unit nginx --priority err
+    ///   Restart=on-failure
+    #[cfg(all(feature = "journal", target_os = "linux"))]
+    #[command(verbatim_doc_comment)]
+    Journal(JournalArgs),
+    /// Run a command and send its status, duration and the end of its output
+    ///
+    /// e.g. `lark-notifier exec -t "Nightly backup" -- /usr/local/bin/backup.sh --full`.
+    /// The output is passed through as it arrives, and lark-notifier exits with
+    /// the command's status.
+    #[command(verbatim_doc_comment)]
+    Exec(ExecArgs),
+    /// Send a message every `--interval` as a liveness signal, until stopped
+    ///
+    /// e.g. `lark-notifier heartbeat --interval 15m -t "Batch worker alive" --content-cmd uptime`.
+    /// Failed sends are reported and the next one is tried on schedule; SIGINT
+    /// and SIGTERM stop it once a send in flight has finished.
+    #[command(verbatim_doc_comment)]
+    Heartbeat(HeartbeatArgs),
+    /// Send the messages --queue-dir (or LARK_QUEUE_DIR) queued while the webhook was unreachable
+    ///
+    /// e.g. `lark-notifier flush --queue-dir /var/spool/lark-notifier` from cron. The
+    /// webhook and secret options are those of a send; messages queued for other
+    /// webhooks stay queued.
+    #[command(verbatim_doc_comment)]
+    Flush(SendArgs),
+    /// Print a shell completion script to stdout
+    ///
+    /// e.g. `lark-notifier completions bash > /etc/bash_completion.d/lark-notifier`
+    /// or `lark-notifier completions zsh > "${fpath[1]}/_lark-notifier"`
+    Completions(CompletionsArgs),
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let args = match cli.command {
+        Some(Command::Send(args)) => args,
+        Some(Command::Card(args)) => SendArgs {
+            format: Format::Card,
+            ..args
+        },
+        Some(Command::Test(args)) => SendArgs {
+            connectivity_test: true,
+            format: Format::Text,
+            content: Some("lark-notifier connectivity test".to_string()),
+            ..args
+        },
+        #[cfg(all(feature = "journal", target_os = "linux"))]
+        Some(Command::Journal(args)) => SendArgs {
+            journal: Some(args.journal),
+            ..args.send
+        },
+        Some(Command::Exec(args)) => SendArgs {
+            exec: Some(args.exec),
+            ..args.send
+        },
+        Some(Command::Flush(args)) => SendArgs {
+            flush: true,
+            ..args
+        },
+        Some(Command::Heartbeat(args)) => SendArgs {
+            heartbeat: Some(args.heartbeat),
+            ..args.send
+        },
+        Some(Command::Serve(args)) => {
+            init_tracing(args.verbose, false);
+            if let Err(err) = serve(args).await.map_err(name_flags) {
+                eprintln!("Error: {}", err);
+                process::exit(exit_code(&err));
+            }
+            return;
+        }
+        Some(Command::Completions(args)) => {
+            clap_complete::generate(
+                args.shell,
+                &mut Cli::command(),
+                env!("CARGO_BIN_NAME"),
+                &mut io::stdout(),
+            );
+            return;
+        }
+        Some(Command::ValidateConfig(args)) => {
+            if let Err(err) = validate_config(args.config.as_deref(), args.allow_insecure_url) {
+                eprintln!("Error: {}", err);
+                process::exit(exit_code(&err));
+            }
+            return;
+        }
+        None => cli.send,
+    };
+
+    QUIET.store(args.quiet, Ordering::Relaxed);
+    init_tracing(args.verbose, args.quiet);
+    let output = args.output;
+    let level = args.level;
+    if let Err(err) = run(args).await.map_err(name_flags) {
+        match output {
+            Output::Human => eprintln!("Error: {}", err),
+            Output::Json | Output::JsonArray => {
+                let mut outcome = Outcome::new(None, None, &Err(&err));
+                outcome.level = level.map(Level::as_str);
+                print_outcomes(output, &[outcome]);
+            }
+        }
+        process::exit(exit_code(&err));
+    }
+}
@@ -0,0 +1,77 @@
+use lark_notifier::{LarkNotifier, NotifierError};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use std::io::{self, IsTerminal};
+use std::process;
+use std::time::Duration;
+
+use crate::args::{Output, SendArgs};
+use crate::report::{exit_code, quiet, usage};
+use crate::send::{Tally, send_piped};
+
+/// Send stdin as it arrives for `--pipe`: each line on its own, or up to
+/// `--pipe-batch` lines together, sent early once `--pipe-interval` passes.
+/// Failed sends are reported and skipped; pending lines are sent on EOF and
+/// Ctrl-C, and the exit status reflects the first failure.
+pub(crate) async fn run_pipe(
+    args: SendArgs,
+    keywords: Vec<String>,
+    notifiers: &[LarkNotifier],
+) -> Result<(), NotifierError> {
+    if io::stdin().is_terminal() {
+        return Err(usage(
+            "--pipe reads lines from stdin; pipe something into it",
+        ));
+    }
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let batch = args.pipe_batch.max(1);
+    let interval = args.pipe_interval.map(Duration::from_secs);
+    let mut pending: Vec<String> = Vec::new();
+    let mut deadline = tokio::time::Instant::now();
+    let mut tally = Tally::default();
+    let interrupted = tokio::signal::ctrl_c();
+    tokio::pin!(interrupted);
+
+    loop {
+        tokio::select! {
+            line = lines.next_line() => match line {
+                Ok(Some(line)) if line.trim().is_empty() => {}
+                Ok(Some(line)) => {
+                    if let (true, Some(interval)) = (pending.is_empty(), interval) {
+                        deadline = tokio::time::Instant::now() + interval;
+                    }
+                    pending.push(line);
+                    if pending.len() >= batch {
+                        send_piped(&args, &keywords, notifiers, std::mem::take(&mut pending), &mut tally).await?;
+                    }
+                }
+                Ok(None) => break,
+                Err(source) => {
+                    tally.record(Err(NotifierError::Io { context: "Failed to read stdin".to_string(), source }));
+                    break;
+                }
+            },
+            _ = tokio::time::sleep_until(deadline), if interval.is_some() && !pending.is_empty() => {
+                send_piped(&args, &keywords, notifiers, std::mem::take(&mut pending), &mut tally).await?;
+            }
+            _ = &mut interrupted => break,
+        }
+    }
+    if !pending.is_empty() {
+        send_piped(&args, &keywords, notifiers, pending, &mut tally).await?;
+    }
+
+    if args.output == Output::Human && !args.dry_run && !quiet() {
+        println!(
+            "Sent {} of {} messages ({} failed)",
+            tally.sent,
+            tally.sent + tally.failed,
+            tally.failed
+        );
+    }
+    if let Some(err) = tally.first_error {
+        process::exit(exit_code(&err));
+    }
+    Ok(())
+}
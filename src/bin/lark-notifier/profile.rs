@@ -0,0 +1,101 @@
+use lark_notifier::{Config, NotifierError, Profile, validate_webhook_url};
+
+use std::env;
+use std::path::Path;
+
+use crate::report::name_flags;
+
+pub(crate) fn get_env_or_arg(arg: Option<String>, env_name: &str) -> Result<String, NotifierError> {
+    match arg {
+        Some(value) => Ok(value),
+        None => match env::var(env_name) {
+            Ok(value) => Ok(value),
+            Err(_) => Err(NotifierError::MissingConfig(env_name.to_string())),
+        },
+    }
+}
+
+/// Resolve the profile to use. An explicit `--config` or `--profile` must
+/// resolve; the default config file and `default` profile are optional.
+pub(crate) fn load_profile(
+    config: Option<&Path>,
+    profile: Option<&str>,
+) -> Result<Profile, NotifierError> {
+    let path = match config {
+        Some(path) => path.to_path_buf(),
+        None => match Config::default_path().filter(|path| path.exists()) {
+            Some(path) => path,
+            None if profile.is_some() => {
+                return Err(NotifierError::Config(
+                    "--profile requires a config file (--config or ~/.config/lark-notifier/config.toml)".to_string(),
+                ));
+            }
+            None => return Ok(Profile::default()),
+        },
+    };
+
+    let config = Config::load(&path)?;
+    match profile {
+        Some(name) => Ok(config.profile(name)?.clone()),
+        None => Ok(config.profiles.get("default").cloned().unwrap_or_default()),
+    }
+}
+
+/// Load the config file and check every profile's webhook URLs, printing one
+/// line per profile.
+pub(crate) fn validate_config(
+    path: Option<&Path>,
+    allow_insecure_url: bool,
+) -> Result<(), NotifierError> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => Config::default_path().ok_or_else(|| {
+            NotifierError::Config("Cannot locate the config directory; pass --config".to_string())
+        })?,
+    };
+    let config = Config::load(&path)?;
+
+    let mut invalid = 0;
+    for (name, profile) in &config.profiles {
+        let urls: Vec<&str> = profile
+            .webhook_url
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|entry| entry.split_once('|').map_or(entry, |(url, _)| url).trim())
+            .filter(|url| !url.is_empty())
+            .collect();
+        let error = urls.iter().find_map(|url| {
+            validate_webhook_url(url, allow_insecure_url)
+                .err()
+                .map(name_flags)
+        });
+
+        match (error, urls.is_empty()) {
+            (Some(err), _) => {
+                invalid += 1;
+                println!("{}: {}", name, err);
+            }
+            (None, true) => println!(
+                "{}: ok (no webhook_url, LARK_WEBHOOK_URL or --webhook-url is needed)",
+                name
+            ),
+            (None, false) => println!("{}: ok", name),
+        }
+    }
+
+    if invalid > 0 {
+        return Err(NotifierError::Config(format!(
+            "{} of {} profiles in {} are invalid",
+            invalid,
+            config.profiles.len(),
+            path.display()
+        )));
+    }
+    println!(
+        "{} is valid ({} profiles)",
+        path.display(),
+        config.profiles.len()
+    );
+    Ok(())
+}
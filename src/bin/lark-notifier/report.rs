@@ -0,0 +1,331 @@
+use lark_notifier::{
+    ALLOW_INSECURE_URL, FlushReport, LarkNotifier, NotifierError, SIGN_MISMATCH, SendResponse,
+};
+use serde::Serialize;
+use tracing_subscriber::EnvFilter;
+
+use std::io;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::args::Output;
+
+/// Set by `--quiet`: only errors are printed.
+pub(crate) static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Print a warning to stderr unless `--quiet` was given.
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        if !$crate::report::quiet() {
+            eprintln!("Warning: {}", format_args!($($arg)*));
+        }
+    };
+}
+
+pub(crate) fn usage(msg: impl Into<String>) -> NotifierError {
+    NotifierError::Usage(msg.into())
+}
+
+/// `err` with the library's builder options named as the flags that set them.
+pub(crate) fn name_flags(err: NotifierError) -> NotifierError {
+    match err {
+        NotifierError::InvalidWebhookUrl { url, problems } => NotifierError::InvalidWebhookUrl {
+            url,
+            problems: problems
+                .into_iter()
+                .map(|problem| problem.replace(ALLOW_INSECURE_URL, "--allow-insecure-url"))
+                .collect(),
+        },
+        err => err,
+    }
+}
+
+// These are documented in the README and must not change.
+
+/// Exit status for any other failure, e.g. a message that could not be signed.
+pub(crate) const EXIT_FAILURE: i32 = 1;
+
+/// Exit status for missing or invalid configuration and arguments, as clap uses for usage errors.
+const EXIT_CONFIG: i32 = 2;
+
+/// Exit status when the webhook could not be reached, directly or through the proxy.
+const EXIT_TRANSPORT: i32 = 3;
+
+/// Exit status when the webhook answered with a non-2xx HTTP status.
+const EXIT_HTTP_STATUS: i32 = 4;
+
+/// Exit status when Lark rejected the message with a non-zero `code`.
+const EXIT_REJECTED: i32 = 5;
+
+/// Exit status when `--check-clock` found the clock too far off to sign.
+const EXIT_CLOCK_SKEW: i32 = 6;
+
+/// Exit status when a request timed out, matching `timeout(1)`.
+const EXIT_TIMEOUT: i32 = 124;
+
+/// Map an error to the process exit status so scripts can tell failures apart.
+pub(crate) fn exit_code(err: &NotifierError) -> i32 {
+    match err.final_error() {
+        NotifierError::Config(_)
+        | NotifierError::MissingConfig(_)
+        | NotifierError::Usage(_)
+        | NotifierError::Template(_)
+        | NotifierError::MissingWebhookUrl
+        | NotifierError::InvalidWebhookUrl { .. }
+        | NotifierError::InvalidProxy(_)
+        | NotifierError::InvalidMessage(_)
+        | NotifierError::InvalidPayload(_)
+        | NotifierError::InvalidImage { .. }
+        | NotifierError::InvalidCertificate { .. }
+        | NotifierError::TooLarge { .. }
+        | NotifierError::Io { .. } => EXIT_CONFIG,
+        NotifierError::Http(_) | NotifierError::Proxy { .. } => EXIT_TRANSPORT,
+        NotifierError::Status { .. } => EXIT_HTTP_STATUS,
+        NotifierError::LarkApi { .. } => EXIT_REJECTED,
+        NotifierError::ClockSkew { .. } => EXIT_CLOCK_SKEW,
+        NotifierError::Timeout { .. } => EXIT_TIMEOUT,
+        _ => EXIT_FAILURE,
+    }
+}
+
+/// The result of one send for `--output json`.
+#[derive(Serialize)]
+pub(crate) struct Outcome {
+    pub(crate) ok: bool,
+    /// The `--level` of the message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) level: Option<&'static str>,
+    /// Line of the `--batch` file the message came from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) line: Option<usize>,
+    pub(crate) target: Option<String>,
+    pub(crate) http_status: Option<u16>,
+    pub(crate) lark_code: Option<i64>,
+    pub(crate) lark_msg: Option<String>,
+    /// The id of the message created, for `--chat-id`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) message_id: Option<String>,
+    /// How many messages were queued to send later, for `--queue-dir`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) queued: Option<usize>,
+    pub(crate) latency_ms: Option<u128>,
+    /// Unix time in seconds when the outcome was reported.
+    pub(crate) timestamp: u64,
+    pub(crate) error: Option<String>,
+}
+
+impl Outcome {
+    pub(crate) fn new(
+        target: Option<String>,
+        latency: Option<Duration>,
+        result: &Result<&SendResponse, &NotifierError>,
+    ) -> Self {
+        let mut outcome = Outcome {
+            ok: result.is_ok(),
+            level: None,
+            line: None,
+            target,
+            http_status: None,
+            lark_code: None,
+            lark_msg: None,
+            message_id: None,
+            queued: None,
+            latency_ms: latency.map(|latency| latency.as_millis()),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |now| now.as_secs()),
+            error: None,
+        };
+
+        match result {
+            Ok(res) => {
+                outcome.http_status = Some(res.status.as_u16());
+                outcome.message_id = res.message_id().map(str::to_string);
+                if let Some(response) = &res.response {
+                    outcome.lark_code = Some(response.code);
+                    outcome.lark_msg = Some(response.msg.clone());
+                }
+            }
+            Err(err) => {
+                match err.final_error() {
+                    NotifierError::Status { status, .. } => {
+                        outcome.http_status = Some(status.as_u16())
+                    }
+                    NotifierError::LarkApi { code, msg } => {
+                        outcome.lark_code = Some(*code);
+                        outcome.lark_msg = Some(msg.clone());
+                    }
+                    _ => {}
+                }
+                outcome.error = Some(err.to_string());
+            }
+        }
+        outcome
+    }
+
+    pub(crate) fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("outcome serializes to JSON")
+    }
+}
+
+/// Print `outcomes` for `--output json` one per line, or for `--output json-array` as one array.
+pub(crate) fn print_outcomes(output: Output, outcomes: &[Outcome]) {
+    match output {
+        Output::Human => {}
+        Output::Json => outcomes
+            .iter()
+            .for_each(|outcome| println!("{}", outcome.to_json())),
+        Output::JsonArray => println!(
+            "{}",
+            serde_json::to_string(outcomes).expect("outcomes serialize to JSON")
+        ),
+    }
+}
+
+/// Print the outcome of one send, prefixed with its target when fanning out.
+pub(crate) fn report(target: Option<&str>, result: &Result<SendResponse, NotifierError>) {
+    let prefix = target.map(|t| format!("[{}] ", t)).unwrap_or_default();
+
+    match result {
+        Ok(_) if quiet() => {}
+        Ok(res) => {
+            match res.message_id() {
+                Some(message_id) => println!(
+                    "{}Successfully sent notification to Lark (message_id {})",
+                    prefix, message_id
+                ),
+                None => println!("{}Successfully sent notification to Lark", prefix),
+            }
+            if res.response.is_none() && !res.body.is_empty() {
+                println!("{}Unrecognized response: {}", prefix, res.body);
+            }
+        }
+        Err(err) => eprintln!("{}Error: {}", prefix, err),
+    }
+}
+
+/// Print whether the webhook, and the secret if one is set, accepted a
+/// connectivity test message.
+pub(crate) fn report_test(
+    target: Option<&str>,
+    notifier: &LarkNotifier,
+    result: &Result<SendResponse, NotifierError>,
+) {
+    let prefix = target.map(|t| format!("[{}] ", t)).unwrap_or_default();
+
+    match result.as_ref().map_err(NotifierError::final_error) {
+        Ok(_) if quiet() => {}
+        Ok(_) if notifier.secret().is_some() => {
+            println!("{}ok: webhook reachable and signature accepted", prefix)
+        }
+        Ok(_) => println!("{}ok: webhook reachable (no secret configured)", prefix),
+        Err(NotifierError::LarkApi {
+            code: SIGN_MISMATCH,
+            msg,
+        }) => {
+            eprintln!(
+                "{}signature rejected: {} (check the secret and the system clock)",
+                prefix, msg
+            )
+        }
+        Err(NotifierError::LarkApi { code, msg }) => {
+            eprintln!(
+                "{}webhook rejected the message: code {}: {}",
+                prefix, code, msg
+            )
+        }
+        Err(err) => eprintln!("{}webhook unreachable: {}", prefix, err),
+    }
+}
+
+/// Log to stderr at the level `-v`/`-q` ask for, unless `RUST_LOG` is set.
+pub(crate) fn init_tracing(verbose: u8, quiet: bool) {
+    let directives = match verbose {
+        0 if quiet => "error",
+        0 => "warn",
+        1 => "lark_notifier=debug",
+        _ => "lark_notifier=trace,reqwest=debug,hyper=debug",
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(directives));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .init();
+}
+
+/// Exit with the `exec` command's status if it failed, else with the status
+/// for `err`, the first failed send, if any.
+pub(crate) fn exit_after(command_status: Option<i32>, err: Option<&NotifierError>) {
+    match (command_status, err) {
+        (Some(status), _) if status != 0 => process::exit(status),
+        (_, Some(err)) => process::exit(exit_code(err)),
+        _ => {}
+    }
+}
+
+/// Print what a flush of the queue did; with `always` also when it did nothing.
+pub(crate) fn report_flush(
+    report: &FlushReport,
+    notifiers: &[LarkNotifier],
+    output: Output,
+    always: bool,
+) {
+    let masked = |url: &str| {
+        notifiers
+            .iter()
+            .find(|notifier| notifier.webhook_url() == url)
+            .map_or_else(|| url.to_string(), LarkNotifier::masked_webhook_url)
+    };
+    if output != Output::Human {
+        if always {
+            let summary = serde_json::json!({
+                "ok": report.stalled.is_empty() && report.rejected.is_empty(),
+                "busy": report.busy,
+                "flushed": report.flushed,
+                "expired": report.expired,
+                "rejected": report.rejected.len(),
+                "remaining": report.remaining,
+                "errors": report.stalled.iter().chain(&report.rejected)
+                    .map(|(url, err)| serde_json::json!({ "target": masked(url), "error": err.to_string() }))
+                    .collect::<Vec<_>>(),
+            });
+            println!("{}", summary);
+        }
+        return;
+    }
+    // A dropped message is lost for good, so this is an error even with --quiet.
+    for (url, err) in &report.rejected {
+        eprintln!("Dropped a queued message for {}: {}", masked(url), err);
+    }
+    if quiet() {
+        return;
+    }
+    if report.busy {
+        if always {
+            eprintln!("Another flush of the queue is in progress");
+        }
+        return;
+    }
+    // Before a send, the send reports the same error.
+    for (url, err) in report.stalled.iter().filter(|_| always) {
+        eprintln!(
+            "Queued messages for {} are still waiting: {}",
+            masked(url),
+            err
+        );
+    }
+    if always || report.flushed + report.expired + report.rejected.len() > 0 {
+        eprintln!(
+            "Flushed {} queued message(s): {} expired, {} rejected, {} still queued",
+            report.flushed,
+            report.expired,
+            report.rejected.len(),
+            report.remaining
+        );
+    }
+}
@@ -0,0 +1,869 @@
+use futures::future::join_all;
+use lark_notifier::{
+    AppClient, CiProvider, GitHub, GitLab, LarkMessage, LarkNotifier, LarkNotifierBuilder,
+    NotifierError, Queue, RateLimiter, Region, RetryPolicy, SIGN_MISMATCH, SendResponse,
+    chat_message_body, check_image, generate_sign, parse_keyword, string_to_sign,
+};
+use reqwest::Client;
+
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::args::{Format, Level, Output, SendArgs};
+use crate::batch::run_batch;
+use crate::compose::{
+    build_message, fit_message, has_content, read_keywords_file, read_raw_json, read_secret,
+    render_template,
+};
+use crate::exec::run_command;
+use crate::heartbeat::run_heartbeat;
+#[cfg(all(feature = "journal", target_os = "linux"))]
+use crate::journal::run_journal;
+use crate::pipe::run_pipe;
+use crate::profile::{get_env_or_arg, load_profile};
+use crate::report::{
+    Outcome, exit_after, exit_code, print_outcomes, quiet, report, report_flush, report_test, usage,
+};
+
+pub(crate) async fn run(mut args: SendArgs) -> Result<(), NotifierError> {
+    if let Some(timestamp) = args.timestamp
+        && !args.force
+    {
+        check_timestamp(timestamp)?;
+    }
+    let profile = load_profile(args.config.as_deref(), args.profile.as_deref())?;
+    if let Some(name) = args.template.take() {
+        let (title, content) = render_template(&name, &args.vars)?;
+        args.title.get_or_insert(title);
+        args.content = Some(content);
+    }
+    match (&profile.title_prefix, &args.title) {
+        (Some(prefix), Some(title)) => args.title = Some(format!("{}{}", prefix, title)),
+        (prefix, None) => args.title_prefix = prefix.clone(),
+        _ => {}
+    }
+    let provider: Option<&dyn CiProvider> = match (args.github, args.gitlab) {
+        (true, _) => Some(&GitHub),
+        (_, true) => Some(&GitLab),
+        _ => None,
+    };
+    if let Some(provider) = provider {
+        let footer = provider.footer(&|name| env::var(name).ok());
+        if !footer.missing.is_empty() {
+            let missing = footer.missing.join(", ");
+            if provider.requires_all() {
+                return Err(usage(format!(
+                    "--{}: {} not set; is this running in {}?",
+                    provider.flag(),
+                    missing,
+                    provider.name()
+                )));
+            }
+            warning!("--{}: {} not set, skipping", provider.flag(), missing);
+        }
+        args.ci_footer = Some(footer).filter(|footer| !footer.is_empty());
+    }
+    if let Some(level) = args.level {
+        args.level_emoji = level.emoji_for(&profile);
+        if args.card_color.is_none() {
+            args.card_color = Some(level.color_for(&profile)?);
+        }
+    }
+
+    let secret = match (args.secret.take(), args.secret_file.take()) {
+        (Some(secret), _) if secret == "@-" => {
+            if args.stdin
+                || args.pipe
+                || args.content.as_deref() == Some("-")
+                || args.raw_json.as_deref() == Some(Path::new("-"))
+            {
+                return Err(usage(
+                    "--secret @- cannot be combined with content from stdin",
+                ));
+            }
+            Some(read_secret(io::stdin(), "stdin")?)
+        }
+        (Some(secret), _) => Some(secret),
+        (None, Some(path)) => {
+            let file = fs::File::open(&path).map_err(|source| NotifierError::Io {
+                context: format!("Failed to open secret file {}", path.display()),
+                source,
+            })?;
+            Some(read_secret(file, &path.display().to_string())?)
+        }
+        (None, None) => get_env_or_arg(profile.secret, "LARK_SECRET").ok(),
+    };
+
+    if let Some(timestamp) = args.verify_sign {
+        let secret = secret.ok_or_else(|| {
+            usage("--verify-sign needs a secret (--secret, --secret-file or LARK_SECRET)")
+        })?;
+        let version = args.sign_version;
+        let digest: String = version
+            .digest(timestamp, &secret)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        println!("timestamp:      {}", timestamp);
+        println!("string_to_sign: {:?}", string_to_sign(timestamp, &secret));
+        println!("{:<16}{}", format!("{}:", version), digest);
+        println!(
+            "sign:           {}",
+            generate_sign(timestamp, &secret, version)
+        );
+        return Ok(());
+    }
+
+    let mut keywords: Vec<String> = match args.keywords.take() {
+        Some(k) => k.split(',').map(|s| s.trim().to_string()).collect(),
+        None if args.keyword.is_empty() && args.keywords_file.is_none() => {
+            profile.keywords.unwrap_or_default()
+        }
+        None => Vec::new(),
+    };
+    if let Some(path) = args.keywords_file.take() {
+        keywords.extend(read_keywords_file(&path)?);
+    }
+    keywords.append(&mut args.keyword);
+    for spec in args.keyword_link.drain(..) {
+        if matches!(parse_keyword(&spec), ("", _) | (_, None)) {
+            return Err(usage(format!(
+                "--keyword-link expects KEYWORD=URL, got '{}'",
+                spec
+            )));
+        }
+        keywords.push(spec);
+    }
+    let mut seen = HashSet::new();
+    keywords.retain(|keyword| seen.insert(keyword.clone()));
+
+    if let Some(chat_id) = args.chat_id.take() {
+        return run_chat(args, keywords, chat_id).await;
+    }
+
+    let webhook_urls = if args.webhook_url.is_empty() {
+        get_env_or_arg(profile.webhook_url, "LARK_WEBHOOK_URL")?
+            .split(',')
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+            .collect()
+    } else {
+        std::mem::take(&mut args.webhook_url)
+    };
+    if !args.secrets.is_empty() && args.secrets.len() != webhook_urls.len() {
+        return Err(usage(format!(
+            "--secrets has {} entries but there are {} webhook URLs",
+            args.secrets.len(),
+            webhook_urls.len()
+        )));
+    }
+    let targets: Vec<(String, Option<String>)> = webhook_urls
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| match entry.split_once('|') {
+            Some((url, secret)) => (url.to_string(), Some(secret.to_string())),
+            None => (
+                entry.clone(),
+                args.secrets.get(i).cloned().or_else(|| secret.clone()),
+            ),
+        })
+        .collect();
+    let regions = targets
+        .iter()
+        .map(|(url, _)| resolve_region(url, args.region))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let base = notifier_builder(&mut args)
+        .webhook_url(
+            targets
+                .first()
+                .map(|(url, _)| url.clone())
+                .unwrap_or_default(),
+        )
+        .build()?;
+
+    let mut notifiers = Vec::with_capacity(targets.len());
+    for (url, secret) in targets {
+        notifiers.push(base.with_webhook_url(url)?.with_secret(secret));
+    }
+
+    if args.check_clock
+        && !args.dry_run
+        && let Some(notifier) = notifiers
+            .iter()
+            .find(|notifier| notifier.secret().is_some())
+    {
+        check_clock(notifier, args.max_clock_skew).await?;
+    }
+
+    let queue = queue(&args);
+    if args.flush {
+        let queue = queue.ok_or_else(|| usage("flush needs --queue-dir or LARK_QUEUE_DIR"))?;
+        return run_flush(&queue, &notifiers, args.output).await;
+    }
+    if let Some(options) = args.heartbeat.take() {
+        return run_heartbeat(args, keywords, &notifiers, options).await;
+    }
+    if let Some(path) = args.batch.take() {
+        return run_batch(args, keywords, &notifiers, &path).await;
+    }
+    if args.pipe {
+        return run_pipe(args, keywords, &notifiers).await;
+    }
+    #[cfg(all(feature = "journal", target_os = "linux"))]
+    if let Some(options) = args.journal.take() {
+        return run_journal(args, keywords, &notifiers, options).await;
+    }
+    let command_status = match args.exec.take() {
+        Some(options) => Some(run_command(&mut args, &options).await?),
+        None => None,
+    };
+
+    let dry_run = args.dry_run;
+    let checked_clock = args.check_clock;
+    let output = args.output;
+    let connectivity_test = args.connectivity_test;
+    let level = args.level;
+    let max_bytes = args.max_bytes;
+    let on_oversize = args.on_oversize;
+    if args.image_file.is_some() || !args.at_mobile.is_empty() {
+        let flag = if args.image_file.is_some() {
+            "--image-file"
+        } else {
+            "--at-mobile"
+        };
+        if regions.windows(2).any(|pair| pair[0] != pair[1]) {
+            return Err(usage(format!(
+                "{} cannot be sent to webhooks in different regions",
+                flag
+            )));
+        }
+        let region = regions.first().copied().flatten();
+        let app = app_client(&args, region, base.client().clone(), flag)?;
+        resolve_mobiles(&mut args, &app).await?;
+        if let Some(path) = &args.image_file {
+            args.image_key = Some(upload_image_file(&args, &app, path).await?);
+        }
+    }
+    let message = match args.raw_json.take() {
+        Some(path) => read_raw_json(&path)?,
+        None => build_message(args, keywords)?,
+    };
+    let messages = fit_message(message, max_bytes, on_oversize)?;
+    if messages.len() > 1 && output == Output::Human && !quiet() {
+        eprintln!(
+            "Message exceeds {} bytes, sending it in {} parts",
+            max_bytes,
+            messages.len()
+        );
+    }
+
+    if dry_run {
+        for notifier in &notifiers {
+            eprintln!("Would POST to {}", notifier.masked_webhook_url());
+            for message in &messages {
+                let payload = notifier.prepare(message.clone())?;
+                println!("{}", serde_json::to_string_pretty(&payload)?);
+            }
+        }
+        exit_after(command_status, None);
+        return Ok(());
+    }
+    let queue = queue.filter(|_| !connectivity_test);
+    let (results, queued): (Vec<_>, Vec<_>) = match &queue {
+        Some(queue) => {
+            // Queued messages go first; a webhook still unreachable gets this one queued behind them.
+            let report = queue.flush(&notifiers).await?;
+            report_flush(&report, &notifiers, output, false);
+            let mut stalled = report.stalled;
+            let sends = notifiers.iter().map(|notifier| {
+                let stalled = stalled
+                    .iter()
+                    .position(|(url, _)| url == notifier.webhook_url())
+                    .map(|i| stalled.swap_remove(i).1);
+                deliver_or_queue(notifier, &messages, queue, stalled)
+            });
+            let sent = join_all(sends.collect::<Vec<_>>()).await;
+            sent.into_iter()
+                .map(|(result, latency, queued)| ((result, latency), queued))
+                .unzip()
+        }
+        None => {
+            let results = join_all(
+                notifiers
+                    .iter()
+                    .map(|notifier| deliver(notifier, &messages)),
+            )
+            .await;
+            let queued = vec![0; results.len()];
+            (results, queued)
+        }
+    };
+
+    let fan_out = notifiers.len() > 1;
+    let mut succeeded = 0;
+    let mut outcomes = Vec::new();
+    for ((notifier, (result, latency)), queued) in notifiers.iter().zip(&results).zip(queued) {
+        match output {
+            Output::Human => {
+                let target = fan_out.then(|| notifier.masked_webhook_url());
+                let target = target.as_deref();
+                if connectivity_test {
+                    report_test(target, notifier, result);
+                } else {
+                    report(target, result);
+                }
+                if let Some(queue) = queue.as_ref().filter(|_| queued > 0 && !quiet()) {
+                    let prefix = target.map(|t| format!("[{}] ", t)).unwrap_or_default();
+                    eprintln!(
+                        "{}Queued {} message(s) in {} to send later",
+                        prefix,
+                        queued,
+                        queue.dir().display()
+                    );
+                }
+            }
+            Output::Json | Output::JsonArray => {
+                let target = notifier.masked_webhook_url();
+                let mut outcome = Outcome::new(Some(target), Some(*latency), &result.as_ref());
+                outcome.level = level.map(Level::as_str);
+                outcome.queued = (queued > 0).then_some(queued);
+                outcomes.push(outcome);
+            }
+        }
+        if result.is_ok() {
+            succeeded += 1;
+        }
+    }
+    print_outcomes(output, &outcomes);
+
+    if fan_out && output == Output::Human && !quiet() {
+        println!("Sent to {}/{} webhooks", succeeded, notifiers.len());
+    }
+    if !checked_clock {
+        for (notifier, (result, _)) in notifiers.iter().zip(&results) {
+            if let Err(err) = result
+                && let NotifierError::LarkApi {
+                    code: SIGN_MISMATCH,
+                    ..
+                } = err.final_error()
+            {
+                explain_sign_mismatch(notifier).await;
+            }
+        }
+    }
+    exit_after(
+        command_status,
+        results.iter().find_map(|(result, _)| result.as_ref().err()),
+    );
+
+    Ok(())
+}
+
+/// A notifier builder with the retry, timeout, proxy and rate limit arguments;
+/// the webhook URL and secret are set per target.
+fn notifier_builder(args: &mut SendArgs) -> LarkNotifierBuilder {
+    let retry = RetryPolicy::new(args.retries, Duration::from_millis(args.retry_base_ms));
+    let mut builder = LarkNotifier::builder()
+        .retry_policy(retry)
+        .sign_version(args.sign_version)
+        .time_offset(args.time_offset_secs)
+        .timeout(Duration::from_secs(args.timeout_secs));
+    if let Some(timestamp) = args.timestamp {
+        builder = builder.sign_timestamp(timestamp);
+    }
+    if let Some(secs) = args.connect_timeout {
+        builder = builder.connect_timeout(Duration::from_secs(secs));
+    }
+    if let Some(proxy) = args.proxy.take() {
+        builder = builder.proxy(proxy);
+    }
+    if let Some(secs) = args.pool_idle_secs {
+        builder = builder.pool_idle_timeout(Duration::from_secs(secs));
+    }
+    if args.no_proxy {
+        builder = builder.no_proxy();
+    }
+    if let Some(path) = ca_cert(args.ca_cert.clone()) {
+        builder = builder.ca_cert(path);
+    }
+    builder = builder.allow_insecure_url(args.allow_insecure_url);
+    if args.rate_limit > 0 {
+        builder = builder.rate_limiter(RateLimiter::per_minute(args.rate_limit));
+    }
+    builder
+}
+
+/// The open API client for `flag` (`--image-file`, `--at-mobile` or `--chat-id`), for
+/// `region`'s host or `--api-base-url`, authenticated with `--tenant-token` or
+/// the app credentials.
+fn app_client(
+    args: &SendArgs,
+    region: Option<Region>,
+    client: Client,
+    flag: &str,
+) -> Result<AppClient, NotifierError> {
+    let region = match (&args.api_base_url, region) {
+        (None, None) => {
+            return Err(usage(format!(
+                "{} needs --region cn|intl or --api-base-url to find the open API host",
+                flag
+            )));
+        }
+        // The region only picks the host, which --api-base-url replaces.
+        (_, region) => region.unwrap_or(Region::Cn),
+    };
+    let token = get_env_or_arg(args.tenant_token.clone(), "LARK_TENANT_ACCESS_TOKEN").ok();
+    let app_id = get_env_or_arg(args.app_id.clone(), "LARK_APP_ID");
+    let app_secret = get_env_or_arg(args.app_secret.clone(), "LARK_APP_SECRET");
+    let mut app = match (token, app_id, app_secret) {
+        (Some(token), _, _) => AppClient::with_tenant_access_token(client, region, token),
+        (None, Ok(app_id), Ok(app_secret)) => AppClient::new(client, region, app_id, app_secret),
+        (None, Err(_), _) | (None, _, Err(_)) => {
+            return Err(usage(format!(
+                "{} uses the open API, which needs app credentials: --app-id and --app-secret \
+                 (or LARK_APP_ID and LARK_APP_SECRET), or --tenant-token; a webhook alone is not enough",
+                flag
+            )));
+        }
+    };
+    if let Some(base_url) = &args.api_base_url {
+        app = app.with_base_url(base_url);
+    }
+    let retry = RetryPolicy::new(args.retries, Duration::from_millis(args.retry_base_ms));
+    Ok(app.with_retry_policy(retry))
+}
+
+/// Add the users `--at-mobile` names to the `--at` mentions, looked up through
+/// `app`. A dry run mentions a placeholder for each instead.
+async fn resolve_mobiles(args: &mut SendArgs, app: &AppClient) -> Result<(), NotifierError> {
+    if args.at_mobile.is_empty() {
+        return Ok(());
+    }
+    if args.dry_run {
+        args.at.extend(
+            args.at_mobile
+                .iter()
+                .map(|mobile| format!("<open_id of {}>", mobile)),
+        );
+        return Ok(());
+    }
+    let open_ids = app.open_ids_by_mobile(&args.at_mobile).await?;
+    for (mobile, open_id) in args.at_mobile.iter().zip(open_ids) {
+        let open_id = open_id.ok_or_else(|| {
+            usage(format!(
+                "--at-mobile {}: no user the app can see has this mobile number",
+                mobile
+            ))
+        })?;
+        if !quiet() && args.output == Output::Human {
+            eprintln!("Mentioning {} as {}", mobile, open_id);
+        }
+        args.at.push(open_id);
+    }
+    Ok(())
+}
+
+/// Upload `--image-file` through `app`, returning its image key. A dry run only
+/// checks the file.
+async fn upload_image_file(
+    args: &SendArgs,
+    app: &AppClient,
+    path: &Path,
+) -> Result<String, NotifierError> {
+    if has_content(args)
+        && args.format != Format::Image
+        && (args.format != Format::Post || args.code)
+    {
+        return Err(usage(
+            "--image-file with content is only supported for post messages",
+        ));
+    }
+    if args.dry_run {
+        let data = fs::read(path).map_err(|source| NotifierError::Io {
+            context: format!("Failed to read image {}", path.display()),
+            source,
+        })?;
+        check_image(&path.display().to_string(), &data)?;
+        return Ok(format!("<image_key of {}>", path.display()));
+    }
+    let image_key = app.upload_image_file(path).await?;
+    if !quiet() && args.output == Output::Human {
+        eprintln!("Uploaded {} as {}", path.display(), image_key);
+    }
+    Ok(image_key)
+}
+
+/// Send through the open API as the app's bot to `chat_id`, for `--chat-id`.
+async fn run_chat(
+    mut args: SendArgs,
+    keywords: Vec<String>,
+    chat_id: String,
+) -> Result<(), NotifierError> {
+    if !chat_id.starts_with("oc_") || chat_id.len() == "oc_".len() {
+        return Err(usage(format!(
+            "Invalid chat id '{}': expected an open chat id like oc_xxx",
+            chat_id
+        )));
+    }
+    let client = notifier_builder(&mut args).build_client()?;
+    let app = app_client(&args, args.region, client, "--chat-id")?;
+    let command_status = match args.exec.take() {
+        Some(options) => Some(run_command(&mut args, &options).await?),
+        None => None,
+    };
+    resolve_mobiles(&mut args, &app).await?;
+    if let Some(path) = &args.image_file {
+        args.image_key = Some(upload_image_file(&args, &app, path).await?);
+    }
+
+    let dry_run = args.dry_run;
+    let output = args.output;
+    let level = args.level;
+    let max_bytes = args.max_bytes;
+    let on_oversize = args.on_oversize;
+    let message = match args.raw_json.take() {
+        Some(path) => read_raw_json(&path)?,
+        None => build_message(args, keywords)?,
+    };
+    let messages = fit_message(message, max_bytes, on_oversize)?;
+    if messages.len() > 1 && output == Output::Human && !quiet() {
+        eprintln!(
+            "Message exceeds {} bytes, sending it in {} parts",
+            max_bytes,
+            messages.len()
+        );
+    }
+
+    if dry_run {
+        eprintln!(
+            "Would POST to the open API im/v1/messages for chat {}",
+            chat_id
+        );
+        for message in &messages {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&chat_message_body(&chat_id, message)?)?
+            );
+        }
+        exit_after(command_status, None);
+        return Ok(());
+    }
+
+    let started = Instant::now();
+    let mut result = Err(usage("Nothing to send"));
+    for message in &messages {
+        result = app.send_to_chat(&chat_id, message).await;
+        if result.is_err() {
+            break;
+        }
+    }
+    match output {
+        Output::Human => report(None, &result),
+        Output::Json | Output::JsonArray => {
+            let mut outcome =
+                Outcome::new(Some(chat_id), Some(started.elapsed()), &result.as_ref());
+            outcome.level = level.map(Level::as_str);
+            print_outcomes(output, &[outcome]);
+        }
+    }
+    exit_after(command_status, result.as_ref().err());
+    Ok(())
+}
+
+/// The region of `webhook_url`: `--region` if given, else the one its host belongs to.
+/// A `--region` that contradicts the host is a config error.
+fn resolve_region(
+    webhook_url: &str,
+    region: Option<Region>,
+) -> Result<Option<Region>, NotifierError> {
+    match (region, Region::from_webhook_url(webhook_url)) {
+        (Some(region), Some(detected)) if region != detected => {
+            Err(NotifierError::Config(format!(
+                "webhook host {} belongs to region {}, but --region {} was given",
+                detected.host(),
+                detected,
+                region
+            )))
+        }
+        (region, detected) => Ok(region.or(detected)),
+    }
+}
+
+/// After a webhook rejected a signature, check whether the clock is to blame,
+/// since `sign match fail` does not say whether it was the secret or the timestamp.
+async fn explain_sign_mismatch(notifier: &LarkNotifier) {
+    match notifier.clock_skew().await {
+        Ok(Some(skew)) if skew.unsigned_abs() > 60 => warning!(
+            "local clock is {}s {} the webhook server, which may be why the signature was rejected; \
+             fix it with NTP, or pass --time-offset-secs {}",
+            skew.unsigned_abs(),
+            if skew > 0 { "ahead of" } else { "behind" },
+            notifier.time_offset() - skew
+        ),
+        Ok(Some(_)) => warning!("the local clock matches the webhook server; check the secret"),
+        Ok(None) | Err(_) => {}
+    }
+}
+
+/// A `--timestamp` must be after the epoch and at most a day ahead; past that
+/// it is more likely a typo, or milliseconds, than a deliberate choice.
+fn check_timestamp(timestamp: u64) -> Result<(), NotifierError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs());
+    if timestamp == 0 {
+        return Err(usage(
+            "--timestamp 0 is not a plausible signing time; pass --force to use it anyway",
+        ));
+    }
+    if timestamp > now + 24 * 3600 {
+        let unit = if timestamp >= 1_000_000_000_000 {
+            " (milliseconds? it takes seconds)"
+        } else {
+            ""
+        };
+        return Err(usage(format!(
+            "--timestamp {} is more than a day in the future{}; pass --force to use it anyway",
+            timestamp, unit
+        )));
+    }
+    Ok(())
+}
+
+/// Lark only accepts signatures made within an hour of its own clock.
+const LARK_MAX_CLOCK_SKEW: u64 = 3600;
+
+/// Fail if the local clock is too far off for Lark to accept signatures, and
+/// warn if it is more than `warn_after` seconds off.
+pub(crate) async fn check_clock(
+    notifier: &LarkNotifier,
+    warn_after: u64,
+) -> Result<(), NotifierError> {
+    match notifier.clock_skew().await? {
+        Some(skew) if skew.unsigned_abs() > LARK_MAX_CLOCK_SKEW => {
+            Err(NotifierError::ClockSkew { skew })
+        }
+        Some(skew) if skew.unsigned_abs() > warn_after => {
+            warning!(
+                "local clock is {}s {} the webhook server; signed messages may be rejected",
+                skew.unsigned_abs(),
+                if skew > 0 { "ahead of" } else { "behind" }
+            );
+            Ok(())
+        }
+        Some(_) => Ok(()),
+        None => {
+            warning!("the webhook server sent no Date header; cannot check the clock");
+            Ok(())
+        }
+    }
+}
+
+/// Send the parts of one message in order, timing the whole delivery.
+/// A failed part stops the rest for that webhook.
+pub(crate) async fn deliver(
+    notifier: &LarkNotifier,
+    messages: &[LarkMessage],
+) -> (Result<SendResponse, NotifierError>, Duration) {
+    let started = Instant::now();
+    let mut result = Err(usage("Nothing to send"));
+    for message in messages {
+        result = notifier.send(message.clone()).await;
+        if result.is_err() {
+            break;
+        }
+    }
+    (result, started.elapsed())
+}
+
+/// The `--ca-cert` file, or else `LARK_CA_CERT`'s.
+pub(crate) fn ca_cert(arg: Option<PathBuf>) -> Option<PathBuf> {
+    arg.or_else(|| {
+        env::var_os("LARK_CA_CERT")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+    })
+}
+
+/// The `--queue-dir` (or `LARK_QUEUE_DIR`) queue, if there is one.
+pub(crate) fn queue(args: &SendArgs) -> Option<Queue> {
+    let dir = args.queue_dir.clone().or_else(|| {
+        env::var_os("LARK_QUEUE_DIR")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+    })?;
+    Some(
+        Queue::new(dir)
+            .with_max_age(args.queue_max_age)
+            .with_max_count(args.queue_max_count),
+    )
+}
+
+/// Send `messages` like [`deliver`], queueing the unsent ones if the webhook
+/// cannot be reached; with `stalled`, the error a flush just got from this
+/// webhook, they are queued without trying. Also returns how many were queued.
+async fn deliver_or_queue(
+    notifier: &LarkNotifier,
+    messages: &[LarkMessage],
+    queue: &Queue,
+    stalled: Option<NotifierError>,
+) -> (Result<SendResponse, NotifierError>, Duration, usize) {
+    let started = Instant::now();
+    let (result, unsent) = match stalled {
+        Some(err) => (Err(err), messages),
+        None => {
+            let mut result = Err(usage("Nothing to send"));
+            let mut unsent: &[LarkMessage] = &[];
+            for (i, message) in messages.iter().enumerate() {
+                result = notifier.send(message.clone()).await;
+                if result.is_err() {
+                    unsent = &messages[i..];
+                    break;
+                }
+            }
+            (result, unsent)
+        }
+    };
+    let mut queued = 0;
+    if result.as_ref().is_err_and(NotifierError::is_transient) {
+        for message in unsent {
+            match queue.push(notifier.webhook_url(), message) {
+                Ok(0) => queued += 1,
+                Ok(dropped) => {
+                    queued += 1;
+                    warning!(
+                        "Dropped {} old queued message(s) from {} (--queue-max-age, --queue-max-count)",
+                        dropped,
+                        queue.dir().display()
+                    );
+                }
+                Err(err) => warning!("{}", err),
+            }
+        }
+    }
+    (result, started.elapsed(), queued)
+}
+
+/// Send what `queue` holds for the `flush` subcommand, exiting with the status
+/// for the first message that could not be sent.
+async fn run_flush(
+    queue: &Queue,
+    notifiers: &[LarkNotifier],
+    output: Output,
+) -> Result<(), NotifierError> {
+    let report = queue.flush(notifiers).await?;
+    report_flush(&report, notifiers, output, true);
+    if let Some((_, err)) = report.stalled.first().or(report.rejected.first()) {
+        process::exit(exit_code(err));
+    }
+    Ok(())
+}
+
+/// Successes and failures over several sends.
+#[derive(Default)]
+pub(crate) struct Tally {
+    pub(crate) sent: usize,
+    pub(crate) failed: usize,
+    pub(crate) first_error: Option<NotifierError>,
+}
+
+impl Tally {
+    pub(crate) fn record(&mut self, result: Result<SendResponse, NotifierError>) {
+        match result {
+            Ok(_) => self.sent += 1,
+            Err(err) => {
+                self.failed += 1;
+                self.first_error.get_or_insert(err);
+            }
+        }
+    }
+}
+
+/// Send `lines` read by `--pipe` as one message to every webhook.
+pub(crate) async fn send_piped(
+    args: &SendArgs,
+    keywords: &[String],
+    notifiers: &[LarkNotifier],
+    lines: Vec<String>,
+    tally: &mut Tally,
+) -> Result<(), NotifierError> {
+    let mut message_args = args.clone();
+    message_args.content = Some(lines.join("\n"));
+    message_args.strip_ansi = true;
+    send_tallied(message_args, keywords, notifiers, tally).await
+}
+
+/// Build the message `args` describe and send it to every webhook, reporting
+/// and tallying the outcome; only a failure to print a dry run is returned.
+pub(crate) async fn send_tallied(
+    args: SendArgs,
+    keywords: &[String],
+    notifiers: &[LarkNotifier],
+    tally: &mut Tally,
+) -> Result<(), NotifierError> {
+    let messages = match build_message(args.clone(), keywords.to_vec()) {
+        Ok(message) => match fit_message(message, args.max_bytes, args.on_oversize) {
+            Ok(messages) => messages,
+            Err(err) => {
+                let result = Err(err);
+                report(None, &result);
+                tally.record(result);
+                return Ok(());
+            }
+        },
+        Err(err) => {
+            let result = Err(err);
+            report(None, &result);
+            tally.record(result);
+            return Ok(());
+        }
+    };
+
+    if args.dry_run {
+        for notifier in notifiers {
+            eprintln!("Would POST to {}", notifier.masked_webhook_url());
+            for message in &messages {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&notifier.prepare(message.clone())?)?
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    let fan_out = notifiers.len() > 1;
+    let results = join_all(
+        notifiers
+            .iter()
+            .map(|notifier| deliver(notifier, &messages)),
+    )
+    .await;
+    let mut outcomes = Vec::new();
+    for (notifier, (result, latency)) in notifiers.iter().zip(results) {
+        match args.output {
+            Output::Human => report(
+                fan_out.then(|| notifier.masked_webhook_url()).as_deref(),
+                &result,
+            ),
+            Output::Json | Output::JsonArray => {
+                let mut outcome = Outcome::new(
+                    Some(notifier.masked_webhook_url()),
+                    Some(latency),
+                    &result.as_ref(),
+                );
+                outcome.level = args.level.map(Level::as_str);
+                outcomes.push(outcome);
+            }
+        }
+        tally.record(result);
+    }
+    print_outcomes(args.output, &outcomes);
+    Ok(())
+}
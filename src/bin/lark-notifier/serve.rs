@@ -0,0 +1,340 @@
+use clap::ValueEnum;
+use futures::future::join_all;
+use hyper::header::{CONTENT_TYPE, HeaderValue};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode as HyperStatus};
+use lark_notifier::{
+    AlertmanagerPayload, Config, DEFAULT_MAX_BYTES, Highlighter, LarkMessage, LarkNotifier,
+    NotifierError, Profile, RateLimiter, RetryPolicy, SlackPayload, split_message,
+};
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::env;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::args::{Level, ServeArgs};
+use crate::profile::get_env_or_arg;
+use crate::send::{ca_cert, deliver};
+
+/// The notifiers and defaults behind one `serve` route.
+struct Route {
+    notifiers: Vec<LarkNotifier>,
+    profile: Profile,
+}
+
+/// The JSON body `serve` accepts.
+#[derive(Deserialize)]
+struct ServeRequest {
+    title: String,
+    content: String,
+    level: Option<String>,
+    /// Overrides the profile's keywords.
+    keywords: Option<Vec<String>>,
+}
+
+/// The JSON body `serve` answers with; `code` is Lark's, or -1 if Lark was not reached.
+#[derive(Serialize)]
+struct ServeResponse {
+    code: i64,
+    msg: String,
+}
+
+/// Run the `serve` subcommand until SIGTERM or Ctrl-C; requests in flight are finished first.
+pub(crate) async fn serve(args: ServeArgs) -> Result<(), NotifierError> {
+    let config = match args
+        .config
+        .clone()
+        .or_else(|| Config::default_path().filter(|path| path.exists()))
+    {
+        Some(path) => Config::load(&path)?,
+        None => Config::default(),
+    };
+    let mut profiles = config.profiles;
+    if !profiles.contains_key("default") && env::var("LARK_WEBHOOK_URL").is_ok() {
+        profiles.insert("default".to_string(), Profile::default());
+    }
+
+    let mut targets = Vec::new();
+    for (name, profile) in profiles {
+        let urls = get_env_or_arg(profile.webhook_url.clone(), "LARK_WEBHOOK_URL")
+            .map_err(|_| NotifierError::Config(format!("Profile '{}' has no webhook_url", name)))?;
+        let secret = get_env_or_arg(profile.secret.clone(), "LARK_SECRET").ok();
+        let webhooks: Vec<(String, Option<String>)> = urls
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| match entry.split_once('|') {
+                Some((url, secret)) => (url.to_string(), Some(secret.to_string())),
+                None => (entry.to_string(), secret.clone()),
+            })
+            .collect();
+        if webhooks.is_empty() {
+            return Err(NotifierError::Config(format!(
+                "Profile '{}' has no webhook_url",
+                name
+            )));
+        }
+        targets.push((name, profile, webhooks));
+    }
+    let Some(first_url) = targets
+        .iter()
+        .flat_map(|(_, _, webhooks)| webhooks)
+        .map(|(url, _)| url.clone())
+        .next()
+    else {
+        return Err(NotifierError::Config(
+            "No routes: add profiles to the config file or set LARK_WEBHOOK_URL".to_string(),
+        ));
+    };
+
+    let mut builder = LarkNotifier::builder()
+        .webhook_url(first_url)
+        .retry_policy(RetryPolicy::new(args.retries, Duration::from_millis(500)))
+        .timeout(Duration::from_secs(args.timeout_secs))
+        .allow_insecure_url(args.allow_insecure_url);
+    if let Some(path) = ca_cert(None) {
+        builder = builder.ca_cert(path);
+    }
+    if args.rate_limit > 0 {
+        builder = builder.rate_limiter(RateLimiter::per_minute(args.rate_limit));
+    }
+    let base = builder.build()?;
+
+    let mut routes = BTreeMap::new();
+    for (name, profile, webhooks) in targets {
+        let mut notifiers = Vec::with_capacity(webhooks.len());
+        for (url, secret) in webhooks {
+            notifiers.push(base.with_webhook_url(url)?.with_secret(secret));
+        }
+        routes.insert(name, Route { notifiers, profile });
+    }
+
+    let listener =
+        std::net::TcpListener::bind(args.listen).map_err(|source| NotifierError::Io {
+            context: format!("Failed to listen on {}", args.listen),
+            source,
+        })?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|source| NotifierError::Io {
+            context: format!("Failed to listen on {}", args.listen),
+            source,
+        })?;
+    eprintln!(
+        "Listening on http://{} (routes: {})",
+        args.listen,
+        routes
+            .keys()
+            .map(|name| format!("/{}", name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let routes = Arc::new(routes);
+    let make_service = make_service_fn(move |_| {
+        let routes = Arc::clone(&routes);
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(Arc::clone(&routes), req))) }
+    });
+    let server_error = |e: hyper::Error| NotifierError::Io {
+        context: format!("Server on {} failed", args.listen),
+        source: io::Error::other(e),
+    };
+    Server::from_tcp(listener)
+        .map_err(server_error)?
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .map_err(server_error)
+}
+
+/// Resolves on SIGTERM or Ctrl-C.
+pub(crate) async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = terminate.recv() => {}
+                    _ = tokio::signal::ctrl_c() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+    eprintln!("Shutting down, finishing requests in flight");
+}
+
+/// The payload format a `serve` request is posted in.
+#[derive(Clone, Copy)]
+enum Hook {
+    Plain,
+    Alertmanager,
+    Slack,
+}
+
+/// Forward one `serve` request to the webhooks of its route.
+async fn handle(
+    routes: Arc<BTreeMap<String, Route>>,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let reply = |status: HyperStatus, code: i64, msg: String| {
+        let body = serde_json::to_vec(&ServeResponse { code, msg }).unwrap_or_default();
+        let mut response = Response::new(Body::from(body));
+        *response.status_mut() = status;
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        Ok(response)
+    };
+
+    if req.method() != Method::POST {
+        return reply(
+            HyperStatus::METHOD_NOT_ALLOWED,
+            -1,
+            "Only POST is supported".to_string(),
+        );
+    }
+    // `/alertmanager[/<profile>]` and `/slack[/<profile>]` take those services' webhook payloads.
+    let path = req.uri().path().trim_matches('/').to_string();
+    let (hook, name) = match path.split_once('/') {
+        Some(("alertmanager", name)) => (Hook::Alertmanager, name),
+        Some(("slack", name)) => (Hook::Slack, name),
+        _ if path == "alertmanager" && !routes.contains_key("alertmanager") => {
+            (Hook::Alertmanager, "default")
+        }
+        _ if path == "slack" && !routes.contains_key("slack") => (Hook::Slack, "default"),
+        _ if path.is_empty() => (Hook::Plain, "default"),
+        _ => (Hook::Plain, path.as_str()),
+    };
+    // Slack clients expect a plain-text `ok`, or an error string, rather than JSON.
+    let reply = |status: HyperStatus, code: i64, msg: String| match hook {
+        Hook::Slack => {
+            let body = if status == HyperStatus::OK {
+                "ok".to_string()
+            } else {
+                msg
+            };
+            let mut response = Response::new(Body::from(body));
+            *response.status_mut() = status;
+            response.headers_mut().insert(
+                CONTENT_TYPE,
+                HeaderValue::from_static("text/plain; charset=utf-8"),
+            );
+            Ok(response)
+        }
+        _ => reply(status, code, msg),
+    };
+    let Some(route) = routes.get(name) else {
+        return reply(HyperStatus::NOT_FOUND, -1, format!("No route '/{}'", path));
+    };
+
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(body) => body,
+        Err(e) => {
+            return reply(
+                HyperStatus::BAD_REQUEST,
+                -1,
+                format!("Failed to read request: {}", e),
+            );
+        }
+    };
+    let messages = match hook {
+        Hook::Alertmanager => match serde_json::from_slice::<AlertmanagerPayload>(&body) {
+            Ok(payload) => vec![payload.to_message()],
+            Err(e) => {
+                return reply(
+                    HyperStatus::BAD_REQUEST,
+                    -1,
+                    format!("Invalid Alertmanager payload: {}", e),
+                );
+            }
+        },
+        Hook::Slack => match serde_json::from_slice::<SlackPayload>(&body) {
+            Ok(payload) => vec![payload.to_message()],
+            Err(e) => {
+                return reply(
+                    HyperStatus::BAD_REQUEST,
+                    -1,
+                    format!("invalid_payload: {}", e),
+                );
+            }
+        },
+        Hook::Plain => match serde_json::from_slice::<ServeRequest>(&body) {
+            Ok(request) => match route_message(route, request) {
+                Ok(messages) => messages,
+                Err(msg) => return reply(HyperStatus::BAD_REQUEST, -1, msg),
+            },
+            Err(e) => {
+                return reply(
+                    HyperStatus::BAD_REQUEST,
+                    -1,
+                    format!("Invalid request: {}", e),
+                );
+            }
+        },
+    };
+
+    let results = join_all(
+        route
+            .notifiers
+            .iter()
+            .map(|notifier| deliver(notifier, &messages)),
+    )
+    .await;
+    let failure = results.into_iter().find_map(|(result, _)| result.err());
+    match failure.as_ref().map(NotifierError::final_error) {
+        None => reply(HyperStatus::OK, 0, "success".to_string()),
+        Some(NotifierError::LarkApi { code, msg }) => {
+            reply(HyperStatus::BAD_GATEWAY, *code, msg.clone())
+        }
+        Some(_) => {
+            let err = failure.expect("failure is set");
+            eprintln!("[/{}] Error: {}", path, err);
+            reply(HyperStatus::BAD_GATEWAY, -1, err.to_string())
+        }
+    }
+}
+
+/// Build the post for a plain `serve` request, using the route's profile.
+fn route_message(route: &Route, request: ServeRequest) -> Result<Vec<LarkMessage>, String> {
+    let level = match request
+        .level
+        .as_deref()
+        .map(|level| Level::from_str(level, true))
+    {
+        Some(Ok(level)) => Some(level),
+        Some(Err(_)) => {
+            return Err("Invalid level: expected info, warning, error or critical".to_string());
+        }
+        None => None,
+    };
+
+    let mut title = format!(
+        "{}{}",
+        route.profile.title_prefix.as_deref().unwrap_or_default(),
+        request.title
+    );
+    if let Some(emoji) = level.and_then(|level| level.emoji_for(&route.profile)) {
+        title = format!("{} {}", emoji, title);
+    }
+    let keywords = request
+        .keywords
+        .or_else(|| route.profile.keywords.clone())
+        .unwrap_or_default();
+    let rows = Highlighter::parse(&keywords).highlight_lines(&request.content);
+    Ok(split_message(
+        LarkMessage::post(title, rows),
+        DEFAULT_MAX_BYTES,
+    ))
+}
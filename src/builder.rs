@@ -0,0 +1,138 @@
+use crate::error::NotifierError;
+use crate::keywords::Highlighter;
+use crate::message::{LarkMessage, LarkPost, LarkPostContent, LarkTextContent, Locale};
+use crate::sign::{SignVersion, sign_message};
+
+/// Builds a post message step by step.
+///
+/// ```
+/// use lark_notifier::{Locale, MessageBuilder};
+///
+/// let message = MessageBuilder::new()
+///     .title("Deploy finished")
+///     .locale(Locale::EnUs)
+///     .paragraph("api v1.4.2 is live")
+///     .paragraph("error rate is back to normal")
+///     .keyword("error")
+///     .mention("ou_xxx")
+///     .build()?;
+/// assert_eq!(message.msg_type, "post");
+/// # Ok::<(), lark_notifier::NotifierError>(())
+/// ```
+///
+/// Building fails without a title or any content:
+///
+/// ```
+/// use lark_notifier::MessageBuilder;
+///
+/// assert!(MessageBuilder::new().paragraph("no title").build().is_err());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MessageBuilder {
+    title: Option<String>,
+    locales: Vec<Locale>,
+    paragraphs: Vec<String>,
+    keywords: Vec<String>,
+    mentions: Vec<String>,
+    secret: Option<String>,
+    sign_version: SignVersion,
+}
+
+impl MessageBuilder {
+    pub fn new() -> Self {
+        MessageBuilder::default()
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Add a locale to fill with the post (repeatable); defaults to `zh_cn`.
+    pub fn locale(mut self, locale: Locale) -> Self {
+        if !self.locales.contains(&locale) {
+            self.locales.push(locale);
+        }
+        self
+    }
+
+    /// Add a paragraph; every paragraph is its own row in the post.
+    pub fn paragraph(mut self, text: impl Into<String>) -> Self {
+        self.paragraphs.push(text.into());
+        self
+    }
+
+    /// Highlight `keyword` in every paragraph; `keyword=url` makes it a link.
+    pub fn keyword(mut self, keyword: impl Into<String>) -> Self {
+        self.keywords.push(keyword.into());
+        self
+    }
+
+    /// Mention a user by open_id, or everyone with `all`, on a final row.
+    pub fn mention(mut self, user_id: impl Into<String>) -> Self {
+        self.mentions.push(user_id.into());
+        self
+    }
+
+    /// Sign the message with `secret` when it is built.
+    pub fn sign_with(mut self, secret: impl Into<String>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    /// Sign with `version` instead of Lark's [`SignVersion::HmacSha256`].
+    pub fn sign_version(mut self, version: SignVersion) -> Self {
+        self.sign_version = version;
+        self
+    }
+
+    pub fn build(self) -> Result<LarkMessage, NotifierError> {
+        let title = self
+            .title
+            .filter(|title| !title.trim().is_empty())
+            .ok_or_else(|| NotifierError::InvalidMessage("a post needs a title".to_string()))?;
+
+        let highlighter = Highlighter::parse(&self.keywords);
+        let mut rows: Vec<Vec<LarkTextContent>> = highlighter
+            .highlight_each(self.paragraphs.iter().map(String::as_str))
+            .into_iter()
+            .filter(|row| !row.is_empty())
+            .collect();
+        if rows.is_empty() {
+            return Err(NotifierError::InvalidMessage(
+                "a post needs some content".to_string(),
+            ));
+        }
+        if !self.mentions.is_empty() {
+            let mut row = Vec::new();
+            for user_id in &self.mentions {
+                row.push(LarkTextContent::at(user_id));
+                row.push(LarkTextContent::text(" "));
+            }
+            row.pop();
+            rows.push(row);
+        }
+
+        let locales = if self.locales.is_empty() {
+            vec![Locale::ZhCn]
+        } else {
+            self.locales
+        };
+        let mut post = LarkPost::default();
+        for locale in locales {
+            post.set(
+                locale,
+                LarkPostContent {
+                    title: title.clone(),
+                    content: rows.clone(),
+                },
+            );
+        }
+
+        let mut message = LarkMessage::from_post(post);
+        if let Some(secret) = &self.secret {
+            sign_message(&mut message, secret, self.sign_version)?;
+        }
+        Ok(message)
+    }
+}
@@ -0,0 +1,194 @@
+use crate::message::LarkTextContent;
+
+/// One item of a CI footer, e.g. `Commit: abc1234` linked to the commit.
+#[derive(Debug, Clone)]
+pub struct CiField {
+    pub label: &'static str,
+    pub text: String,
+    pub href: Option<String>,
+}
+
+/// Build details appended to a message sent from a CI job.
+#[derive(Debug, Clone, Default)]
+pub struct CiFooter {
+    pub fields: Vec<CiField>,
+    /// Environment variables that were expected but not set; their fields are left out.
+    pub missing: Vec<&'static str>,
+}
+
+/// A CI system whose environment describes the job that is running.
+///
+/// Implementations only read variables through `env`, normally
+/// `|name| std::env::var(name).ok()`, so they can be fed a fixed environment.
+pub trait CiProvider {
+    /// Human-readable name, e.g. "GitHub Actions".
+    fn name(&self) -> &'static str;
+
+    /// Command-line flag that enables the provider, without dashes.
+    fn flag(&self) -> &'static str;
+
+    /// Whether a footer with missing variables should be refused rather than sent without them.
+    fn requires_all(&self) -> bool {
+        false
+    }
+
+    fn footer(&self, env: &dyn Fn(&str) -> Option<String>) -> CiFooter;
+}
+
+/// Variables GitHub Actions sets for every job.
+pub struct GitHub;
+
+impl CiProvider for GitHub {
+    fn name(&self) -> &'static str {
+        "GitHub Actions"
+    }
+
+    fn flag(&self) -> &'static str {
+        "github"
+    }
+
+    fn footer(&self, env: &dyn Fn(&str) -> Option<String>) -> CiFooter {
+        let mut footer = CiFooter::default();
+        let server = env("GITHUB_SERVER_URL").unwrap_or_else(|| "https://github.com".to_string());
+        let repo = footer.var(env, "GITHUB_REPOSITORY");
+        let git_ref = footer.var(env, "GITHUB_REF_NAME");
+        let sha = footer.var(env, "GITHUB_SHA");
+        let actor = footer.var(env, "GITHUB_ACTOR");
+        let run_id = footer.var(env, "GITHUB_RUN_ID");
+
+        let repo_url = repo
+            .as_ref()
+            .map(|repo| format!("{}/{}", server.trim_end_matches('/'), repo));
+        if let Some(repo) = &repo {
+            footer.push("Repo", repo, repo_url.clone());
+        }
+        if let Some(git_ref) = git_ref {
+            footer.push("Ref", git_ref, None);
+        }
+        if let Some(sha) = sha {
+            let href = repo_url
+                .as_ref()
+                .map(|url| format!("{}/commit/{}", url, sha));
+            footer.push("Commit", sha.chars().take(7).collect::<String>(), href);
+        }
+        if let Some(actor) = actor {
+            footer.push("Actor", actor, None);
+        }
+        if let Some(run_id) = run_id {
+            let href = repo_url
+                .as_ref()
+                .map(|url| format!("{}/actions/runs/{}", url, run_id));
+            footer.push("Run", format!("#{}", run_id), href);
+        }
+        footer
+    }
+}
+
+/// Predefined variables of a GitLab CI job.
+pub struct GitLab;
+
+impl CiProvider for GitLab {
+    fn name(&self) -> &'static str {
+        "GitLab CI"
+    }
+
+    fn flag(&self) -> &'static str {
+        "gitlab"
+    }
+
+    fn requires_all(&self) -> bool {
+        true
+    }
+
+    fn footer(&self, env: &dyn Fn(&str) -> Option<String>) -> CiFooter {
+        let mut footer = CiFooter::default();
+        let project = footer.var(env, "CI_PROJECT_PATH");
+        let pipeline_url = footer.var(env, "CI_PIPELINE_URL");
+        let sha = footer.var(env, "CI_COMMIT_SHORT_SHA");
+        let git_ref = footer.var(env, "CI_COMMIT_REF_NAME");
+        let job = footer.var(env, "CI_JOB_NAME");
+        let user = footer.var(env, "GITLAB_USER_NAME");
+
+        // Pipeline URLs look like <project URL>/-/pipelines/<id>.
+        let project_url = env("CI_PROJECT_URL").or_else(|| {
+            let url = pipeline_url.as_deref()?;
+            url.split_once("/-/pipelines/")
+                .map(|(project, _)| project.to_string())
+        });
+        if let Some(project) = project {
+            footer.push("Project", project, project_url.clone());
+        }
+        if let Some(git_ref) = git_ref {
+            footer.push("Ref", git_ref, None);
+        }
+        if let Some(sha) = sha {
+            let href = project_url
+                .as_ref()
+                .map(|url| format!("{}/-/commit/{}", url, sha));
+            footer.push("Commit", sha, href);
+        }
+        if let Some(job) = job {
+            footer.push("Job", job, env("CI_JOB_URL"));
+        }
+        if let Some(user) = user {
+            footer.push("User", user, None);
+        }
+        if let Some(url) = pipeline_url {
+            let id = url.rsplit('/').next().unwrap_or_default().to_string();
+            footer.push("Pipeline", format!("#{}", id), Some(url));
+        }
+        footer
+    }
+}
+
+impl CiFooter {
+    /// Look up `name`, recording it as missing when it is unset or empty.
+    fn var(&mut self, env: &dyn Fn(&str) -> Option<String>, name: &'static str) -> Option<String> {
+        let value = env(name).filter(|value| !value.is_empty());
+        if value.is_none() {
+            self.missing.push(name);
+        }
+        value
+    }
+
+    fn push(&mut self, label: &'static str, text: impl Into<String>, href: Option<String>) {
+        self.fields.push(CiField {
+            label,
+            text: text.into(),
+            href,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// The footer as a single post row, with links as `a` elements.
+    pub fn row(&self) -> Vec<LarkTextContent> {
+        let mut row = Vec::new();
+        for (i, field) in self.fields.iter().enumerate() {
+            let separator = if i == 0 { "" } else { " | " };
+            row.push(LarkTextContent::text(format!(
+                "{}{}: ",
+                separator, field.label
+            )));
+            row.push(match &field.href {
+                Some(href) => LarkTextContent::link(&field.text, href),
+                None => LarkTextContent::text(&field.text),
+            });
+        }
+        row
+    }
+
+    /// The footer as plain text, for text messages; links are spelled out.
+    pub fn to_text(&self) -> String {
+        self.fields
+            .iter()
+            .map(|field| match &field.href {
+                Some(href) => format!("{}: {} ({})", field.label, field.text, href),
+                None => format!("{}: {}", field.label, field.text),
+            })
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+}
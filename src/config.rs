@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::NotifierError;
+
+/// A TOML config file holding named profiles:
+///
+/// ```toml
+/// [profiles.prod]
+/// webhook_url = "https://open.larksuite.com/open-apis/bot/v2/hook/xxx"
+/// secret = "..."
+/// keywords = ["error", "timeout"]
+/// title_prefix = "[prod] "
+///
+/// [profiles.prod.levels.critical]
+/// emoji = "🚨"
+/// color = "carmine"
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Config {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Defaults for one webhook target; every field is optional.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Profile {
+    pub webhook_url: Option<String>,
+    pub secret: Option<String>,
+    pub keywords: Option<Vec<String>>,
+    /// Prepended to every message title.
+    pub title_prefix: Option<String>,
+    /// Overrides for how `--level` decorates messages, keyed by level name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub levels: BTreeMap<String, LevelStyle>,
+}
+
+/// How one severity level is shown; unset fields keep the built-in default.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LevelStyle {
+    /// Put before post titles and text messages; empty for none.
+    pub emoji: Option<String>,
+    /// Card header color, e.g. `red` or `carmine`.
+    pub color: Option<String>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/lark-notifier/config.toml`, falling back to `~/.config`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("lark-notifier").join("config.toml"))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, NotifierError> {
+        let text = fs::read_to_string(path).map_err(|e| {
+            NotifierError::Config(format!(
+                "Failed to read config file {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+        toml::from_str(&text).map_err(|e| {
+            NotifierError::Config(format!(
+                "Failed to parse config file {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    pub fn profile(&self, name: &str) -> Result<&Profile, NotifierError> {
+        self.profiles.get(name).ok_or_else(|| {
+            let available: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            let available = if available.is_empty() {
+                "none".to_string()
+            } else {
+                available.join(", ")
+            };
+            NotifierError::Config(format!(
+                "Profile '{}' not found in config (available: {})",
+                name, available
+            ))
+        })
+    }
+}
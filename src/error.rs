@@ -0,0 +1,136 @@
+use std::io;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum NotifierError {
+    /// The config file could not be read or is invalid.
+    #[error("{0}")]
+    Config(String),
+    /// A required setting was given neither as an argument nor in the environment.
+    #[error("Missing {0} environment variable or command argument")]
+    MissingConfig(String),
+    /// A message template could not be read or rendered.
+    #[error("{0}")]
+    Template(String),
+    /// Command-line arguments are missing or inconsistent.
+    #[error("{0}")]
+    Usage(String),
+    /// No webhook URL was given to the builder.
+    #[error("Missing webhook URL")]
+    MissingWebhookUrl,
+    /// The webhook URL could not be parsed or does not look like a Lark bot webhook.
+    /// `url` has its token masked.
+    #[error("Invalid webhook URL {url}: {}", problems.join("; "))]
+    InvalidWebhookUrl { url: String, problems: Vec<String> },
+    /// The proxy URL could not be parsed.
+    #[error("Invalid proxy URL: {0}")]
+    InvalidProxy(String),
+    /// A message was built without something it requires, e.g. a post without a title.
+    #[error("Invalid message: {0}")]
+    InvalidMessage(String),
+    /// The message is larger than the payload limit and could not be made to fit.
+    #[error("Message payload is {size} bytes, over the limit of {max} bytes")]
+    TooLarge { size: usize, max: usize },
+    /// A file given for upload is not an image the open API accepts.
+    #[error("Cannot upload {name}: {reason}")]
+    InvalidImage { name: String, reason: String },
+    /// A CA certificate file could not be loaded.
+    #[error("Invalid CA certificate file {name}: {reason}")]
+    InvalidCertificate { name: String, reason: String },
+    /// A raw JSON payload is not a usable message.
+    #[error("Invalid raw JSON payload: {0}")]
+    InvalidPayload(String),
+    /// The message could not be signed.
+    #[error("Failed to sign message: {0}")]
+    Signature(String),
+    /// Reading message content failed.
+    #[error("{context}: {source}")]
+    Io { context: String, source: io::Error },
+    /// The message could not be serialized.
+    #[error("Failed to serialize message: {0}")]
+    Json(#[from] serde_json::Error),
+    /// Connecting through the configured proxy failed.
+    #[error("Failed to connect through proxy {proxy}: {source}")]
+    Proxy {
+        proxy: String,
+        source: reqwest::Error,
+    },
+    /// Connecting or the whole request did not complete within the configured timeout.
+    #[error("Request to {url} timed out after {after:?}")]
+    Timeout { url: String, after: Duration },
+    /// The HTTP request itself failed.
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    /// The webhook answered with a non-2xx status.
+    #[error("Webhook returned {status}: {body}")]
+    Status { status: StatusCode, body: String },
+    /// Lark accepted the request but rejected the message with a non-zero code.
+    #[error("Lark rejected the notification: code {code}: {msg}{}", hint(*code))]
+    LarkApi { code: i64, msg: String },
+    /// The local clock is too far from the webhook server's for signatures to be accepted.
+    #[error(
+        "Local clock is {skew}s off from the webhook server; Lark rejects signatures more than an hour off"
+    )]
+    ClockSkew { skew: i64 },
+    /// Every attempt failed; `last` is the error from the final one.
+    #[error("{last} (after {attempts} attempts, waited {waited:.1?} between them)")]
+    RetriesExhausted {
+        attempts: u32,
+        /// Total time spent sleeping before retries.
+        waited: Duration,
+        #[source]
+        last: Box<NotifierError>,
+    },
+}
+
+impl NotifierError {
+    /// True if the (final) failure was a timeout.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self.final_error(), NotifierError::Timeout { .. })
+    }
+
+    /// The error from the final attempt, looking through [`RetriesExhausted`](Self::RetriesExhausted).
+    pub fn final_error(&self) -> &NotifierError {
+        match self {
+            NotifierError::RetriesExhausted { last, .. } => last.final_error(),
+            other => other,
+        }
+    }
+
+    /// Whether sending again later might succeed: the webhook could not be
+    /// reached, or answered 429 or 5xx, rather than rejecting the message.
+    pub fn is_transient(&self) -> bool {
+        match self.final_error() {
+            NotifierError::Http(_)
+            | NotifierError::Proxy { .. }
+            | NotifierError::Timeout { .. } => true,
+            NotifierError::Status { status, .. } => {
+                *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Lark's code for a missing, wrong or expired signature.
+pub const SIGN_MISMATCH: i64 = 19021;
+
+/// Extra advice for Lark error codes with a common, non-obvious cause.
+fn hint(code: i64) -> &'static str {
+    match code {
+        SIGN_MISMATCH => {
+            " (signature rejected — check the secret, and that this machine's clock is within 1 hour of Lark's servers)"
+        }
+        10014 => " (check --app-id and --app-secret)",
+        99991663 => " (the tenant access token is invalid or has expired)",
+        230002 => " (the bot is not in the chat; add it to the group)",
+        230006 => " (enable the bot capability for the app and publish a new version)",
+        99991672 => {
+            " (the app lacks a permission this needs; grant it in the developer console and publish a new version)"
+        }
+        _ => "",
+    }
+}
@@ -0,0 +1,80 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+/// One entry of `journalctl -o json` output.
+#[derive(Debug, Clone, Deserialize)]
+pub struct JournalEntry {
+    #[serde(rename = "__CURSOR")]
+    pub cursor: Option<String>,
+    #[serde(rename = "_SYSTEMD_UNIT")]
+    pub unit: Option<String>,
+    #[serde(rename = "SYSLOG_IDENTIFIER")]
+    pub identifier: Option<String>,
+    /// Syslog priority, `0` (emerg) to `7` (debug).
+    #[serde(rename = "PRIORITY", default, deserialize_with = "priority")]
+    pub priority: Option<u8>,
+    #[serde(rename = "MESSAGE", default, deserialize_with = "message")]
+    pub message: String,
+}
+
+impl JournalEntry {
+    /// Parse one line of `journalctl -o json`; `None` if it is not an entry.
+    pub fn parse(line: &str) -> Option<Self> {
+        serde_json::from_str(line).ok()
+    }
+
+    /// The priority's journalctl name, e.g. `err`.
+    pub fn priority_name(&self) -> &'static str {
+        match self.priority {
+            Some(0) => "emerg",
+            Some(1) => "alert",
+            Some(2) => "crit",
+            Some(3) => "err",
+            Some(4) => "warning",
+            Some(5) => "notice",
+            Some(6) => "info",
+            Some(7) => "debug",
+            _ => "unknown",
+        }
+    }
+
+    /// A single content line: `unit [priority] message`.
+    pub fn to_line(&self) -> String {
+        let source = self
+            .unit
+            .as_deref()
+            .or(self.identifier.as_deref())
+            .unwrap_or("journal");
+        format!(
+            "{} [{}] {}",
+            source,
+            self.priority_name(),
+            self.message.trim_end()
+        )
+    }
+}
+
+fn priority<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<u8>, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(priority) => priority.parse().ok(),
+        Value::Number(priority) => priority
+            .as_u64()
+            .and_then(|priority| u8::try_from(priority).ok()),
+        _ => None,
+    })
+}
+
+/// Messages that are not valid UTF-8 are exported as an array of bytes.
+fn message<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(message) => message,
+        Value::Array(bytes) => {
+            let bytes: Vec<u8> = bytes
+                .iter()
+                .filter_map(|byte| byte.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+                .collect();
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+        _ => String::new(),
+    })
+}
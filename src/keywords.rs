@@ -0,0 +1,480 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::iter;
+use std::str::FromStr;
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use caseless::Caseless;
+use regex::Regex;
+
+use crate::message::LarkTextContent;
+
+/// Keywords to highlight in message content and how to match them.
+#[derive(Debug, Clone, Default)]
+pub struct Highlighter {
+    pub keywords: Vec<String>,
+    /// Links for keywords that should become clickable, keyed by keyword.
+    pub links: BTreeMap<String, String>,
+    /// Match keywords regardless of case; highlights keep the content's casing.
+    pub ignore_case: bool,
+    /// Styles applied to the plain text between keywords, e.g. `bold` or `italic`.
+    pub style: Vec<String>,
+    /// Turn bare `http://` and `https://` URLs into links to themselves.
+    pub autolink: bool,
+    /// Regular expressions whose matches are highlighted like keywords.
+    pub patterns: Vec<KeywordPattern>,
+}
+
+/// A match as its byte range in the content and the link it becomes, if any.
+type Match = (usize, usize, Option<String>);
+
+/// A regex keyword, `PATTERN` or `PATTERN=URL`.
+///
+/// The URL may refer to capture groups (`$0` for the whole match, `$1` or
+/// `${name}`), so `JIRA-\d+=https://jira.example.com/browse/$0` links every
+/// ticket ID to its page. The first `=` followed by `http://` or `https://`
+/// starts the URL, so other `=`s stay part of the pattern.
+#[derive(Debug, Clone)]
+pub struct KeywordPattern {
+    pub regex: Regex,
+    pub link: Option<String>,
+}
+
+impl FromStr for KeywordPattern {
+    type Err = regex::Error;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let split = spec.match_indices('=').map(|(i, _)| i).find(|&i| {
+            spec[i + 1..].starts_with("http://") || spec[i + 1..].starts_with("https://")
+        });
+        let (pattern, link) = match split {
+            Some(i) => (&spec[..i], Some(spec[i + 1..].to_string())),
+            None => (spec, None),
+        };
+        Ok(KeywordPattern {
+            regex: Regex::new(pattern)?,
+            link,
+        })
+    }
+}
+
+impl KeywordPattern {
+    /// The first non-empty match at or after `pos`, with its link expanded.
+    fn find_at(&self, content: &str, mut pos: usize) -> Option<Match> {
+        while pos <= content.len() {
+            let captures = self.regex.captures_at(content, pos)?;
+            let found = captures.get(0)?;
+            if found.is_empty() {
+                pos = found.end()
+                    + content[found.end()..]
+                        .chars()
+                        .next()
+                        .map_or(1, char::len_utf8);
+                continue;
+            }
+            let href = self.link.as_ref().map(|link| {
+                let mut href = String::new();
+                captures.expand(link, &mut href);
+                href
+            });
+            return Some((found.start(), found.end(), href));
+        }
+        None
+    }
+}
+
+impl Highlighter {
+    pub fn new(keywords: Vec<String>) -> Self {
+        Highlighter {
+            keywords,
+            ..Default::default()
+        }
+    }
+
+    /// Build from `keyword` or `keyword=url` specs; see [`parse_keyword`].
+    pub fn parse<S: AsRef<str>>(specs: &[S]) -> Self {
+        let mut highlighter = Highlighter::default();
+        for spec in specs {
+            let (keyword, url) = parse_keyword(spec.as_ref());
+            if let Some(url) = url {
+                highlighter
+                    .links
+                    .insert(keyword.to_string(), url.to_string());
+            }
+            highlighter.keywords.push(keyword.to_string());
+        }
+        highlighter
+    }
+
+    /// Link `keyword` to `href` instead of only highlighting it.
+    pub fn with_link(mut self, keyword: impl Into<String>, href: impl Into<String>) -> Self {
+        let keyword = keyword.into();
+        if !self.keywords.contains(&keyword) {
+            self.keywords.push(keyword.clone());
+        }
+        self.links.insert(keyword, href.into());
+        self
+    }
+
+    /// Match keywords regardless of case, keeping the content's own casing.
+    ///
+    /// ```
+    /// use lark_notifier::Highlighter;
+    ///
+    /// let highlighter = Highlighter::new(vec!["error".to_string(), "STRASSE".to_string()]).ignore_case(true);
+    /// let elements = highlighter.highlight("Error in Straße, ERROR again");
+    /// let bold: Vec<&str> = elements
+    ///     .iter()
+    ///     .filter(|element| element.has_style("bold"))
+    ///     .map(|element| element.text.as_str())
+    ///     .collect();
+    /// assert_eq!(bold, ["Error", "Straße", "ERROR"]);
+    /// ```
+    pub fn ignore_case(mut self, ignore_case: bool) -> Self {
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    pub fn style(mut self, style: Vec<String>) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn autolink(mut self, autolink: bool) -> Self {
+        self.autolink = autolink;
+        self
+    }
+
+    /// Also highlight every match of `patterns`.
+    ///
+    /// Regex and plain keyword matches compete like keywords do: the earliest
+    /// wins, then the longest, then plain keywords before patterns.
+    ///
+    /// ```
+    /// use lark_notifier::Highlighter;
+    ///
+    /// let highlighter = Highlighter::parse(&["deploy"])
+    ///     .patterns(vec![r"JIRA-\d+=https://jira.example.com/browse/$0".parse().unwrap()]);
+    /// let elements = highlighter.highlight("JIRA-42 deploy; see JIRA-7");
+    /// assert_eq!(elements[0].href.as_deref(), Some("https://jira.example.com/browse/JIRA-42"));
+    /// assert_eq!(elements[2].text, "deploy");
+    /// assert_eq!(elements[4].href.as_deref(), Some("https://jira.example.com/browse/JIRA-7"));
+    /// ```
+    pub fn patterns(mut self, patterns: Vec<KeywordPattern>) -> Self {
+        self.patterns = patterns;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keywords.iter().all(|keyword| keyword.is_empty()) && self.patterns.is_empty()
+    }
+
+    /// Split `content` into plain text and highlighted keyword elements.
+    ///
+    /// Matches are found in a single left-to-right scan regardless of the order
+    /// keywords are listed in, so concatenating the `text` of the returned
+    /// elements always reproduces `content` exactly.
+    ///
+    /// With [`autolink`](Self::autolink), URLs are linked first and keywords
+    /// are only searched for in the text between them, so a keyword inside a
+    /// URL never breaks the link.
+    pub fn highlight(&self, content: &str) -> Vec<LarkTextContent> {
+        self.matcher().highlight(content)
+    }
+
+    /// Highlight line by line, producing one paragraph row per line.
+    pub fn highlight_lines(&self, content: &str) -> Vec<Vec<LarkTextContent>> {
+        self.highlight_each(content.lines())
+    }
+
+    /// Highlight each of `parts` as its own row, preparing the keywords only once.
+    pub fn highlight_each<'a>(
+        &self,
+        parts: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<Vec<LarkTextContent>> {
+        let matcher = self.matcher();
+        parts
+            .into_iter()
+            .map(|part| matcher.highlight(part))
+            .collect()
+    }
+
+    /// The keywords compiled for matching, to highlight any number of texts with.
+    pub(crate) fn matcher(&self) -> Matcher<'_> {
+        let fold = |keyword: &str| -> String {
+            if self.ignore_case {
+                keyword.chars().default_case_fold().collect()
+            } else {
+                keyword.to_string()
+            }
+        };
+        let (indices, needles): (Vec<usize>, Vec<String>) = self
+            .keywords
+            .iter()
+            .enumerate()
+            .filter(|(_, keyword)| !keyword.is_empty())
+            .map(|(i, keyword)| (i, fold(keyword)))
+            .unzip();
+        // Standard semantics, to report every match including overlapping ones;
+        // which of them win is decided in `Scan::next_match`.
+        let automaton = (!needles.is_empty()).then(|| {
+            AhoCorasick::builder()
+                .match_kind(MatchKind::Standard)
+                .build(&needles)
+                .expect("keyword automaton within the default size limits")
+        });
+        Matcher {
+            highlighter: self,
+            automaton,
+            indices,
+        }
+    }
+
+    /// A text element for non-keyword content, carrying the configured style.
+    fn plain(&self, text: &str) -> LarkTextContent {
+        LarkTextContent {
+            style: (!self.style.is_empty()).then(|| self.style.clone()),
+            ..LarkTextContent::text(text)
+        }
+    }
+}
+
+/// A [`Highlighter`] with its keywords compiled into one automaton, so each
+/// text is scanned once however many keywords there are.
+pub(crate) struct Matcher<'h> {
+    highlighter: &'h Highlighter,
+    /// The non-empty keywords, case folded when ignoring case.
+    automaton: Option<AhoCorasick>,
+    /// Each automaton pattern's index in `highlighter.keywords`.
+    indices: Vec<usize>,
+}
+
+impl Matcher<'_> {
+    /// See [`Highlighter::highlight`].
+    pub(crate) fn highlight(&self, content: &str) -> Vec<LarkTextContent> {
+        if !self.highlighter.autolink {
+            return self.highlight_keywords(content);
+        }
+
+        let mut result = Vec::new();
+        let mut pos = 0;
+        while let Some((start, end)) = next_url(content, pos) {
+            result.extend(self.highlight_keywords(&content[pos..start]));
+            result.push(LarkTextContent::link(
+                &content[start..end],
+                &content[start..end],
+            ));
+            pos = end;
+        }
+        result.extend(self.highlight_keywords(&content[pos..]));
+        result
+    }
+
+    fn highlight_keywords(&self, content: &str) -> Vec<LarkTextContent> {
+        let highlighter = self.highlighter;
+        if content.is_empty() {
+            return Vec::new();
+        }
+        if highlighter.is_empty() {
+            return vec![highlighter.plain(content)];
+        }
+
+        let mut result = Vec::new();
+        let mut pos = 0;
+
+        // Repeatedly take the earliest match of any keyword after `pos`, so every
+        // occurrence is highlighted in document order.
+        let mut scan = self.scan(content);
+        while let Some((start, end, href)) = scan.next_match(pos) {
+            if start > pos {
+                result.push(highlighter.plain(&content[pos..start]));
+            }
+
+            // Linked keywords become `a` elements; an empty href renders as a
+            // dead link, so the rest are highlighted in bold instead.
+            let matched = &content[start..end];
+            result.push(match href {
+                Some(href) => LarkTextContent::link(matched, href),
+                None => LarkTextContent::bold(matched),
+            });
+
+            pos = end;
+        }
+
+        if pos < content.len() {
+            result.push(highlighter.plain(&content[pos..]));
+        }
+
+        result
+    }
+
+    /// Every keyword match in `content`, found in one pass over it, ready for
+    /// [`Scan::next_match`].
+    fn scan<'a>(&'a self, content: &'a str) -> Scan<'a> {
+        let mut keywords: Vec<(usize, usize, usize)> = match &self.automaton {
+            None => Vec::new(),
+            Some(automaton) if self.highlighter.ignore_case => {
+                // Ignoring case compares Unicode case folds, so "STRASSE" matches
+                // "Straße"; a match must start and end on whole characters of
+                // `content`. `boundaries` holds, for every character, where its
+                // fold starts; no character folds to nothing, so both increase.
+                let mut folded = String::with_capacity(content.len());
+                let mut boundaries = Vec::with_capacity(content.len() + 1);
+                for (offset, c) in content.char_indices() {
+                    boundaries.push((folded.len(), offset));
+                    folded.extend(iter::once(c).default_case_fold());
+                }
+                boundaries.push((folded.len(), content.len()));
+                let original = |folded_offset: usize| {
+                    boundaries
+                        .binary_search_by_key(&folded_offset, |&(folded, _)| folded)
+                        .ok()
+                        .map(|i| boundaries[i].1)
+                };
+                automaton
+                    .find_overlapping_iter(&folded)
+                    .filter_map(|found| {
+                        let (start, end) = (original(found.start())?, original(found.end())?);
+                        Some((start, end, self.indices[found.pattern().as_usize()]))
+                    })
+                    .collect()
+            }
+            Some(automaton) => automaton
+                .find_overlapping_iter(content)
+                .map(|found| {
+                    (
+                        found.start(),
+                        found.end(),
+                        self.indices[found.pattern().as_usize()],
+                    )
+                })
+                .collect(),
+        };
+        keywords.sort_unstable_by_key(|&(start, end, keyword)| (start, Reverse(end), keyword));
+        Scan {
+            highlighter: self.highlighter,
+            content,
+            keywords,
+            next_keyword: 0,
+            patterns: vec![None; self.highlighter.patterns.len()],
+        }
+    }
+}
+
+/// The matches in one text, consumed left to right.
+struct Scan<'a> {
+    highlighter: &'a Highlighter,
+    content: &'a str,
+    /// Keyword matches as `(start, end, keyword index)`, best first at each start.
+    keywords: Vec<(usize, usize, usize)>,
+    next_keyword: usize,
+    /// Each pattern's next match as last found, `Some(None)` once it has no more.
+    patterns: Vec<Option<Option<Match>>>,
+}
+
+impl Scan<'_> {
+    /// Earliest keyword or pattern match starting at or after `pos`, with its link.
+    ///
+    /// When several keywords match at the same position the longest wins, so
+    /// "error code" is highlighted whole rather than as "error" plus " code";
+    /// remaining ties go to the keyword listed first, and keywords come before patterns.
+    fn next_match(&mut self, pos: usize) -> Option<Match> {
+        while self
+            .keywords
+            .get(self.next_keyword)
+            .is_some_and(|&(start, _, _)| start < pos)
+        {
+            self.next_keyword += 1;
+        }
+        let keyword = self
+            .keywords
+            .get(self.next_keyword)
+            .map(|&(start, end, keyword)| {
+                let href = self
+                    .highlighter
+                    .links
+                    .get(&self.highlighter.keywords[keyword])
+                    .cloned();
+                (start, end, href)
+            });
+
+        // A pattern's match stays its next one until `pos` passes its start.
+        for (pattern, next) in self.highlighter.patterns.iter().zip(&mut self.patterns) {
+            if next
+                .as_ref()
+                .is_none_or(|found| found.as_ref().is_some_and(|&(start, _, _)| start < pos))
+            {
+                *next = Some(pattern.find_at(self.content, pos));
+            }
+        }
+        let patterns = self
+            .patterns
+            .iter()
+            .filter_map(|next| next.clone().flatten());
+
+        keyword
+            .into_iter()
+            .chain(patterns)
+            .min_by_key(|(start, end, _)| (*start, Reverse(*end)))
+    }
+}
+
+/// Byte range of the first bare URL at or after `pos`.
+///
+/// A URL runs until whitespace or a quote or angle bracket. Trailing
+/// punctuation is left out, and so is a closing parenthesis without a matching
+/// opening one, so "(see https://example.com)." links just the URL.
+fn next_url(content: &str, pos: usize) -> Option<(usize, usize)> {
+    let mut from = pos;
+    loop {
+        let start = from
+            + ["https://", "http://"]
+                .iter()
+                .filter_map(|scheme| content[from..].find(scheme))
+                .min()?;
+        let rest = &content[start..];
+        let mut url = &rest[..rest
+            .find(|c: char| c.is_whitespace() || "<>\"'`".contains(c))
+            .unwrap_or(rest.len())];
+        loop {
+            let trimmed = url.trim_end_matches(['.', ',', ';', ':', '!', '?']);
+            let trimmed = match trimmed.strip_suffix(')') {
+                Some(open) if open.matches('(').count() < trimmed.matches(')').count() => open,
+                _ => trimmed,
+            };
+            if trimmed.len() == url.len() {
+                break;
+            }
+            url = trimmed;
+        }
+        if url.contains("://") && !url.ends_with("://") {
+            return Some((start, start + url.len()));
+        }
+        from = start + 1;
+    }
+}
+
+/// Split a `keyword=url` spec at the first `=`; a bare keyword has no link.
+///
+/// Only the first `=` separates, so URLs with query strings survive intact.
+pub fn parse_keyword(spec: &str) -> (&str, Option<&str>) {
+    match spec.split_once('=') {
+        Some((keyword, url)) if !url.is_empty() => (keyword.trim(), Some(url.trim())),
+        _ => (spec.trim(), None),
+    }
+}
+
+/// Highlight `keywords` in `content`; see [`Highlighter::highlight`].
+///
+/// Keywords written as `keyword=url` become links to `url`.
+pub fn process_content_with_keywords(content: &str, keywords: &[String]) -> Vec<LarkTextContent> {
+    Highlighter::parse(keywords).highlight(content)
+}
+
+/// Highlight keywords line by line, producing one paragraph row per line.
+pub fn process_lines_with_keywords(
+    content: &str,
+    keywords: &[String],
+) -> Vec<Vec<LarkTextContent>> {
+    Highlighter::parse(keywords).highlight_lines(content)
+}
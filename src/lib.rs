@@ -0,0 +1,75 @@
+//! Send notifications to Lark (Feishu) custom bot webhooks.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), lark_notifier::NotifierError> {
+//! let notifier = lark_notifier::LarkNotifier::builder()
+//!     .webhook_url("https://open.larksuite.com/open-apis/bot/v2/hook/your-webhook")
+//!     .secret("your-secret")
+//!     .build()?;
+//! notifier.send_post("Deploy", "deploy finished", &["deploy".to_string()]).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod alertmanager;
+mod ansi;
+mod builder;
+mod ci;
+mod config;
+mod error;
+#[cfg(feature = "journal")]
+mod journal;
+mod keywords;
+mod markdown;
+mod message;
+mod notifier;
+mod openapi;
+mod proxy;
+mod queue;
+mod rate;
+mod region;
+mod retry;
+mod sign;
+mod slack;
+mod split;
+mod tail;
+mod template;
+mod tls;
+
+pub use alertmanager::{Alert, AlertmanagerPayload};
+pub use ansi::strip_ansi;
+pub use builder::MessageBuilder;
+pub use ci::{CiField, CiFooter, CiProvider, GitHub, GitLab};
+pub use config::{Config, LevelStyle, Profile};
+pub use error::{NotifierError, SIGN_MISMATCH};
+#[cfg(feature = "journal")]
+pub use journal::JournalEntry;
+pub use keywords::{
+    Highlighter, KeywordPattern, parse_keyword, process_content_with_keywords,
+    process_lines_with_keywords,
+};
+pub use markdown::markdown_to_rows;
+pub use message::{
+    CardButton, CardElement, CardHeader, CardText, InteractiveCard, LarkContent, LarkMessage,
+    LarkPost, LarkPostContent, LarkTextContent, Locale, elements_to_lark_md,
+};
+pub use notifier::{
+    ALLOW_INSECURE_URL, LarkNotifier, LarkNotifierBuilder, LarkResponse, SendPolicy, SendResponse,
+    send_message, send_with_policy, validate_webhook_url,
+};
+pub use openapi::{
+    AppClient, INVALID_ACCESS_TOKEN, MAX_IMAGE_BYTES, chat_message_body, check_image,
+};
+pub use proxy::redact_credentials;
+pub use queue::{FlushReport, Queue, QueuedMessage};
+pub use rate::RateLimiter;
+pub use region::Region;
+pub use retry::RetryPolicy;
+pub use sign::{
+    SignVersion, generate_sign, sign_digest, sign_message, sign_message_at, string_to_sign,
+};
+pub use slack::{SlackAttachment, SlackField, SlackPayload, mrkdwn_to_lark_md};
+pub use split::{DEFAULT_MAX_BYTES, payload_size, split_message, truncate_message};
+pub use tail::Tail;
+pub use template::{Template, interpolate_env};
+pub use tls::{parse_ca_certs, read_ca_certs};
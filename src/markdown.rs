@@ -0,0 +1,153 @@
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+use crate::keywords::{Highlighter, Matcher};
+use crate::message::LarkTextContent;
+
+/// Convert Markdown into post paragraph rows.
+///
+/// Links become `a` elements, without their title since post links have none.
+/// Every block and line break starts a new row and list items are prefixed with
+/// bullets indented by nesting depth. Strong text and headings are styled bold,
+/// other emphasis is dropped and anything without a post equivalent (tables,
+/// images, HTML) degrades to its plain text.
+pub fn markdown_to_rows(markdown: &str, highlighter: &Highlighter) -> Vec<Vec<LarkTextContent>> {
+    let mut renderer = Renderer {
+        matcher: highlighter.matcher(),
+        rows: Vec::new(),
+        row: Vec::new(),
+        lists: Vec::new(),
+        link: None,
+        bold: 0,
+        in_code_block: false,
+    };
+
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    for event in Parser::new_ext(markdown, options) {
+        renderer.event(event);
+    }
+
+    renderer.flush();
+    renderer.rows
+}
+
+struct Renderer<'h> {
+    matcher: Matcher<'h>,
+    rows: Vec<Vec<LarkTextContent>>,
+    row: Vec<LarkTextContent>,
+    /// Next item number for each open list; `None` for bullet lists.
+    lists: Vec<Option<u64>>,
+    /// Destination and collected text of the link being rendered.
+    link: Option<(String, String)>,
+    /// How many strong or heading spans are open.
+    bold: usize,
+    in_code_block: bool,
+}
+
+impl Renderer<'_> {
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(Tag::List(start)) => {
+                self.flush();
+                self.lists.push(start);
+            }
+            Event::End(TagEnd::List(_)) => {
+                self.lists.pop();
+            }
+            Event::Start(Tag::Item) => {
+                self.flush();
+                let depth = self.lists.len().saturating_sub(1);
+                let marker = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "• ".to_string(),
+                };
+                self.plain(format!("{}{}", "  ".repeat(depth), marker));
+            }
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                self.link = Some((dest_url.to_string(), String::new()));
+            }
+            Event::End(TagEnd::Link) => {
+                if let Some((href, text)) = self.link.take() {
+                    self.row.push(LarkTextContent::link(text, href));
+                }
+            }
+            Event::Start(Tag::Strong | Tag::Heading { .. }) => {
+                self.bold += 1;
+            }
+            Event::End(TagEnd::Strong) => {
+                self.bold = self.bold.saturating_sub(1);
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                self.flush();
+                self.in_code_block = true;
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                self.in_code_block = false;
+            }
+            Event::Start(Tag::TableCell) if !self.row.is_empty() => {
+                self.plain(" | ".to_string());
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                self.bold = self.bold.saturating_sub(1);
+                self.flush();
+            }
+            Event::End(TagEnd::Paragraph | TagEnd::Item | TagEnd::TableHead | TagEnd::TableRow) => {
+                self.flush()
+            }
+            Event::Text(text) if self.in_code_block => {
+                for line in text.lines() {
+                    self.plain(line.to_string());
+                    self.flush();
+                }
+            }
+            Event::Text(text) | Event::Code(text) | Event::Html(text) | Event::InlineHtml(text) => {
+                if let Some((_, link_text)) = &mut self.link {
+                    link_text.push_str(&text);
+                } else {
+                    let mut elements = self.matcher.highlight(&text);
+                    if self.bold > 0 {
+                        for element in elements
+                            .iter_mut()
+                            .filter(|e| e.tag == "text" && !e.has_style("bold"))
+                        {
+                            element
+                                .style
+                                .get_or_insert_with(Vec::new)
+                                .push("bold".to_string());
+                        }
+                    }
+                    self.row.extend(elements);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some((_, link_text)) = &mut self.link {
+                    link_text.push(' ');
+                } else {
+                    self.flush();
+                }
+            }
+            Event::Rule => {
+                self.flush();
+                self.plain("----".to_string());
+                self.flush();
+            }
+            Event::TaskListMarker(checked) => {
+                self.plain(if checked { "[x] " } else { "[ ] " }.to_string());
+            }
+            _ => {}
+        }
+    }
+
+    fn plain(&mut self, text: String) {
+        self.row.push(LarkTextContent::text(text));
+    }
+
+    fn flush(&mut self) {
+        if !self.row.is_empty() {
+            self.rows.push(std::mem::take(&mut self.row));
+        }
+    }
+}
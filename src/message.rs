@@ -0,0 +1,473 @@
+use crate::error::NotifierError;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LarkMessage {
+    pub msg_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<LarkContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub card: Option<InteractiveCard>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sign: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    /// Any other top-level fields, sent as they are (see [`LarkMessage::raw`]).
+    #[serde(flatten, default, skip_serializing_if = "Map::is_empty")]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum LarkContent {
+    Post { post: LarkPost },
+    Text { text: String },
+    Image { image_key: String },
+    ShareChat { share_chat_id: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LarkPost {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zh_cn: Option<LarkPostContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub en_us: Option<LarkPostContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ja_jp: Option<LarkPostContent>,
+}
+
+impl LarkPost {
+    pub fn set(&mut self, locale: Locale, content: LarkPostContent) {
+        match locale {
+            Locale::ZhCn => self.zh_cn = Some(content),
+            Locale::EnUs => self.en_us = Some(content),
+            Locale::JaJp => self.ja_jp = Some(content),
+        }
+    }
+}
+
+/// A post locale key understood by Lark clients.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Locale {
+    ZhCn,
+    EnUs,
+    JaJp,
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zh_cn" => Ok(Locale::ZhCn),
+            "en_us" => Ok(Locale::EnUs),
+            "ja_jp" => Ok(Locale::JaJp),
+            _ => Err(format!(
+                "unknown locale '{}' (expected zh_cn, en_us or ja_jp)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Locale::ZhCn => "zh_cn",
+            Locale::EnUs => "en_us",
+            Locale::JaJp => "ja_jp",
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LarkPostContent {
+    pub title: String,
+    pub content: Vec<Vec<LarkTextContent>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LarkTextContent {
+    pub tag: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub href: Option<String>,
+    /// Mentioned user for `at` elements; `all` mentions everyone.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    /// Text styles such as `bold`, `italic`, `underline` or `lineThrough`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<Vec<String>>,
+    /// Uploaded image shown by `img` elements.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_key: Option<String>,
+}
+
+impl LarkTextContent {
+    /// A plain `text` element.
+    pub fn text(text: impl Into<String>) -> Self {
+        LarkTextContent {
+            tag: "text".to_string(),
+            text: text.into(),
+            href: None,
+            user_id: None,
+            style: None,
+            image_key: None,
+        }
+    }
+
+    /// An `a` element linking `text` to `href`.
+    pub fn link(text: impl Into<String>, href: impl Into<String>) -> Self {
+        LarkTextContent {
+            tag: "a".to_string(),
+            text: text.into(),
+            href: Some(href.into()),
+            user_id: None,
+            style: None,
+            image_key: None,
+        }
+    }
+
+    /// A bold `text` element.
+    pub fn bold(text: impl Into<String>) -> Self {
+        LarkTextContent {
+            style: Some(vec!["bold".to_string()]),
+            ..LarkTextContent::text(text)
+        }
+    }
+
+    /// Whether this element carries `style`.
+    pub fn has_style(&self, style: &str) -> bool {
+        self.style.iter().flatten().any(|s| s == style)
+    }
+
+    /// An `at` element mentioning `user_id` (`all` for everyone).
+    pub fn at(user_id: impl Into<String>) -> Self {
+        LarkTextContent {
+            tag: "at".to_string(),
+            text: String::new(),
+            href: None,
+            user_id: Some(user_id.into()),
+            style: None,
+            image_key: None,
+        }
+    }
+
+    /// An `img` element showing the uploaded image `image_key`.
+    pub fn image(image_key: impl Into<String>) -> Self {
+        LarkTextContent {
+            tag: "img".to_string(),
+            text: String::new(),
+            href: None,
+            user_id: None,
+            style: None,
+            image_key: Some(image_key.into()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InteractiveCard {
+    pub header: CardHeader,
+    pub elements: Vec<CardElement>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CardHeader {
+    pub title: CardText,
+    /// Header color template, e.g. `blue`, `green`, `orange`, `red`.
+    pub template: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CardText {
+    pub tag: String,
+    pub content: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "tag", rename_all = "snake_case")]
+pub enum CardElement {
+    Div {
+        text: CardText,
+    },
+    Note {
+        elements: Vec<CardText>,
+    },
+    Action {
+        actions: Vec<CardButton>,
+    },
+    /// A horizontal divider.
+    Hr,
+}
+
+/// A card button that opens `url` when clicked.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CardButton {
+    pub tag: String,
+    pub text: CardText,
+    pub url: String,
+    /// Button style: `default`, `primary` or `danger`.
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+impl CardButton {
+    pub fn new(label: impl Into<String>, url: impl Into<String>) -> Self {
+        CardButton {
+            tag: "button".to_string(),
+            text: CardText {
+                tag: "plain_text".to_string(),
+                content: label.into(),
+            },
+            url: url.into(),
+            kind: "default".to_string(),
+        }
+    }
+}
+
+impl InteractiveCard {
+    /// Build a card with a colored header and a single lark_md body.
+    pub fn new(
+        title: impl Into<String>,
+        template: impl Into<String>,
+        markdown: impl Into<String>,
+    ) -> Self {
+        InteractiveCard {
+            header: CardHeader {
+                title: CardText {
+                    tag: "plain_text".to_string(),
+                    content: title.into(),
+                },
+                template: template.into(),
+            },
+            elements: vec![CardElement::Div {
+                text: CardText {
+                    tag: "lark_md".to_string(),
+                    content: markdown.into(),
+                },
+            }],
+        }
+    }
+
+    /// Append a divider and another lark_md section.
+    pub fn with_section(mut self, markdown: impl Into<String>) -> Self {
+        self.elements.push(CardElement::Hr);
+        self.elements.push(CardElement::Div {
+            text: CardText {
+                tag: "lark_md".to_string(),
+                content: markdown.into(),
+            },
+        });
+        self
+    }
+
+    /// Append an action module with `buttons` side by side; does nothing if empty.
+    pub fn with_buttons(mut self, buttons: Vec<CardButton>) -> Self {
+        if !buttons.is_empty() {
+            self.elements.push(CardElement::Action { actions: buttons });
+        }
+        self
+    }
+
+    /// Append a footer note rendered as lark_md.
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.elements.push(CardElement::Note {
+            elements: vec![CardText {
+                tag: "lark_md".to_string(),
+                content: note.into(),
+            }],
+        });
+        self
+    }
+}
+
+impl LarkMessage {
+    /// Build an unsigned rich text post message under the `zh_cn` locale.
+    pub fn post(title: impl Into<String>, content: Vec<Vec<LarkTextContent>>) -> Self {
+        Self::localized_post(&[Locale::ZhCn], title, content)
+    }
+
+    /// Build an unsigned post with the same title and content under each locale.
+    pub fn localized_post(
+        locales: &[Locale],
+        title: impl Into<String>,
+        content: Vec<Vec<LarkTextContent>>,
+    ) -> Self {
+        let content = LarkPostContent {
+            title: title.into(),
+            content,
+        };
+        let mut post = LarkPost::default();
+        for locale in locales {
+            post.set(*locale, content.clone());
+        }
+
+        Self::from_post(post)
+    }
+
+    /// Build an unsigned post from already localized content.
+    pub fn from_post(post: LarkPost) -> Self {
+        LarkMessage {
+            msg_type: "post".to_string(),
+            content: Some(LarkContent::Post { post }),
+            card: None,
+            sign: None,
+            timestamp: None,
+            extra: Map::new(),
+        }
+    }
+
+    /// Build an unsigned plain text message.
+    pub fn text(text: impl Into<String>) -> Self {
+        LarkMessage {
+            msg_type: "text".to_string(),
+            content: Some(LarkContent::Text { text: text.into() }),
+            card: None,
+            sign: None,
+            timestamp: None,
+            extra: Map::new(),
+        }
+    }
+
+    /// Build an unsigned image message from an uploaded image key.
+    pub fn image(image_key: impl Into<String>) -> Self {
+        LarkMessage {
+            msg_type: "image".to_string(),
+            content: Some(LarkContent::Image {
+                image_key: image_key.into(),
+            }),
+            card: None,
+            sign: None,
+            timestamp: None,
+            extra: Map::new(),
+        }
+    }
+
+    /// Build an unsigned message sharing the group chat `share_chat_id`.
+    pub fn share_chat(share_chat_id: impl Into<String>) -> Self {
+        LarkMessage {
+            msg_type: "share_chat".to_string(),
+            content: Some(LarkContent::ShareChat {
+                share_chat_id: share_chat_id.into(),
+            }),
+            card: None,
+            sign: None,
+            timestamp: None,
+            extra: Map::new(),
+        }
+    }
+
+    /// Build an unsigned interactive card message.
+    pub fn card(card: InteractiveCard) -> Self {
+        LarkMessage {
+            msg_type: "interactive".to_string(),
+            content: None,
+            card: Some(card),
+            sign: None,
+            timestamp: None,
+            extra: Map::new(),
+        }
+    }
+
+    /// Build an unsigned interactive card from a card builder template, filling
+    /// in its variables.
+    ///
+    /// ```
+    /// use lark_notifier::LarkMessage;
+    /// use serde_json::{Map, json};
+    ///
+    /// let mut variables = Map::new();
+    /// variables.insert("count".to_string(), json!(3));
+    /// let message = LarkMessage::card_template("AAqk1234", variables);
+    /// assert_eq!(
+    ///     serde_json::to_value(&message).unwrap(),
+    ///     json!({
+    ///         "msg_type": "interactive",
+    ///         "card": { "type": "template", "data": { "template_id": "AAqk1234", "template_variable": { "count": 3 } } },
+    ///     })
+    /// );
+    /// ```
+    pub fn card_template(template_id: impl Into<String>, variables: Map<String, Value>) -> Self {
+        let card = serde_json::json!({
+            "type": "template",
+            "data": { "template_id": template_id.into(), "template_variable": variables },
+        });
+        LarkMessage {
+            msg_type: "interactive".to_string(),
+            content: None,
+            card: None,
+            sign: None,
+            timestamp: None,
+            extra: Map::from_iter([("card".to_string(), card)]),
+        }
+    }
+
+    /// Wrap an arbitrary JSON payload so it is posted verbatim.
+    ///
+    /// The value must be an object; any `sign` and `timestamp` in it are
+    /// dropped, since they are filled in when the message is signed.
+    pub fn raw(value: Value) -> Result<Self, NotifierError> {
+        let Value::Object(mut fields) = value else {
+            return Err(NotifierError::InvalidPayload(format!(
+                "expected a JSON object, got {}",
+                json_kind(&value)
+            )));
+        };
+        let msg_type = match fields.remove("msg_type") {
+            Some(Value::String(msg_type)) => msg_type,
+            Some(_) => {
+                return Err(NotifierError::InvalidPayload(
+                    "msg_type must be a string".to_string(),
+                ));
+            }
+            None => {
+                return Err(NotifierError::InvalidPayload(
+                    "missing msg_type".to_string(),
+                ));
+            }
+        };
+        fields.remove("sign");
+        fields.remove("timestamp");
+
+        Ok(LarkMessage {
+            msg_type,
+            content: None,
+            card: None,
+            sign: None,
+            timestamp: None,
+            extra: fields,
+        })
+    }
+}
+
+fn json_kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Render highlighted elements as lark_md, bolding keywords and keeping links.
+pub fn elements_to_lark_md(elements: &[LarkTextContent]) -> String {
+    elements
+        .iter()
+        .map(|element| match (element.tag.as_str(), &element.user_id) {
+            ("a", _) => match element.href.as_deref() {
+                Some(href) if !href.is_empty() => format!("[{}]({})", element.text, href),
+                _ => format!("**{}**", element.text),
+            },
+            ("text", _) if element.has_style("bold") => format!("**{}**", element.text),
+            ("at", Some(user_id)) => format!("<at id={}></at>", user_id),
+            _ => element.text.clone(),
+        })
+        .collect()
+}
@@ -0,0 +1,812 @@
+use reqwest::header::{CONTENT_TYPE, DATE, RETRY_AFTER};
+use reqwest::{Client, Proxy, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{Level, debug, enabled};
+
+use crate::error::NotifierError;
+use crate::keywords::process_content_with_keywords;
+use crate::message::{InteractiveCard, LarkMessage, elements_to_lark_md};
+use crate::proxy::{env_proxy, redact_credentials};
+use crate::rate::RateLimiter;
+use crate::region::Region;
+use crate::retry::RetryPolicy;
+use crate::sign::{SignVersion, sign_message, sign_message_at, timestamp};
+use crate::tls::read_ca_certs;
+
+/// The JSON body Lark replies with; a non-zero `code` means the message was rejected.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LarkResponse {
+    #[serde(alias = "StatusCode")]
+    pub code: i64,
+    #[serde(default, alias = "StatusMessage")]
+    pub msg: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+/// The HTTP outcome of a webhook call.
+#[derive(Debug)]
+pub struct SendResponse {
+    pub status: StatusCode,
+    pub body: String,
+    /// The parsed body, if it matched Lark's response schema.
+    pub response: Option<LarkResponse>,
+    /// Number of attempts made, including the final one.
+    pub attempts: u32,
+    /// How long the server asked us to wait before retrying (`Retry-After`, in seconds).
+    pub retry_after: Option<Duration>,
+}
+
+impl SendResponse {
+    /// True when the HTTP status is 2xx and Lark did not report an error code.
+    pub fn is_success(&self) -> bool {
+        self.status.is_success() && self.lark_error().is_none()
+    }
+
+    /// The Lark application error, if the body carried a non-zero `code`.
+    pub fn lark_error(&self) -> Option<&LarkResponse> {
+        self.response.as_ref().filter(|response| response.code != 0)
+    }
+
+    /// Turn a non-2xx status or a Lark error code into the matching [`NotifierError`].
+    pub fn error_for_status(self) -> Result<SendResponse, NotifierError> {
+        if let Some(error) = self.lark_error() {
+            return Err(NotifierError::LarkApi {
+                code: error.code,
+                msg: error.msg.clone(),
+            });
+        }
+        if !self.status.is_success() {
+            return Err(NotifierError::Status {
+                status: self.status,
+                body: self.body,
+            });
+        }
+        Ok(self)
+    }
+
+    /// The id of the message created, when sent through the open API.
+    pub fn message_id(&self) -> Option<&str> {
+        self.response
+            .as_ref()?
+            .data
+            .as_ref()?
+            .get("message_id")?
+            .as_str()
+    }
+
+    /// Read the status, `Retry-After` and body of a single attempt.
+    pub(crate) async fn read(res: reqwest::Response) -> Result<SendResponse, NotifierError> {
+        let status = res.status();
+        let retry_after = res
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .map(Duration::from_secs);
+        let body = res.text().await?;
+        let response = serde_json::from_str(&body).ok();
+        Ok(SendResponse {
+            status,
+            body,
+            response,
+            attempts: 1,
+            retry_after,
+        })
+    }
+
+    pub(crate) fn is_retryable(&self) -> bool {
+        self.status == StatusCode::TOO_MANY_REQUESTS || self.status.is_server_error()
+    }
+}
+
+/// Sends messages to a single Lark webhook, signing them when a secret is set.
+#[derive(Clone)]
+pub struct LarkNotifier {
+    client: Client,
+    webhook_url: String,
+    secret: Option<String>,
+    sign_version: SignVersion,
+    /// Seconds added to the local clock for signature timestamps.
+    time_offset: i64,
+    /// Timestamp to sign with instead of the clock's.
+    sign_timestamp: Option<u64>,
+    retry: RetryPolicy,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    /// Timeout set on each request, for a client passed to the builder.
+    request_timeout: Option<Duration>,
+    /// Proxy the requests go through, with credentials redacted.
+    proxy: Option<String>,
+    allow_insecure_url: bool,
+    rate_limiter: Option<RateLimiter>,
+}
+
+impl LarkNotifier {
+    pub fn builder() -> LarkNotifierBuilder {
+        LarkNotifierBuilder::default()
+    }
+
+    pub fn webhook_url(&self) -> &str {
+        &self.webhook_url
+    }
+
+    /// The HTTP client, with this notifier's proxy and timeouts, for other Lark APIs.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// The webhook URL with most of its token (the last path segment) masked, for display.
+    pub fn masked_webhook_url(&self) -> String {
+        mask_webhook_url(&self.webhook_url)
+    }
+
+    pub fn secret(&self) -> Option<&str> {
+        self.secret.as_deref()
+    }
+
+    pub fn sign_version(&self) -> SignVersion {
+        self.sign_version
+    }
+
+    /// Seconds added to the local clock for signature timestamps.
+    pub fn time_offset(&self) -> i64 {
+        self.time_offset
+    }
+
+    /// The fixed timestamp messages are signed with, if one was set.
+    pub fn sign_timestamp(&self) -> Option<u64> {
+        self.sign_timestamp
+    }
+
+    /// How failed sends are retried.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry
+    }
+
+    /// The timeout for each request, if one was set.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// A notifier with the same settings but a different (or no) secret.
+    pub fn with_secret(&self, secret: Option<String>) -> Self {
+        LarkNotifier {
+            secret,
+            ..self.clone()
+        }
+    }
+
+    /// A notifier with the same client and settings targeting another webhook.
+    pub fn with_webhook_url(&self, url: impl Into<String>) -> Result<Self, NotifierError> {
+        let webhook_url = url.into();
+        validate_webhook_url(&webhook_url, self.allow_insecure_url)?;
+
+        Ok(LarkNotifier {
+            webhook_url,
+            ..self.clone()
+        })
+    }
+
+    /// How many seconds the local clock, with the [time offset](Self::time_offset),
+    /// is ahead of (or, if negative, behind) the webhook host, judged from the
+    /// `Date` header of a HEAD request.
+    ///
+    /// Returns `None` if the server did not send a usable `Date` header.
+    pub async fn clock_skew(&self) -> Result<Option<i64>, NotifierError> {
+        let res = self
+            .client
+            .head(&self.webhook_url)
+            .send()
+            .await
+            .map_err(|e| self.map_http_error(e))?;
+        let now = SystemTime::now();
+        let server = res
+            .headers()
+            .get(DATE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| httpdate::parse_http_date(value).ok());
+
+        Ok(server.map(|server| match now.duration_since(server) {
+            Ok(ahead) => ahead.as_secs() as i64,
+            Err(behind) => -(behind.duration().as_secs() as i64),
+        } + self.time_offset))
+    }
+
+    /// Sign `message` exactly as [`send`](Self::send) would, without sending it.
+    pub fn prepare(&self, mut message: LarkMessage) -> Result<LarkMessage, NotifierError> {
+        if let Some(secret) = &self.secret {
+            let timestamp = match self.sign_timestamp {
+                Some(timestamp) => timestamp,
+                None => timestamp(self.time_offset)?,
+            };
+            sign_message_at(&mut message, secret, self.sign_version, timestamp);
+        }
+        Ok(message)
+    }
+
+    /// Sign (if configured) and post `message` to the webhook.
+    ///
+    /// Connection errors, timeouts, 429 and 5xx responses are retried according
+    /// to the retry policy; every attempt is signed with a fresh timestamp.
+    /// A final non-2xx status or Lark error code is returned as an error.
+    pub async fn send(&self, message: LarkMessage) -> Result<SendResponse, NotifierError> {
+        debug!(
+            "Sending {} message to {} (signed: {})",
+            message.msg_type,
+            self.masked_webhook_url(),
+            self.secret.is_some()
+        );
+        if let Some(proxy) = &self.proxy {
+            debug!("Connecting through proxy {}", proxy);
+        }
+        let mut attempt = 0;
+        let mut waited = Duration::ZERO;
+        loop {
+            debug!(
+                "Sending to webhook, attempt {}/{}",
+                attempt + 1,
+                self.retry.retries + 1
+            );
+            let result = self.send_once(message.clone(), attempt + 1).await;
+            let retryable = match &result {
+                Ok(res) => res.is_retryable(),
+                Err(NotifierError::Timeout { .. } | NotifierError::Proxy { .. }) => true,
+                Err(NotifierError::Http(err)) => err.is_connect() || err.is_timeout(),
+                Err(_) => false,
+            };
+
+            if !retryable || attempt >= self.retry.retries {
+                return match result.and_then(SendResponse::error_for_status) {
+                    Err(err) if attempt > 0 => Err(NotifierError::RetriesExhausted {
+                        attempts: attempt + 1,
+                        waited,
+                        last: Box::new(err),
+                    }),
+                    result => result,
+                };
+            }
+
+            // Honor Retry-After on 429s so rate limiting pauses instead of failing.
+            let delay = match &result {
+                Ok(res) => res.retry_after.unwrap_or_else(|| self.retry.delay(attempt)),
+                Err(_) => self.retry.delay(attempt),
+            };
+            match &result {
+                Ok(res) => debug!(
+                    "Attempt {} got {}, retrying in {:?}",
+                    attempt + 1,
+                    res.status,
+                    delay
+                ),
+                Err(err) => debug!(
+                    "Attempt {} failed: {}, retrying in {:?}",
+                    attempt + 1,
+                    err,
+                    delay
+                ),
+            }
+            tokio::time::sleep(delay).await;
+            waited += delay;
+            attempt += 1;
+        }
+    }
+
+    async fn send_once(
+        &self,
+        message: LarkMessage,
+        attempts: u32,
+    ) -> Result<SendResponse, NotifierError> {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
+        let message = self.prepare(message)?;
+        let mut res = post(
+            &self.client,
+            &self.webhook_url,
+            None,
+            message,
+            self.request_timeout,
+        )
+        .await
+        .map_err(|e| match e {
+            NotifierError::Http(err) => self.map_http_error(err),
+            other => other,
+        })?;
+
+        res.attempts = attempts;
+        Ok(res)
+    }
+
+    fn map_http_error(&self, err: reqwest::Error) -> NotifierError {
+        let after = if !err.is_timeout() {
+            None
+        } else if err.is_connect() {
+            self.connect_timeout
+        } else {
+            self.timeout
+        };
+
+        match (after, &self.proxy) {
+            (Some(after), _) => NotifierError::Timeout {
+                url: self.masked_webhook_url(),
+                after,
+            },
+            (None, Some(proxy)) if err.is_connect() => NotifierError::Proxy {
+                proxy: proxy.clone(),
+                source: err,
+            },
+            _ => NotifierError::Http(err),
+        }
+    }
+
+    /// Send a rich text post with `keywords` highlighted in `content`.
+    pub async fn send_post(
+        &self,
+        title: &str,
+        content: &str,
+        keywords: &[String],
+    ) -> Result<SendResponse, NotifierError> {
+        let elements = process_content_with_keywords(content, keywords);
+        self.send(LarkMessage::post(title, vec![elements])).await
+    }
+
+    /// Send an interactive card with a `template` colored header; keywords are bolded.
+    pub async fn send_card(
+        &self,
+        title: &str,
+        content: &str,
+        keywords: &[String],
+        template: &str,
+    ) -> Result<SendResponse, NotifierError> {
+        let markdown = elements_to_lark_md(&process_content_with_keywords(content, keywords));
+        self.send(LarkMessage::card(InteractiveCard::new(
+            title, template, markdown,
+        )))
+        .await
+    }
+
+    /// Send a plain text message.
+    pub async fn send_text(&self, text: &str) -> Result<SendResponse, NotifierError> {
+        self.send(LarkMessage::text(text)).await
+    }
+}
+
+#[derive(Default)]
+pub struct LarkNotifierBuilder {
+    client: Option<Client>,
+    webhook_url: Option<String>,
+    secret: Option<String>,
+    sign_version: SignVersion,
+    time_offset: i64,
+    sign_timestamp: Option<u64>,
+    retry: RetryPolicy,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    proxy: Option<String>,
+    no_proxy: bool,
+    allow_insecure_url: bool,
+    rate_limiter: Option<RateLimiter>,
+    pool_idle_timeout: Option<Duration>,
+    ca_certs: Vec<PathBuf>,
+}
+
+/// TCP keep-alive interval for pooled connections.
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+
+/// Idle connections kept per host; more than any fan-out sends at once.
+const POOL_MAX_IDLE_PER_HOST: usize = 16;
+
+impl LarkNotifierBuilder {
+    pub fn webhook_url(mut self, url: impl Into<String>) -> Self {
+        self.webhook_url = Some(url.into());
+        self
+    }
+
+    pub fn secret(mut self, secret: impl Into<String>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    /// Sign with `version` instead of Lark's [`SignVersion::HmacSha256`].
+    pub fn sign_version(mut self, version: SignVersion) -> Self {
+        self.sign_version = version;
+        self
+    }
+
+    /// Add `secs` (which may be negative) to the local clock for signature
+    /// timestamps, for a machine whose clock is off and cannot be fixed.
+    pub fn time_offset(mut self, secs: i64) -> Self {
+        self.time_offset = secs;
+        self
+    }
+
+    /// Sign every message with `timestamp` (Unix seconds) instead of the
+    /// current time, e.g. to compare signatures with a reference
+    /// implementation. Overrides [`time_offset`](Self::time_offset).
+    ///
+    /// ```
+    /// use lark_notifier::{LarkMessage, LarkNotifier};
+    ///
+    /// let notifier = LarkNotifier::builder()
+    ///     .webhook_url("https://open.feishu.cn/open-apis/bot/v2/hook/xxx")
+    ///     .secret("secret")
+    ///     .sign_timestamp(1599360473)
+    ///     .build()?;
+    /// let message = notifier.prepare(LarkMessage::text("hi"))?;
+    /// assert_eq!(message.timestamp.as_deref(), Some("1599360473"));
+    /// assert_eq!(message.sign.as_deref(), Some("q4jswNiMy51J5JuQV566yJat0/lQ/c+22kINzUgKsGU="));
+    /// # Ok::<(), lark_notifier::NotifierError>(())
+    /// ```
+    pub fn sign_timestamp(mut self, timestamp: u64) -> Self {
+        self.sign_timestamp = Some(timestamp);
+        self
+    }
+
+    /// Reuse an existing client (and its connection pool) instead of creating one.
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Limit the whole request, including connecting, to `timeout`.
+    ///
+    /// With a client passed to [`client`](Self::client) it is set on each
+    /// request instead; [`connect_timeout`](Self::connect_timeout) only applies
+    /// to the client created by the builder.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Limit establishing the connection to `timeout`; defaults to the request timeout.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Close pooled connections idle for longer than `timeout` (reqwest's default is 90s).
+    ///
+    /// Only applies to the client created by the builder.
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Also trust the root certificates in the PEM file at `path`, for example
+    /// a corporate proxy's CA. Can be given more than once; the system roots
+    /// are still trusted.
+    ///
+    /// Only applies to the client created by the builder.
+    pub fn ca_cert(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ca_certs.push(path.into());
+        self
+    }
+
+    /// Send through an `http://`, `https://` or `socks5://` proxy instead of
+    /// the one from `HTTPS_PROXY`/`HTTP_PROXY`/`ALL_PROXY`.
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    /// Ignore proxies configured in the environment.
+    pub fn no_proxy(mut self) -> Self {
+        self.no_proxy = true;
+        self
+    }
+
+    /// Accept any http(s) webhook URL, not just `https` Lark bot webhooks.
+    pub fn allow_insecure_url(mut self, allow: bool) -> Self {
+        self.allow_insecure_url = allow;
+        self
+    }
+
+    /// Throttle every request, including retries, through `limiter`.
+    ///
+    /// Notifiers derived with [`LarkNotifier::with_webhook_url`] share it, so the
+    /// limit holds across all of their webhooks.
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Retry transient failures; the default is not to retry.
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn build(self) -> Result<LarkNotifier, NotifierError> {
+        let webhook_url = self
+            .webhook_url
+            .clone()
+            .ok_or(NotifierError::MissingWebhookUrl)?;
+        validate_webhook_url(&webhook_url, self.allow_insecure_url)?;
+
+        let connect_timeout = self.connect_timeout.or(self.timeout);
+        let client_given = self.client.is_some();
+        let (client, timeout, connect_timeout, proxy) = match &self.client {
+            Some(client) => (client.clone(), self.timeout, None, None),
+            None => {
+                let scheme = Url::parse(&webhook_url)
+                    .ok()
+                    .map(|url| url.scheme().to_string());
+                let (client, proxy) = self.new_client(scheme.as_deref())?;
+                (client, self.timeout, connect_timeout, proxy)
+            }
+        };
+
+        Ok(LarkNotifier {
+            client,
+            webhook_url,
+            secret: self.secret,
+            sign_version: self.sign_version,
+            time_offset: self.time_offset,
+            sign_timestamp: self.sign_timestamp,
+            retry: self.retry,
+            timeout,
+            connect_timeout,
+            request_timeout: if client_given { self.timeout } else { None },
+            proxy,
+            allow_insecure_url: self.allow_insecure_url,
+            rate_limiter: self.rate_limiter,
+        })
+    }
+
+    /// Build only the HTTP client, with this builder's proxy, timeouts and
+    /// pooling, for the open API; no webhook URL is needed.
+    pub fn build_client(&self) -> Result<Client, NotifierError> {
+        match &self.client {
+            Some(client) => Ok(client.clone()),
+            None => Ok(self.new_client(Some("https"))?.0),
+        }
+    }
+
+    /// A new client and the proxy it goes through, with credentials redacted.
+    /// `scheme` picks the proxy from the environment when none is set.
+    fn new_client(&self, scheme: Option<&str>) -> Result<(Client, Option<String>), NotifierError> {
+        // Every notifier derived from one builder shares the client, so
+        // connections and TLS sessions are reused across sends.
+        let mut builder = Client::builder()
+            .tcp_keepalive(TCP_KEEPALIVE)
+            .pool_max_idle_per_host(POOL_MAX_IDLE_PER_HOST);
+        if let Some(idle) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(idle);
+        }
+        for path in &self.ca_certs {
+            for cert in read_ca_certs(path)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        let proxy = match (&self.proxy, self.no_proxy) {
+            (Some(proxy), _) => {
+                let parsed = Proxy::all(proxy)
+                    .map_err(|_| NotifierError::InvalidProxy(redact_credentials(proxy)))?;
+                builder = builder.proxy(parsed);
+                Some(proxy.clone())
+            }
+            (None, true) => {
+                builder = builder.no_proxy();
+                None
+            }
+            (None, false) => scheme.and_then(env_proxy),
+        };
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout.or(self.timeout) {
+            builder = builder.connect_timeout(timeout);
+        }
+        Ok((
+            builder.build()?,
+            proxy.map(|proxy| redact_credentials(&proxy)),
+        ))
+    }
+}
+
+/// Timeout, retries and rate limit for [`send_with_policy`].
+#[derive(Debug, Clone, Default)]
+pub struct SendPolicy {
+    /// Limit on each attempt; `None` leaves it to the client.
+    pub timeout: Option<Duration>,
+    pub retry: RetryPolicy,
+    pub rate_limiter: Option<RateLimiter>,
+}
+
+/// Sign and post `message` to `webhook_url` with the same retries, backoff, rate
+/// limiting and error reporting as [`LarkNotifier::send`].
+///
+/// Every attempt is signed afresh. Like [`send_message`] the URL is not
+/// validated, so test servers and gateways work.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), lark_notifier::NotifierError> {
+/// use std::time::Duration;
+/// use lark_notifier::{LarkMessage, RateLimiter, RetryPolicy, SendPolicy, send_with_policy};
+///
+/// let policy = SendPolicy {
+///     timeout: Some(Duration::from_secs(10)),
+///     retry: RetryPolicy::new(3, Duration::from_millis(500)),
+///     rate_limiter: Some(RateLimiter::per_minute(60)),
+/// };
+/// let client = reqwest::Client::new();
+/// let url = "https://open.larksuite.com/open-apis/bot/v2/hook/xxx";
+/// let response = send_with_policy(&client, url, Some("secret"), LarkMessage::text("hi"), &policy).await?;
+/// println!("sent after {} attempts", response.attempts);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn send_with_policy(
+    client: &Client,
+    webhook_url: &str,
+    secret: Option<&str>,
+    message: LarkMessage,
+    policy: &SendPolicy,
+) -> Result<SendResponse, NotifierError> {
+    let mut builder = LarkNotifier::builder()
+        .client(client.clone())
+        .webhook_url(webhook_url)
+        .retry_policy(policy.retry)
+        .allow_insecure_url(true);
+    if let Some(secret) = secret {
+        builder = builder.secret(secret);
+    }
+    if let Some(timeout) = policy.timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(limiter) = &policy.rate_limiter {
+        builder = builder.rate_limiter(limiter.clone());
+    }
+    builder.build()?.send(message).await
+}
+
+/// Sign `message` with `secret` (if any) and post it once to `webhook_url`.
+/// Like [`send_with_policy`], it signs the way Lark does; use a
+/// [`LarkNotifier`] for another [`SignVersion`].
+///
+/// This is the building block behind [`LarkNotifier::send`], without retries
+/// or timeout reporting; the response is returned whatever its status.
+pub async fn send_message(
+    client: &Client,
+    webhook_url: &str,
+    secret: Option<&str>,
+    message: LarkMessage,
+) -> Result<SendResponse, NotifierError> {
+    let secret = secret.map(|secret| (secret, SignVersion::default()));
+    post(client, webhook_url, secret, message, None).await
+}
+
+async fn post(
+    client: &Client,
+    webhook_url: &str,
+    secret: Option<(&str, SignVersion)>,
+    mut message: LarkMessage,
+    timeout: Option<Duration>,
+) -> Result<SendResponse, NotifierError> {
+    if let Some((secret, version)) = secret {
+        sign_message(&mut message, secret, version)?;
+    }
+
+    let payload = serde_json::to_vec(&message)?;
+    if enabled!(Level::DEBUG) {
+        let mut redacted = message.clone();
+        if redacted.sign.is_some() {
+            redacted.sign = Some("<redacted>".to_string());
+        }
+        debug!(
+            "Payload ({} bytes): {}",
+            payload.len(),
+            serde_json::to_string(&redacted)?
+        );
+    }
+
+    let started = Instant::now();
+    let mut request = client
+        .post(webhook_url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(payload);
+    if let Some(timeout) = timeout {
+        request = request.timeout(timeout);
+    }
+    let res = request.send().await.map_err(mask_error_url)?;
+    let response = SendResponse::read(res).await.map_err(|err| match err {
+        NotifierError::Http(err) => NotifierError::Http(mask_error_url(err)),
+        other => other,
+    })?;
+    debug!(
+        "Webhook responded with {} in {:?}: {}",
+        response.status,
+        started.elapsed(),
+        response.body
+    );
+    Ok(response)
+}
+
+/// Mask the token in the URL reqwest puts in its error messages.
+fn mask_error_url(err: reqwest::Error) -> reqwest::Error {
+    match err
+        .url()
+        .map(|url| Url::parse(&mask_webhook_url(url.as_str())))
+    {
+        Some(Ok(masked)) => err.with_url(masked),
+        Some(Err(_)) => err.without_url(),
+        None => err,
+    }
+}
+
+pub(crate) fn mask_webhook_url(webhook_url: &str) -> String {
+    let Some((base, token)) = webhook_url.rsplit_once('/') else {
+        return webhook_url.to_string();
+    };
+    let visible = if token.chars().count() > 8 { 4 } else { 0 };
+    let shown: String = token.chars().take(visible).collect();
+    format!(
+        "{}/{}{}",
+        base,
+        shown,
+        "*".repeat(token.chars().count() - visible)
+    )
+}
+
+/// Path every custom bot webhook lives under, followed by the bot's token.
+const HOOK_PATH: &str = "/open-apis/bot/v2/hook/";
+
+/// The builder option [`validate_webhook_url`] suggests for URLs it rejects.
+pub const ALLOW_INSECURE_URL: &str = "`allow_insecure_url`";
+
+/// Check that `webhook_url` is an `https` URL for a Lark bot webhook on a known
+/// host, listing everything that is wrong with it.
+///
+/// With `allow_insecure` any `http` or `https` URL is accepted, for self-hosted
+/// gateways and test servers.
+pub fn validate_webhook_url(webhook_url: &str, allow_insecure: bool) -> Result<(), NotifierError> {
+    let invalid = |problems: Vec<String>| NotifierError::InvalidWebhookUrl {
+        url: mask_webhook_url(webhook_url),
+        problems,
+    };
+    let url =
+        Url::parse(webhook_url).map_err(|e| invalid(vec![format!("not a valid URL ({})", e)]))?;
+
+    let mut problems = Vec::new();
+    if allow_insecure {
+        if url.scheme() != "https" && url.scheme() != "http" {
+            problems.push(format!(
+                "scheme must be http or https, not {}",
+                url.scheme()
+            ));
+        }
+    } else {
+        if url.scheme() != "https" {
+            problems.push(format!("scheme must be https, not {}", url.scheme()));
+        }
+        let host = url.host_str().unwrap_or_default();
+        if !Region::ALL.iter().any(|region| region.host() == host) {
+            problems.push(format!(
+                "host must be {} or {}, not {}",
+                Region::Cn.host(),
+                Region::Intl.host(),
+                host
+            ));
+        }
+        match url.path().strip_prefix(HOOK_PATH) {
+            Some(token) if !token.is_empty() && !token.contains('/') => {}
+            Some(_) => problems.push(format!("missing the bot token after {}", HOOK_PATH)),
+            None => problems.push(format!("path must start with {}", HOOK_PATH)),
+        }
+        if !problems.is_empty() {
+            problems.push(format!(
+                "allow insecure URLs ({}) for self-hosted or test endpoints",
+                ALLOW_INSECURE_URL
+            ));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(invalid(problems))
+    }
+}
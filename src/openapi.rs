@@ -0,0 +1,497 @@
+use reqwest::Client;
+use reqwest::multipart::{Form, Part};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::error::NotifierError;
+use crate::message::{LarkContent, LarkMessage};
+use crate::notifier::SendResponse;
+use crate::region::Region;
+use crate::retry::RetryPolicy;
+
+/// Largest image `im/v1/images` accepts.
+pub const MAX_IMAGE_BYTES: usize = 10 * 1024 * 1024;
+
+/// How long before its expiry a cached token is replaced.
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+/// Lark's code for an invalid or expired tenant access token.
+pub const INVALID_ACCESS_TOKEN: i64 = 99991663;
+
+/// A client for the parts of the open API a custom bot webhook cannot do on
+/// its own, authenticated as an app with its id and secret: uploading images
+/// and sending as the app's bot to any chat it is in.
+///
+/// The tenant access token is fetched on first use and reused until shortly
+/// before it expires; clones share it.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), lark_notifier::NotifierError> {
+/// use lark_notifier::{AppClient, LarkMessage, Region};
+///
+/// let app = AppClient::new(reqwest::Client::new(), Region::Intl, "cli_xxx", "app-secret");
+/// let image_key = app.upload_image_file("screenshot.png".as_ref()).await?;
+/// let response = app.send_to_chat("oc_xxx", &LarkMessage::image(image_key)).await?;
+/// println!("sent {}", response.message_id().unwrap_or_default());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AppClient {
+    client: Client,
+    base_url: String,
+    app_id: String,
+    app_secret: String,
+    retry: RetryPolicy,
+    token: Arc<Mutex<Option<CachedToken>>>,
+}
+
+struct CachedToken {
+    token: String,
+    /// `None` for a token given by the caller, which is never refreshed.
+    expires: Option<Instant>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    code: i64,
+    #[serde(default)]
+    msg: String,
+    #[serde(default)]
+    tenant_access_token: String,
+    /// Seconds until the token expires.
+    #[serde(default)]
+    expire: u64,
+}
+
+#[derive(Deserialize)]
+struct ApiResponse<T> {
+    code: i64,
+    #[serde(default)]
+    msg: String,
+    data: Option<T>,
+}
+
+#[derive(Deserialize)]
+struct ImageData {
+    image_key: String,
+}
+
+#[derive(Deserialize)]
+struct UserList {
+    #[serde(default)]
+    user_list: Vec<UserId>,
+}
+
+#[derive(Deserialize)]
+struct UserId {
+    #[serde(default)]
+    mobile: String,
+    /// Missing for a number no user the app can see has registered.
+    user_id: Option<String>,
+}
+
+/// Most mobile numbers `contact/v3/users/batch_get_id` looks up at once.
+const MOBILES_PER_LOOKUP: usize = 50;
+
+impl AppClient {
+    /// A client for `region`'s open API, e.g. `https://open.larksuite.com` for [`Region::Intl`].
+    pub fn new(
+        client: Client,
+        region: Region,
+        app_id: impl Into<String>,
+        app_secret: impl Into<String>,
+    ) -> Self {
+        AppClient {
+            client,
+            base_url: format!("https://{}", region.host()),
+            app_id: app_id.into(),
+            app_secret: app_secret.into(),
+            retry: RetryPolicy::default(),
+            token: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// A client using an already obtained tenant access token, with no app
+    /// credentials to fetch a new one.
+    pub fn with_tenant_access_token(
+        client: Client,
+        region: Region,
+        token: impl Into<String>,
+    ) -> Self {
+        let app = AppClient::new(client, region, "", "");
+        let token = CachedToken {
+            token: token.into(),
+            expires: None,
+        };
+        AppClient {
+            token: Arc::new(Mutex::new(Some(token))),
+            ..app
+        }
+    }
+
+    /// Send requests to `base_url` instead, for gateways and test servers.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Retry sends on connection errors, 429 and 5xx responses, as [`LarkNotifier`](crate::LarkNotifier) does.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// The tenant access token, fetching a new one if none is cached or it is about to expire.
+    pub async fn tenant_access_token(&self) -> Result<String, NotifierError> {
+        let mut cached = self.token.lock().await;
+        if let Some(token) = cached.as_ref()
+            && token
+                .expires
+                .is_none_or(|expires| expires > Instant::now() + TOKEN_REFRESH_MARGIN)
+        {
+            return Ok(token.token.clone());
+        }
+
+        let url = format!(
+            "{}/open-apis/auth/v3/tenant_access_token/internal",
+            self.base_url
+        );
+        debug!("Fetching tenant access token for app {}", self.app_id);
+        let res = self
+            .client
+            .post(&url)
+            .json(&json!({ "app_id": self.app_id, "app_secret": self.app_secret }))
+            .send()
+            .await?;
+        let response: TokenResponse = parse(res).await?;
+        if response.code != 0 {
+            return Err(NotifierError::LarkApi {
+                code: response.code,
+                msg: response.msg,
+            });
+        }
+        let token = response.tenant_access_token;
+        *cached = Some(CachedToken {
+            token: token.clone(),
+            expires: Some(Instant::now() + Duration::from_secs(response.expire)),
+        });
+        Ok(token)
+    }
+
+    /// Send `message` as the app's bot to the group chat `chat_id` (`oc_...`).
+    /// [`SendResponse::message_id`] is the id of the new message.
+    ///
+    /// A fetched token rejected as invalid before its expiry is replaced once.
+    pub async fn send_to_chat(
+        &self,
+        chat_id: &str,
+        message: &LarkMessage,
+    ) -> Result<SendResponse, NotifierError> {
+        let body = chat_message_body(chat_id, message)?;
+        let url = format!(
+            "{}/open-apis/im/v1/messages?receive_id_type=chat_id",
+            self.base_url
+        );
+        debug!("Sending {} message to chat {}", message.msg_type, chat_id);
+
+        let mut attempt = 0;
+        let mut waited = Duration::ZERO;
+        let mut refreshed = false;
+        loop {
+            let token = self.tenant_access_token().await?;
+            let result = match self
+                .client
+                .post(&url)
+                .bearer_auth(token)
+                .json(&body)
+                .send()
+                .await
+            {
+                Ok(res) => SendResponse::read(res).await,
+                Err(err) => Err(err.into()),
+            };
+            if let Ok(res) = &result {
+                debug!(
+                    "Open API responded with {}: {}",
+                    res.status,
+                    redact_token(&res.body)
+                );
+                if !refreshed
+                    && res
+                        .lark_error()
+                        .is_some_and(|error| error.code == INVALID_ACCESS_TOKEN)
+                {
+                    refreshed = true;
+                    if self.forget_token().await {
+                        continue;
+                    }
+                }
+            }
+
+            let retryable = match &result {
+                Ok(res) => res.is_retryable(),
+                Err(NotifierError::Http(err)) => err.is_connect() || err.is_timeout(),
+                Err(_) => false,
+            };
+            if !retryable || attempt >= self.retry.retries {
+                return match result.and_then(SendResponse::error_for_status) {
+                    Err(err) if attempt > 0 => Err(NotifierError::RetriesExhausted {
+                        attempts: attempt + 1,
+                        waited,
+                        last: Box::new(err),
+                    }),
+                    Ok(mut res) => {
+                        res.attempts = attempt + 1;
+                        Ok(res)
+                    }
+                    result => result,
+                };
+            }
+
+            let delay = match &result {
+                Ok(res) => res.retry_after.unwrap_or_else(|| self.retry.delay(attempt)),
+                Err(_) => self.retry.delay(attempt),
+            };
+            debug!("Attempt {} failed, retrying in {:?}", attempt + 1, delay);
+            tokio::time::sleep(delay).await;
+            waited += delay;
+            attempt += 1;
+        }
+    }
+
+    /// Drop the cached token so the next request fetches a new one; false for
+    /// a token given by the caller, which cannot be replaced.
+    async fn forget_token(&self) -> bool {
+        let mut cached = self.token.lock().await;
+        if cached.as_ref().is_some_and(|token| token.expires.is_none()) {
+            return false;
+        }
+        cached.take();
+        true
+    }
+
+    /// Upload the image at `path` for use in messages, returning its `image_key`.
+    pub async fn upload_image_file(&self, path: &Path) -> Result<String, NotifierError> {
+        let data = std::fs::read(path).map_err(|source| NotifierError::Io {
+            context: format!("Failed to read image {}", path.display()),
+            source,
+        })?;
+        let name = path
+            .file_name()
+            .map_or_else(|| "image".into(), |name| name.to_string_lossy());
+        self.upload_image(&name, data).await
+    }
+
+    /// Upload `data`, the contents of the image file `name`, returning its `image_key`.
+    pub async fn upload_image(&self, name: &str, data: Vec<u8>) -> Result<String, NotifierError> {
+        let mime = check_image(name, &data)?;
+        let token = self.tenant_access_token().await?;
+
+        let url = format!("{}/open-apis/im/v1/images", self.base_url);
+        debug!("Uploading {} ({} bytes, {})", name, data.len(), mime);
+        let image = Part::bytes(data)
+            .file_name(name.to_string())
+            .mime_str(mime)?;
+        let form = Form::new()
+            .text("image_type", "message")
+            .part("image", image);
+        let res = self
+            .client
+            .post(&url)
+            .bearer_auth(token)
+            .multipart(form)
+            .send()
+            .await?;
+        let response: ApiResponse<ImageData> = parse(res).await?;
+        match response.data {
+            Some(data) if response.code == 0 => Ok(data.image_key),
+            _ => Err(NotifierError::LarkApi {
+                code: response.code,
+                msg: response.msg,
+            }),
+        }
+    }
+
+    /// Look up the users who registered `mobiles`, returning each one's open_id
+    /// in the same order, or `None` for a number with no user the app can see.
+    /// Needs the app's `contact:user.id:readonly` permission.
+    pub async fn open_ids_by_mobile(
+        &self,
+        mobiles: &[String],
+    ) -> Result<Vec<Option<String>>, NotifierError> {
+        let url = format!(
+            "{}/open-apis/contact/v3/users/batch_get_id?user_id_type=open_id",
+            self.base_url
+        );
+        let mut open_ids = Vec::with_capacity(mobiles.len());
+        for chunk in mobiles.chunks(MOBILES_PER_LOOKUP) {
+            let token = self.tenant_access_token().await?;
+            debug!("Looking up {} mobile numbers", chunk.len());
+            let res = self
+                .client
+                .post(&url)
+                .bearer_auth(token)
+                .json(&json!({ "mobiles": chunk }))
+                .send()
+                .await?;
+            let response: ApiResponse<UserList> = parse(res).await?;
+            let users = match response.data {
+                Some(data) if response.code == 0 => data.user_list,
+                _ => {
+                    return Err(NotifierError::LarkApi {
+                        code: response.code,
+                        msg: response.msg,
+                    });
+                }
+            };
+            open_ids.extend(chunk.iter().map(|mobile| {
+                users
+                    .iter()
+                    .find(|user| same_mobile(&user.mobile, mobile))
+                    .and_then(|user| user.user_id.clone())
+            }));
+        }
+        Ok(open_ids)
+    }
+}
+
+/// Whether two spellings of a mobile number are the same number: the same
+/// digits, or a `+` international number and its national number, the rest
+/// after a country code of 1 to 3 digits.
+fn same_mobile(a: &str, b: &str) -> bool {
+    let digits = |s: &str| s.chars().filter(char::is_ascii_digit).collect::<String>();
+    let national_of = |international: &str, national: &str| {
+        let (international, national) = (international.trim(), national.trim());
+        international.starts_with('+')
+            && !national.starts_with('+')
+            && (1..=3).any(|code| digits(international).get(code..) == Some(&*digits(national)))
+    };
+    !digits(a).is_empty() && (digits(a) == digits(b) || national_of(a, b) || national_of(b, a))
+}
+
+/// The `im/v1/messages` request body sending `message` to `chat_id`: the same
+/// content the webhook takes, as a JSON string.
+///
+/// ```
+/// use lark_notifier::{LarkMessage, chat_message_body};
+/// use serde_json::json;
+///
+/// assert_eq!(
+///     chat_message_body("oc_1", &LarkMessage::text("hi")).unwrap(),
+///     json!({ "receive_id": "oc_1", "msg_type": "text", "content": "{\"text\":\"hi\"}" })
+/// );
+/// ```
+pub fn chat_message_body(chat_id: &str, message: &LarkMessage) -> Result<Value, NotifierError> {
+    let content = match (&message.content, &message.card) {
+        // The open API takes the locales without the `post` wrapper.
+        (Some(LarkContent::Post { post }), _) => serde_json::to_string(post)?,
+        (Some(LarkContent::ShareChat { share_chat_id }), _) => {
+            json!({ "chat_id": share_chat_id }).to_string()
+        }
+        (Some(content), _) => serde_json::to_string(content)?,
+        (None, Some(card)) => serde_json::to_string(card)?,
+        // Template cards and raw payloads keep theirs among the other fields.
+        (None, None) => match (message.extra.get("card"), message.extra.get("content")) {
+            (Some(card), _) => card.to_string(),
+            (None, Some(content)) => content.get("post").unwrap_or(content).to_string(),
+            (None, None) => {
+                return Err(NotifierError::InvalidPayload(format!(
+                    "a {} message without content or card cannot be sent to a chat",
+                    message.msg_type
+                )));
+            }
+        },
+    };
+    Ok(json!({ "receive_id": chat_id, "msg_type": message.msg_type, "content": content }))
+}
+
+/// Check that `data` is an image the upload accepts, returning its MIME type.
+///
+/// ```
+/// use lark_notifier::check_image;
+///
+/// assert_eq!(check_image("a.png", b"\x89PNG\r\n\x1a\n...").unwrap(), "image/png");
+/// assert!(check_image("notes.txt", b"hello").unwrap_err().to_string().contains("not a supported image"));
+/// assert!(check_image("empty.png", b"").is_err());
+/// ```
+pub fn check_image(name: &str, data: &[u8]) -> Result<&'static str, NotifierError> {
+    let invalid = |reason: String| NotifierError::InvalidImage {
+        name: name.to_string(),
+        reason,
+    };
+    if data.is_empty() {
+        return Err(invalid("the file is empty".to_string()));
+    }
+    if data.len() > MAX_IMAGE_BYTES {
+        return Err(invalid(format!(
+            "it is {} bytes, over the upload limit of {} bytes (10 MB)",
+            data.len(),
+            MAX_IMAGE_BYTES
+        )));
+    }
+    image_type(data).ok_or_else(|| {
+        invalid(
+            "not a supported image; expected JPEG, PNG, WEBP, GIF, TIFF, BMP or ICO".to_string(),
+        )
+    })
+}
+
+/// The MIME type of an image, from its leading magic bytes.
+fn image_type(data: &[u8]) -> Option<&'static str> {
+    match data {
+        [0xff, 0xd8, 0xff, ..] => Some("image/jpeg"),
+        [0x89, b'P', b'N', b'G', ..] => Some("image/png"),
+        [b'G', b'I', b'F', b'8', ..] => Some("image/gif"),
+        [
+            b'R',
+            b'I',
+            b'F',
+            b'F',
+            _,
+            _,
+            _,
+            _,
+            b'W',
+            b'E',
+            b'B',
+            b'P',
+            ..,
+        ] => Some("image/webp"),
+        [b'I', b'I', 0x2a, 0x00, ..] | [b'M', b'M', 0x00, 0x2a, ..] => Some("image/tiff"),
+        [b'B', b'M', ..] => Some("image/bmp"),
+        [0x00, 0x00, 0x01, 0x00, ..] => Some("image/x-icon"),
+        _ => None,
+    }
+}
+
+/// `body` with any `tenant_access_token` in it redacted, to log it.
+fn redact_token(body: &str) -> String {
+    match serde_json::from_str::<Value>(body) {
+        Ok(Value::Object(mut reply)) if reply.contains_key("tenant_access_token") => {
+            reply.insert("tenant_access_token".to_string(), json!("<redacted>"));
+            Value::Object(reply).to_string()
+        }
+        _ => body.to_string(),
+    }
+}
+
+/// Read an open API reply, turning a non-2xx status without a Lark error body into [`NotifierError::Status`].
+async fn parse<T: for<'de> Deserialize<'de>>(res: reqwest::Response) -> Result<T, NotifierError> {
+    let status = res.status();
+    let body = res.text().await?;
+    debug!(
+        "Open API responded with {}: {}",
+        status,
+        redact_token(&body)
+    );
+    match serde_json::from_str(&body) {
+        Ok(parsed) => Ok(parsed),
+        Err(_) => Err(NotifierError::Status { status, body }),
+    }
+}
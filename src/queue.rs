@@ -0,0 +1,356 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+use crate::error::NotifierError;
+use crate::message::LarkMessage;
+use crate::notifier::{LarkNotifier, mask_webhook_url};
+
+/// Messages queued in this process, to keep their file names apart.
+static QUEUED: AtomicU64 = AtomicU64::new(0);
+
+/// A directory of messages that could not be delivered, to be sent later.
+///
+/// Each message is its own file, written atomically and named so that
+/// messages sort in the order they were queued. Messages are stored unsigned
+/// and signed when they are finally sent. Messages older than the maximum age
+/// are dropped, and so are the oldest ones beyond the maximum count.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), lark_notifier::NotifierError> {
+/// use lark_notifier::{LarkMessage, LarkNotifier, Queue};
+///
+/// let notifier = LarkNotifier::builder()
+///     .webhook_url("https://open.larksuite.com/open-apis/bot/v2/hook/xxx")
+///     .build()?;
+/// let queue = Queue::new("/var/spool/lark-notifier");
+/// let message = LarkMessage::text("disk full");
+/// if let Err(err) = notifier.send(message.clone()).await {
+///     if err.is_transient() {
+///         queue.push(notifier.webhook_url(), &message)?;
+///     }
+/// }
+///
+/// // Later, once the network is back:
+/// let report = queue.flush(&[notifier]).await?;
+/// println!("{} flushed, {} expired", report.flushed, report.expired);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Queue {
+    dir: PathBuf,
+    max_age: Duration,
+    max_count: usize,
+}
+
+/// A message waiting in a [`Queue`].
+#[derive(Debug, Clone)]
+pub struct QueuedMessage {
+    /// The webhook it is to be sent to.
+    pub webhook_url: String,
+    pub queued_at: SystemTime,
+    pub message: LarkMessage,
+    path: PathBuf,
+}
+
+impl QueuedMessage {
+    /// The file holding this message.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// What [`Queue::flush`] did.
+#[derive(Debug, Default)]
+pub struct FlushReport {
+    /// Messages delivered and removed from the queue.
+    pub flushed: usize,
+    /// Messages removed unsent for being older than the maximum age.
+    pub expired: usize,
+    /// Messages still queued, for webhooks that are still unreachable or that
+    /// no notifier was given for.
+    pub remaining: usize,
+    /// Another flush of the same queue was running, so nothing was done.
+    pub busy: bool,
+    /// Webhooks whose first queued message failed to send again with a
+    /// [transient](NotifierError::is_transient) error; their messages stay queued.
+    pub stalled: Vec<(String, NotifierError)>,
+    /// Messages removed unsent because the webhook rejected them, or because
+    /// their file could not be read (it is renamed to `*.bad`).
+    pub rejected: Vec<(String, NotifierError)>,
+}
+
+/// A queued message, as written to its file.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    webhook_url: String,
+    /// Seconds since the Unix epoch.
+    queued_at: u64,
+    message: Value,
+}
+
+impl Queue {
+    /// Messages are kept this long by default.
+    pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+    /// At most this many messages are kept by default.
+    pub const DEFAULT_MAX_COUNT: usize = 1000;
+
+    /// A queue in `dir`, which is created when the first message is queued.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Queue {
+            dir: dir.into(),
+            max_age: Self::DEFAULT_MAX_AGE,
+            max_count: Self::DEFAULT_MAX_COUNT,
+        }
+    }
+
+    /// Drop messages queued longer ago than `max_age` instead of sending them.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Keep at most `max_count` messages, dropping the oldest.
+    pub fn with_max_count(mut self, max_count: usize) -> Self {
+        self.max_count = max_count.max(1);
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Queue `message` for `webhook_url`, without its signature. Returns how
+    /// many older messages were dropped for being too old or too many.
+    pub fn push(&self, webhook_url: &str, message: &LarkMessage) -> Result<usize, NotifierError> {
+        let io_error = |source| NotifierError::Io {
+            context: format!("Failed to queue the message in {}", self.dir.display()),
+            source,
+        };
+        create_private_dir(&self.dir).map_err(io_error)?;
+
+        let mut message = serde_json::to_value(message)?;
+        if let Value::Object(fields) = &mut message {
+            fields.remove("sign");
+            fields.remove("timestamp");
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let entry = Entry {
+            webhook_url: webhook_url.to_string(),
+            queued_at: now.as_secs(),
+            message,
+        };
+        let name = format!(
+            "{:013}-{}-{}.json",
+            now.as_millis(),
+            std::process::id(),
+            QUEUED.fetch_add(1, Ordering::Relaxed)
+        );
+        write_atomically(&self.dir, &name, &serde_json::to_vec(&entry)?).map_err(io_error)?;
+        debug!(
+            "Queued message for {} as {}",
+            mask_webhook_url(webhook_url),
+            name
+        );
+
+        let mut dropped = 0;
+        let paths = self.paths()?;
+        let excess = paths.len().saturating_sub(self.max_count);
+        for (i, path) in paths.iter().enumerate() {
+            if (i < excess || self.is_expired(path)) && remove(path) {
+                dropped += 1;
+            }
+        }
+        Ok(dropped)
+    }
+
+    /// The queued messages, oldest first; files that cannot be read are left out.
+    pub fn messages(&self) -> Result<Vec<QueuedMessage>, NotifierError> {
+        Ok(self
+            .paths()?
+            .into_iter()
+            .filter_map(|path| read_entry(&path).ok())
+            .collect())
+    }
+
+    /// Send the queued messages, oldest first, each through the notifier for
+    /// its webhook, and remove the ones delivered, rejected or expired.
+    ///
+    /// A transient failure leaves that message and the later ones for the same
+    /// webhook queued, keeping them in order. Only one flush of a queue runs
+    /// at a time, across processes; a concurrent one returns at once with
+    /// [`FlushReport::busy`] set.
+    pub async fn flush(&self, notifiers: &[LarkNotifier]) -> Result<FlushReport, NotifierError> {
+        let mut report = FlushReport::default();
+        if !self.dir.is_dir() {
+            return Ok(report);
+        }
+        let Some(_lock) = self.lock()? else {
+            report.busy = true;
+            return Ok(report);
+        };
+
+        let mut stalled: HashSet<String> = HashSet::new();
+        for path in self.paths()? {
+            if self.is_expired(&path) {
+                if remove(&path) {
+                    report.expired += 1;
+                }
+                continue;
+            }
+            let queued = match read_entry(&path) {
+                Ok(queued) => queued,
+                // Gone since listed, e.g. dropped by a concurrent push.
+                Err(NotifierError::Io { source, .. })
+                    if source.kind() == io::ErrorKind::NotFound =>
+                {
+                    continue;
+                }
+                Err(err) => {
+                    let _ = fs::rename(&path, path.with_extension("bad"));
+                    report.rejected.push((path.display().to_string(), err));
+                    continue;
+                }
+            };
+            let notifier = notifiers
+                .iter()
+                .find(|notifier| notifier.webhook_url() == queued.webhook_url);
+            let Some(notifier) = notifier.filter(|_| !stalled.contains(&queued.webhook_url)) else {
+                report.remaining += 1;
+                continue;
+            };
+
+            match notifier.send(queued.message).await {
+                Ok(_) => {
+                    remove(&path);
+                    report.flushed += 1;
+                }
+                Err(err) if err.is_transient() => {
+                    stalled.insert(queued.webhook_url.clone());
+                    report.stalled.push((queued.webhook_url, err));
+                    report.remaining += 1;
+                }
+                Err(err) => {
+                    remove(&path);
+                    report.rejected.push((queued.webhook_url, err));
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// The queued message files, oldest first.
+    fn paths(&self) -> Result<Vec<PathBuf>, NotifierError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(source) => {
+                return Err(NotifierError::Io {
+                    context: format!("Failed to read {}", self.dir.display()),
+                    source,
+                });
+            }
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                !name.starts_with('.') && name.ends_with(".json")
+            })
+            .collect();
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Whether the message in `path` is older than the maximum age, going by
+    /// the time in its name.
+    fn is_expired(&self, path: &Path) -> bool {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let queued_at = name
+            .split('-')
+            .next()
+            .and_then(|millis| millis.parse().ok())
+            .map(Duration::from_millis);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        queued_at.is_some_and(|queued_at| now.saturating_sub(queued_at) > self.max_age)
+    }
+
+    /// Take the queue's flush lock, or `None` if another flush holds it.
+    fn lock(&self) -> Result<Option<File>, NotifierError> {
+        let path = self.dir.join(".lock");
+        let io_error = |source| NotifierError::Io {
+            context: format!("Failed to lock {}", path.display()),
+            source,
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(io_error)?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(file)),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(source)) => Err(io_error(source)),
+        }
+    }
+}
+
+fn read_entry(path: &Path) -> Result<QueuedMessage, NotifierError> {
+    let data = fs::read(path).map_err(|source| NotifierError::Io {
+        context: format!("Failed to read queued message {}", path.display()),
+        source,
+    })?;
+    let entry: Entry = serde_json::from_slice(&data)?;
+    Ok(QueuedMessage {
+        webhook_url: entry.webhook_url,
+        queued_at: UNIX_EPOCH + Duration::from_secs(entry.queued_at),
+        message: LarkMessage::raw(entry.message)?,
+        path: path.to_path_buf(),
+    })
+}
+
+/// Remove a queued message, false if it was already gone.
+fn remove(path: &Path) -> bool {
+    fs::remove_file(path).is_ok()
+}
+
+/// Write `name` in `dir` through a temporary file, so readers never see it half written.
+fn write_atomically(dir: &Path, name: &str, data: &[u8]) -> io::Result<()> {
+    let temp = dir.join(format!(".{}.tmp", name));
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&temp)?;
+    let written = file.write_all(data).and_then(|()| file.sync_all());
+    if let Err(err) = written.and_then(|()| fs::rename(&temp, dir.join(name))) {
+        let _ = fs::remove_file(&temp);
+        return Err(err);
+    }
+    // Make the rename itself durable.
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+/// Create `dir` readable only by its owner, since queued messages include webhook URLs.
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)
+}
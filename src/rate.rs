@@ -0,0 +1,43 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+/// Spaces requests evenly so no more than a set number go out per minute.
+///
+/// Clones share their schedule, so one limiter can throttle several
+/// notifiers (e.g. one per webhook) together.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    interval: Duration,
+    /// When the next request may be sent.
+    next: Arc<Mutex<Instant>>,
+}
+
+impl RateLimiter {
+    /// Allow `per_minute` requests a minute; the first one is not delayed.
+    pub fn per_minute(per_minute: u32) -> Self {
+        RateLimiter {
+            interval: Duration::from_secs(60) / per_minute.max(1),
+            next: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Wait until the next request is allowed and claim its slot.
+    pub async fn acquire(&self) {
+        let wait = {
+            let mut next = self
+                .next
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let now = Instant::now();
+            let slot = (*next).max(now);
+            *next = slot + self.interval;
+            slot - now
+        };
+        if !wait.is_zero() {
+            debug!("Rate limit reached, waiting {:?}", wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
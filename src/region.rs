@@ -0,0 +1,54 @@
+use reqwest::Url;
+use std::fmt;
+use std::str::FromStr;
+
+/// The deployment a webhook belongs to: Feishu in mainland China, or Lark
+/// everywhere else. Both verify signatures the same way.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Region {
+    /// Feishu, `open.feishu.cn`.
+    Cn,
+    /// Lark, `open.larksuite.com`.
+    Intl,
+}
+
+impl Region {
+    pub const ALL: [Region; 2] = [Region::Cn, Region::Intl];
+
+    /// The host serving this region's bot webhooks.
+    pub fn host(self) -> &'static str {
+        match self {
+            Region::Cn => "open.feishu.cn",
+            Region::Intl => "open.larksuite.com",
+        }
+    }
+
+    /// Detect the region from a webhook URL's host; `None` for other hosts,
+    /// such as a self-hosted gateway.
+    pub fn from_webhook_url(webhook_url: &str) -> Option<Region> {
+        let url = Url::parse(webhook_url).ok()?;
+        let host = url.host_str()?;
+        Region::ALL.into_iter().find(|region| region.host() == host)
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cn" => Ok(Region::Cn),
+            "intl" => Ok(Region::Intl),
+            _ => Err(format!("unknown region '{}' (expected cn or intl)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Region::Cn => "cn",
+            Region::Intl => "intl",
+        })
+    }
+}
@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+/// How many times to retry a failed send and how long to wait in between.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; `0` disables retrying.
+    pub retries: u32,
+    /// Delay before the first retry, doubled for every subsequent one.
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 0,
+            base_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(retries: u32, base_delay: Duration) -> Self {
+        RetryPolicy {
+            retries,
+            base_delay,
+        }
+    }
+
+    /// Exponential backoff for the retry following `attempt` (0-based),
+    /// plus up to one base delay of random jitter.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let base = self.base_delay.as_millis() as u64;
+        let backoff = base.saturating_mul(1u64 << attempt.min(16));
+        let jitter = if base == 0 {
+            0
+        } else {
+            rand::random_range(0..base)
+        };
+        Duration::from_millis(backoff.saturating_add(jitter))
+    }
+}
@@ -0,0 +1,142 @@
+use base64::{Engine as _, engine::general_purpose};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::NotifierError;
+use crate::message::LarkMessage;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How a webhook's `sign` field is computed from the timestamp and secret.
+///
+/// Lark and Feishu use [`SignVersion::HmacSha256`]; other versions are for
+/// gateways and forks that verify differently.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SignVersion {
+    /// base64 of the HMAC-SHA256 of an empty message keyed with
+    /// [`string_to_sign`], as Lark documents it.
+    #[default]
+    HmacSha256,
+}
+
+impl SignVersion {
+    pub const ALL: [SignVersion; 1] = [SignVersion::HmacSha256];
+
+    /// The name it is written as, e.g. `hmac-sha256`; also its [`Display`](fmt::Display) form.
+    pub fn name(self) -> &'static str {
+        match self {
+            SignVersion::HmacSha256 => "hmac-sha256",
+        }
+    }
+
+    /// The raw digest for `timestamp`, before encoding.
+    pub fn digest(self, timestamp: u64, secret: &str) -> Vec<u8> {
+        match self {
+            SignVersion::HmacSha256 => sign_digest(timestamp, secret),
+        }
+    }
+
+    /// The `sign` field for `timestamp`.
+    pub fn sign(self, timestamp: u64, secret: &str) -> String {
+        match self {
+            SignVersion::HmacSha256 => {
+                general_purpose::STANDARD.encode(self.digest(timestamp, secret))
+            }
+        }
+    }
+}
+
+impl FromStr for SignVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SignVersion::ALL
+            .into_iter()
+            .find(|version| version.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = SignVersion::ALL
+                    .iter()
+                    .map(|version| version.name())
+                    .collect();
+                format!(
+                    "unknown sign version '{}' (expected {})",
+                    s,
+                    names.join(" or ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for SignVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The HMAC key Lark signs with: the timestamp and secret joined by a newline.
+pub fn string_to_sign(timestamp: u64, secret: &str) -> String {
+    format!("{}\n{}", timestamp, secret)
+}
+
+/// The raw HMAC-SHA256 of an empty message keyed with [`string_to_sign`].
+pub fn sign_digest(timestamp: u64, secret: &str) -> Vec<u8> {
+    // timestamp + key 做 sha256, 再进行 base64 编码
+    let mac = HmacSha256::new_from_slice(string_to_sign(timestamp, secret).as_bytes())
+        .expect("HMAC can take key of any size");
+
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// The `sign` field for `timestamp`, computed as `version` specifies.
+///
+/// ```
+/// use lark_notifier::{SignVersion, generate_sign};
+///
+/// assert_eq!(
+///     generate_sign(1599360473, "secret", SignVersion::HmacSha256),
+///     "q4jswNiMy51J5JuQV566yJat0/lQ/c+22kINzUgKsGU="
+/// );
+/// ```
+pub fn generate_sign(timestamp: u64, secret: &str, version: SignVersion) -> String {
+    version.sign(timestamp, secret)
+}
+
+/// Stamp `message` with the current timestamp and its `version` signature.
+pub fn sign_message(
+    message: &mut LarkMessage,
+    secret: &str,
+    version: SignVersion,
+) -> Result<(), NotifierError> {
+    sign_message_at(message, secret, version, timestamp(0)?);
+    Ok(())
+}
+
+/// Stamp `message` with `timestamp` and its `version` signature.
+pub fn sign_message_at(
+    message: &mut LarkMessage,
+    secret: &str,
+    version: SignVersion,
+    timestamp: u64,
+) {
+    message.sign = Some(generate_sign(timestamp, secret, version));
+    message.timestamp = Some(timestamp.to_string());
+}
+
+/// The current Unix time in seconds, moved by `offset_secs` to make up for a
+/// clock known to be off.
+pub(crate) fn timestamp(offset_secs: i64) -> Result<u64, NotifierError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| NotifierError::Signature("system clock is before the Unix epoch".to_string()))?
+        .as_secs();
+    now.checked_add_signed(offset_secs).ok_or_else(|| {
+        NotifierError::Signature(format!(
+            "time offset {}s is before the Unix epoch",
+            offset_secs
+        ))
+    })
+}
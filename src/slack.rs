@@ -0,0 +1,286 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::message::{CardButton, CardElement, CardText, InteractiveCard, LarkMessage};
+
+/// The body of a Slack incoming webhook: `text`, Block Kit `blocks` and
+/// legacy `attachments`, of which the common parts are translated.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SlackPayload {
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub blocks: Vec<Value>,
+    #[serde(default)]
+    pub attachments: Vec<SlackAttachment>,
+}
+
+/// A legacy message attachment.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SlackAttachment {
+    pub color: Option<String>,
+    pub pretext: Option<String>,
+    pub title: Option<String>,
+    pub title_link: Option<String>,
+    pub text: Option<String>,
+    #[serde(default)]
+    pub fields: Vec<SlackField>,
+    pub footer: Option<String>,
+    #[serde(default)]
+    pub blocks: Vec<Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SlackField {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub value: String,
+}
+
+impl SlackPayload {
+    /// Translate into a card, converting mrkdwn to lark_md.
+    ///
+    /// The card title is the first `header` block, else the first attachment
+    /// title, else the webhook `username`, else "Notification". The header
+    /// color follows the first attachment's `good`/`warning`/`danger` color.
+    pub fn to_card(&self) -> InteractiveCard {
+        let mut title = None;
+        let mut sections: Vec<CardElement> = Vec::new();
+        let mut buttons = Vec::new();
+
+        if let Some(text) = self.text.as_deref().filter(|text| !text.trim().is_empty()) {
+            sections.push(div(mrkdwn_to_lark_md(text)));
+        }
+        translate_blocks(&self.blocks, &mut title, &mut sections, &mut buttons);
+        for attachment in &self.attachments {
+            if title.is_none() {
+                title = attachment.title.clone();
+            }
+            let mut lines = Vec::new();
+            if let Some(pretext) = &attachment.pretext {
+                lines.push(mrkdwn_to_lark_md(pretext));
+            }
+            match (&attachment.title, &attachment.title_link) {
+                (Some(title), Some(link)) => lines.push(format!("**[{}]({})**", title, link)),
+                (Some(title), None) => lines.push(format!("**{}**", title)),
+                _ => {}
+            }
+            if let Some(text) = &attachment.text {
+                lines.push(mrkdwn_to_lark_md(text));
+            }
+            for field in &attachment.fields {
+                lines.push(format!(
+                    "**{}**: {}",
+                    field.title,
+                    mrkdwn_to_lark_md(&field.value)
+                ));
+            }
+            if let Some(footer) = &attachment.footer {
+                lines.push(mrkdwn_to_lark_md(footer));
+            }
+            if !lines.is_empty() {
+                sections.push(div(lines.join("\n")));
+            }
+            translate_blocks(&attachment.blocks, &mut title, &mut sections, &mut buttons);
+        }
+
+        let title = title
+            .or_else(|| self.username.clone())
+            .unwrap_or_else(|| "Notification".to_string());
+        let color = self
+            .attachments
+            .iter()
+            .find_map(|attachment| attachment.color.as_deref())
+            .map_or("blue", attachment_color);
+
+        let mut card = InteractiveCard::new(title, color, "");
+        card.elements = sections;
+        if card.elements.is_empty() {
+            card.elements.push(div(String::new()));
+        }
+        card.with_buttons(buttons)
+    }
+
+    pub fn to_message(&self) -> LarkMessage {
+        LarkMessage::card(self.to_card())
+    }
+}
+
+fn div(markdown: String) -> CardElement {
+    CardElement::Div {
+        text: CardText {
+            tag: "lark_md".to_string(),
+            content: markdown,
+        },
+    }
+}
+
+/// Translate the header, section, context, divider and button blocks; other blocks are skipped.
+fn translate_blocks(
+    blocks: &[Value],
+    title: &mut Option<String>,
+    sections: &mut Vec<CardElement>,
+    buttons: &mut Vec<CardButton>,
+) {
+    let text_of = |value: &Value| {
+        value
+            .get("text")
+            .and_then(Value::as_str)
+            .map(mrkdwn_to_lark_md)
+    };
+    for block in blocks {
+        match block.get("type").and_then(Value::as_str) {
+            Some("header") => {
+                let header = block
+                    .get("text")
+                    .and_then(|text| text.get("text"))
+                    .and_then(Value::as_str);
+                if let (None, Some(header)) = (&title, header) {
+                    *title = Some(header.to_string());
+                }
+            }
+            Some("section") => {
+                let mut lines: Vec<String> =
+                    block.get("text").and_then(text_of).into_iter().collect();
+                if let Some(fields) = block.get("fields").and_then(Value::as_array) {
+                    lines.extend(fields.iter().filter_map(text_of));
+                }
+                if !lines.is_empty() {
+                    sections.push(div(lines.join("\n")));
+                }
+            }
+            Some("context") => {
+                let elements = block.get("elements").and_then(Value::as_array);
+                let texts: Vec<CardText> = elements
+                    .into_iter()
+                    .flatten()
+                    .filter_map(text_of)
+                    .map(|content| CardText {
+                        tag: "lark_md".to_string(),
+                        content,
+                    })
+                    .collect();
+                if !texts.is_empty() {
+                    sections.push(CardElement::Note { elements: texts });
+                }
+            }
+            Some("divider") => sections.push(CardElement::Hr),
+            Some("actions") => {
+                let elements = block
+                    .get("elements")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten();
+                for element in elements {
+                    let label = element
+                        .get("text")
+                        .and_then(|text| text.get("text"))
+                        .and_then(Value::as_str);
+                    let url = element.get("url").and_then(Value::as_str);
+                    if let (Some(label), Some(url)) = (label, url) {
+                        buttons.push(CardButton::new(label, url));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Header color for a Slack attachment color.
+fn attachment_color(color: &str) -> &'static str {
+    match color {
+        "good" => "green",
+        "warning" => "orange",
+        "danger" => "red",
+        _ => "blue",
+    }
+}
+
+/// Convert Slack mrkdwn to lark_md.
+///
+/// `<url|label>` and `<url>` become links, `<!channel>`/`<!here>`/`<!everyone>`
+/// mention everyone, `*bold*`, `_italic_` and `~strike~` are rewritten, and the
+/// `&amp;`, `&lt;` and `&gt;` escapes are decoded.
+pub fn mrkdwn_to_lark_md(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&emphasis(&rest[..start]));
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let inner = &rest[start + 1..start + end];
+        out.push_str(&match inner.split_once('|') {
+            _ if matches!(inner, "!channel" | "!here" | "!everyone") => {
+                "<at id=all></at>".to_string()
+            }
+            Some((target, label)) if target.starts_with('@') || target.starts_with('#') => {
+                format!("{}{}", &target[..1], label)
+            }
+            Some((url, label)) => format!("[{}]({})", unescape(label), unescape(url)),
+            None if inner.starts_with('@') || inner.starts_with('#') => inner.to_string(),
+            None => format!("[{}]({})", unescape(inner), unescape(inner)),
+        });
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(&emphasis(rest));
+    out
+}
+
+/// Rewrite mrkdwn emphasis markers outside of links, and decode escapes.
+fn emphasis(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let marker = chars[i];
+        let replacement = match marker {
+            '*' => "**",
+            '_' => "*",
+            '~' => "~~",
+            _ => "",
+        };
+        // A marker opens emphasis only at the start of a word and only if it has
+        // a closing partner at the end of one on the same line, so the
+        // underscores in `some_var_name` are left alone.
+        let opens = !replacement.is_empty()
+            && (i == 0 || !chars[i - 1].is_alphanumeric())
+            && chars.get(i + 1).is_some_and(|c| !c.is_whitespace());
+        let close = opens
+            .then(|| {
+                let line = chars[i + 1..].iter().take_while(|&&c| c != '\n').count();
+                (1..line).find(|&len| {
+                    let at = i + 1 + len;
+                    chars[at] == marker
+                        && !chars[at - 1].is_whitespace()
+                        && chars.get(at + 1).is_none_or(|c| !c.is_alphanumeric())
+                })
+            })
+            .flatten();
+        match close {
+            Some(len) => {
+                let inner: String = chars[i + 1..i + 1 + len].iter().collect();
+                out.push_str(replacement);
+                out.push_str(&inner);
+                out.push_str(replacement);
+                i += len + 2;
+            }
+            None => {
+                out.push(marker);
+                i += 1;
+            }
+        }
+    }
+    unescape(&out)
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,237 @@
+use crate::message::{
+    LarkContent, LarkMessage, LarkPost, LarkPostContent, LarkTextContent, Locale,
+};
+
+/// Lark's limit on the request body of a custom bot webhook.
+pub const DEFAULT_MAX_BYTES: usize = 20 * 1024;
+
+/// Reserved in every part for the title suffix, so sizes hold once it is known.
+const PART_SUFFIX_PLACEHOLDER: &str = " (999/999)";
+
+/// Split a post whose JSON payload would exceed `max_bytes` into several posts.
+///
+/// Rows (paragraphs) are kept together where possible; a row that does not fit
+/// on its own is broken between elements, so a keyword or link is never cut.
+/// Each part gets "(i/n)" appended to its title. Other message types, and
+/// posts that already fit, are returned unchanged.
+pub fn split_message(message: LarkMessage, max_bytes: usize) -> Vec<LarkMessage> {
+    if payload_size(&message) <= max_bytes {
+        return vec![message];
+    }
+    let Some(LarkContent::Post { post }) = message.content else {
+        return vec![message];
+    };
+
+    let locales: Vec<(Locale, LarkPostContent)> = [
+        (Locale::ZhCn, post.zh_cn),
+        (Locale::EnUs, post.en_us),
+        (Locale::JaJp, post.ja_jp),
+    ]
+    .into_iter()
+    .filter_map(|(locale, content)| content.map(|content| (locale, content)))
+    .map(|(locale, content)| (locale, break_long_rows(locale, content, max_bytes)))
+    .collect();
+
+    let rows = locales
+        .iter()
+        .map(|(_, content)| content.content.len())
+        .max()
+        .unwrap_or(0);
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while start < rows {
+        let mut end = start + 1;
+        while end < rows
+            && payload_size(&part(&locales, start, end + 1, PART_SUFFIX_PLACEHOLDER)) <= max_bytes
+        {
+            end += 1;
+        }
+        ranges.push((start, end));
+        start = end;
+    }
+
+    let total = ranges.len();
+    ranges
+        .into_iter()
+        .enumerate()
+        .map(|(i, (start, end))| part(&locales, start, end, &format!(" ({}/{})", i + 1, total)))
+        .collect()
+}
+
+/// Break rows that are too big to be sent on their own into several rows.
+fn break_long_rows(locale: Locale, content: LarkPostContent, max_bytes: usize) -> LarkPostContent {
+    let fits = |row: &[LarkTextContent]| {
+        let title = format!("{}{}", content.title, PART_SUFFIX_PLACEHOLDER);
+        let mut post = LarkPost::default();
+        post.set(
+            locale,
+            LarkPostContent {
+                title,
+                content: vec![row.to_vec()],
+            },
+        );
+        payload_size(&LarkMessage::from_post(post)) <= max_bytes
+    };
+
+    let mut rows = Vec::new();
+    for row in &content.content {
+        if fits(row) {
+            rows.push(row.clone());
+            continue;
+        }
+
+        let mut current: Vec<LarkTextContent> = Vec::new();
+        for element in row.iter().flat_map(|element| split_text(element, fits)) {
+            current.push(element);
+            if current.len() > 1 && !fits(&current) {
+                let element = current.pop().expect("row has at least two elements");
+                rows.push(std::mem::replace(&mut current, vec![element]));
+            }
+        }
+        rows.push(current);
+    }
+
+    LarkPostContent {
+        title: content.title,
+        content: rows,
+    }
+}
+
+/// Cut a plain text element that does not fit on its own into pieces that do.
+/// Keywords, links and mentions are returned whole.
+fn split_text(
+    element: &LarkTextContent,
+    fits: impl Fn(&[LarkTextContent]) -> bool,
+) -> Vec<LarkTextContent> {
+    if element.tag != "text" || element.style.is_some() || fits(std::slice::from_ref(element)) {
+        return vec![element.clone()];
+    }
+
+    let mut pieces = Vec::new();
+    let mut rest = element.text.as_str();
+    while !rest.is_empty() {
+        let boundaries: Vec<usize> = rest
+            .char_indices()
+            .map(|(i, _)| i)
+            .skip(1)
+            .chain([rest.len()])
+            .collect();
+        // The longest prefix that fits, but always at least one character.
+        let count = boundaries.partition_point(|&end| fits(&[LarkTextContent::text(&rest[..end])]));
+        let end = boundaries[count.saturating_sub(1)];
+        pieces.push(LarkTextContent::text(&rest[..end]));
+        rest = &rest[end..];
+    }
+    pieces
+}
+
+/// A post holding rows `start..end` of every locale, with `suffix` added to the titles.
+fn part(
+    locales: &[(Locale, LarkPostContent)],
+    start: usize,
+    end: usize,
+    suffix: &str,
+) -> LarkMessage {
+    let mut post = LarkPost::default();
+    for (locale, content) in locales {
+        let rows = &content.content;
+        post.set(
+            *locale,
+            LarkPostContent {
+                title: format!("{}{}", content.title, suffix),
+                content: rows[start.min(rows.len())..end.min(rows.len())].to_vec(),
+            },
+        );
+    }
+    LarkMessage::from_post(post)
+}
+
+/// Marker sized for any realistic count, so sizes hold once the count is known.
+const TRUNCATED_PLACEHOLDER: &str = "(truncated, 999999999 bytes omitted)";
+
+/// Cut a post or text message whose JSON payload would exceed `max_bytes`,
+/// ending it with "(truncated, N bytes omitted)", N counting content bytes.
+///
+/// Posts keep whole rows (breaking a row that does not fit on its own, as
+/// [`split_message`] does) and text is cut on a character boundary. Other
+/// message types, and messages that already fit, are returned unchanged.
+pub fn truncate_message(message: LarkMessage, max_bytes: usize) -> LarkMessage {
+    if payload_size(&message) <= max_bytes {
+        return message;
+    }
+    let marker = |omitted: usize| format!("(truncated, {} bytes omitted)", omitted);
+
+    match message.content {
+        Some(LarkContent::Post { post }) => {
+            let locales: Vec<(Locale, LarkPostContent)> = [
+                (Locale::ZhCn, post.zh_cn),
+                (Locale::EnUs, post.en_us),
+                (Locale::JaJp, post.ja_jp),
+            ]
+            .into_iter()
+            .filter_map(|(locale, content)| content.map(|content| (locale, content)))
+            .map(|(locale, content)| (locale, break_long_rows(locale, content, max_bytes)))
+            .collect();
+            let rows = locales
+                .iter()
+                .map(|(_, content)| content.content.len())
+                .max()
+                .unwrap_or(0);
+            let truncated = |kept: usize, marker: &str| {
+                let mut message = part(&locales, 0, kept, "");
+                if let Some(LarkContent::Post { post }) = &mut message.content {
+                    for content in [&mut post.zh_cn, &mut post.en_us, &mut post.ja_jp]
+                        .into_iter()
+                        .flatten()
+                    {
+                        content.content.push(vec![LarkTextContent::text(marker)]);
+                    }
+                }
+                message
+            };
+
+            // Keeping fewer rows always fits at least as well, so search for the most.
+            let kept = (0..=rows).collect::<Vec<_>>().partition_point(|&kept| {
+                kept == 0 || payload_size(&truncated(kept, TRUNCATED_PLACEHOLDER)) <= max_bytes
+            }) - 1;
+            let omitted = locales
+                .iter()
+                .map(|(_, content)| {
+                    content
+                        .content
+                        .iter()
+                        .skip(kept)
+                        .flatten()
+                        .map(|element| element.text.len())
+                        .sum::<usize>()
+                })
+                .max()
+                .unwrap_or(0);
+            truncated(kept, &marker(omitted))
+        }
+        Some(LarkContent::Text { text }) => {
+            let boundaries: Vec<usize> = text
+                .char_indices()
+                .map(|(i, _)| i)
+                .chain([text.len()])
+                .collect();
+            let fits = |end: usize| {
+                let text = format!("{}\n{}", &text[..end], TRUNCATED_PLACEHOLDER);
+                payload_size(&LarkMessage::text(text)) <= max_bytes
+            };
+            let end = boundaries[boundaries
+                .partition_point(|&end| end == 0 || fits(end))
+                .saturating_sub(1)];
+            LarkMessage::text(format!("{}\n{}", &text[..end], marker(text.len() - end)))
+        }
+        content => LarkMessage { content, ..message },
+    }
+}
+
+/// Size of the JSON body as sent, leaving room for a signature and timestamp.
+pub fn payload_size(message: &LarkMessage) -> usize {
+    let mut message = message.clone();
+    message.sign = Some("x".repeat(44));
+    message.timestamp = Some("0".repeat(10));
+    serde_json::to_vec(&message).map_or(0, |json| json.len())
+}
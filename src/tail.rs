@@ -0,0 +1,67 @@
+use std::collections::VecDeque;
+
+/// The last lines of a command's output, kept in bounded memory however much
+/// it writes: at most `max_lines` lines of at most [`Tail::MAX_LINE_BYTES`] each.
+///
+/// ```
+/// use lark_notifier::Tail;
+///
+/// let mut tail = Tail::new(2);
+/// for line in ["one", "two", "three"] {
+///     tail.push(line.as_bytes());
+/// }
+/// assert_eq!(tail.total(), 3);
+/// assert_eq!(tail.lines().collect::<Vec<_>>(), ["two", "three"]);
+///
+/// tail.push(&vec![b'x'; Tail::MAX_LINE_BYTES + 10]);
+/// assert!(tail.lines().last().unwrap().ends_with("…"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Tail {
+    max_lines: usize,
+    lines: VecDeque<String>,
+    total: u64,
+}
+
+impl Tail {
+    /// Longest line kept; the rest of a longer line is replaced by `…`.
+    pub const MAX_LINE_BYTES: usize = 1000;
+
+    pub fn new(max_lines: usize) -> Self {
+        Tail {
+            max_lines,
+            lines: VecDeque::with_capacity(max_lines.min(1024)),
+            total: 0,
+        }
+    }
+
+    /// Add a line, without its newline, dropping the oldest one if full.
+    pub fn push(&mut self, line: &[u8]) {
+        self.total += 1;
+        if self.max_lines == 0 {
+            return;
+        }
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let mut text =
+            String::from_utf8_lossy(&line[..line.len().min(Self::MAX_LINE_BYTES)]).into_owned();
+        if line.len() > Self::MAX_LINE_BYTES {
+            // A cut through a multi-byte character leaves a replacement character.
+            text.truncate(text.trim_end_matches('\u{fffd}').len());
+            text.push('…');
+        }
+        if self.lines.len() == self.max_lines {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(text);
+    }
+
+    /// The kept lines, oldest first.
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().map(String::as_str)
+    }
+
+    /// How many lines were pushed, including those no longer kept.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use crate::error::NotifierError;
+
+const BUILTIN: &[(&str, &str)] = &[
+    (
+        "deploy",
+        "Deployed {{ service }} {{ version }}\n\
+         {{ service }} {{ version }} is live on {{ environment }}.\n",
+    ),
+    (
+        "alert",
+        "[{{ severity }}] {{ summary }}\n\
+         {{ summary }}\n\
+         Host: {{ host }}\n",
+    ),
+];
+
+/// A message template: the first line is the title, the rest is the content.
+///
+/// `{{ name }}` is replaced with the variable `name`, and `{{ env.NAME }}` with
+/// the environment variable `NAME`. Using a variable that is not set is an error.
+#[derive(Debug, Clone)]
+pub struct Template {
+    name: String,
+    source: String,
+}
+
+impl Template {
+    pub fn new(name: impl Into<String>, source: impl Into<String>) -> Self {
+        Template {
+            name: name.into(),
+            source: source.into(),
+        }
+    }
+
+    /// One of the templates shipped with the crate: `deploy` or `alert`.
+    pub fn builtin(name: &str) -> Option<Self> {
+        BUILTIN
+            .iter()
+            .find(|(builtin, _)| *builtin == name)
+            .map(|(name, source)| Template::new(*name, *source))
+    }
+
+    /// Names of the built-in templates.
+    pub fn builtin_names() -> impl Iterator<Item = &'static str> {
+        BUILTIN.iter().map(|(name, _)| *name)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, NotifierError> {
+        let source = fs::read_to_string(path).map_err(|e| {
+            NotifierError::Template(format!("Failed to read template {}: {}", path.display(), e))
+        })?;
+        Ok(Template::new(path.display().to_string(), source))
+    }
+
+    /// Fill in the placeholders and return the title and content.
+    pub fn render(
+        &self,
+        vars: &BTreeMap<String, String>,
+    ) -> Result<(String, String), NotifierError> {
+        let mut rendered = String::with_capacity(self.source.len());
+        for (index, line) in self.source.lines().enumerate() {
+            rendered.push_str(&self.render_line(line, index + 1, vars)?);
+            rendered.push('\n');
+        }
+
+        let (title, content) = rendered.split_once('\n').unwrap_or((&rendered, ""));
+        Ok((title.trim().to_string(), content.trim_end().to_string()))
+    }
+
+    fn render_line(
+        &self,
+        line: &str,
+        number: usize,
+        vars: &BTreeMap<String, String>,
+    ) -> Result<String, NotifierError> {
+        let mut out = String::with_capacity(line.len());
+        let mut rest = line;
+        while let Some(start) = rest.find("{{") {
+            out.push_str(&rest[..start]);
+            let end = rest[start..].find("}}").ok_or_else(|| {
+                NotifierError::Template(format!("{}:{}: unclosed '{{{{'", self.name, number))
+            })?;
+            let name = rest[start + 2..start + end].trim();
+            let value = match name.strip_prefix("env.") {
+                Some(var) => env::var(var).ok(),
+                None => vars.get(name).cloned(),
+            };
+            let value = value.ok_or_else(|| {
+                NotifierError::Template(format!(
+                    "{}:{}: undefined variable '{}'",
+                    self.name, number, name
+                ))
+            })?;
+            out.push_str(&value);
+            rest = &rest[start + end + 2..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+/// Replace `{{NAME}}` (or `{{ NAME }}`) with the environment variable `NAME`.
+///
+/// Only names of letters, digits and underscores are placeholders; anything
+/// else between braces is left alone. A variable that is not set is left as
+/// written, or with `strict` is an error.
+pub fn interpolate_env(text: &str, strict: bool) -> Result<String, NotifierError> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        let token = &rest[start..start + end + 2];
+        let name = token[2..token.len() - 2].trim();
+        let is_name = !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        match (is_name, is_name.then(|| env::var(name).ok()).flatten()) {
+            (true, Some(value)) => out.push_str(&value),
+            (true, None) if strict => {
+                return Err(NotifierError::Template(format!(
+                    "environment variable '{}' is not set (placeholder {})",
+                    name, token
+                )));
+            }
+            _ => out.push_str(token),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
@@ -0,0 +1,83 @@
+use base64::{Engine as _, engine::general_purpose};
+use reqwest::Certificate;
+use std::path::Path;
+
+use crate::error::NotifierError;
+
+/// Read every certificate in the PEM file at `path`, e.g. the root CA of a
+/// TLS-inspecting proxy, to trust alongside the system roots.
+pub fn read_ca_certs(path: &Path) -> Result<Vec<Certificate>, NotifierError> {
+    let pem = std::fs::read(path).map_err(|source| NotifierError::Io {
+        context: format!("Failed to read CA certificate file {}", path.display()),
+        source,
+    })?;
+    parse_ca_certs(&path.display().to_string(), &pem)
+}
+
+/// Parse every `CERTIFICATE` block in `pem`, naming `name` and the byte offset
+/// of the block in errors. Other blocks, such as keys, are skipped.
+///
+/// ```
+/// use lark_notifier::parse_ca_certs;
+///
+/// let pem = "-----BEGIN CERTIFICATE-----\nnot base64!\n-----END CERTIFICATE-----\n";
+/// let err = parse_ca_certs("ca.pem", pem.as_bytes()).unwrap_err();
+/// assert!(err.to_string().contains("ca.pem: certificate at byte 0"), "{}", err);
+/// assert!(parse_ca_certs("empty.pem", b"").is_err());
+/// ```
+pub fn parse_ca_certs(name: &str, pem: &[u8]) -> Result<Vec<Certificate>, NotifierError> {
+    let invalid = |reason: String| NotifierError::InvalidCertificate {
+        name: name.to_string(),
+        reason,
+    };
+    let text = std::str::from_utf8(pem).map_err(|err| {
+        invalid(format!(
+            "not a PEM file: invalid UTF-8 at byte {}",
+            err.valid_up_to()
+        ))
+    })?;
+
+    let mut certs = Vec::new();
+    let mut pos = 0;
+    while let Some(found) = text[pos..].find("-----BEGIN ") {
+        let start = pos + found;
+        let label_start = start + "-----BEGIN ".len();
+        let Some(label_len) = text[label_start..].find("-----") else {
+            return Err(invalid(format!("malformed BEGIN line at byte {}", start)));
+        };
+        let label = &text[label_start..label_start + label_len];
+        let body_start = label_start + label_len + "-----".len();
+        let end = format!("-----END {}-----", label);
+        let Some(body_len) = text[body_start..].find(&end) else {
+            return Err(invalid(format!(
+                "BEGIN {} at byte {} has no matching END line",
+                label, start
+            )));
+        };
+        pos = body_start + body_len + end.len();
+        if label != "CERTIFICATE" {
+            continue;
+        }
+
+        let body: String = text[body_start..body_start + body_len]
+            .split_whitespace()
+            .collect();
+        let der = general_purpose::STANDARD.decode(body).map_err(|err| {
+            invalid(format!(
+                "certificate at byte {} is not valid base64: {}",
+                start, err
+            ))
+        })?;
+        let cert = Certificate::from_der(&der).map_err(|err| {
+            invalid(format!(
+                "certificate at byte {} could not be parsed: {}",
+                start, err
+            ))
+        })?;
+        certs.push(cert);
+    }
+    if certs.is_empty() {
+        return Err(invalid("no PEM certificates found".to_string()));
+    }
+    Ok(certs)
+}
@@ -0,0 +1,88 @@
+//! [`AlertmanagerPayload`] cards, rendered from the example webhook payload.
+
+use lark_notifier::AlertmanagerPayload;
+use serde_json::Value;
+
+fn example() -> AlertmanagerPayload {
+    serde_json::from_str(include_str!("../examples/alertmanager.json")).expect("example parses")
+}
+
+/// The `lark_md` text of every section of `payload`'s card, the summary first.
+fn sections(payload: &AlertmanagerPayload) -> Vec<String> {
+    let card = serde_json::to_value(payload.to_card()).unwrap();
+    card["elements"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|element| element["text"]["content"].as_str().map(str::to_string))
+        .collect()
+}
+
+fn header(payload: &AlertmanagerPayload) -> Value {
+    serde_json::to_value(payload.to_card()).unwrap()["header"].clone()
+}
+
+#[test]
+fn firing_group_has_a_severity_colored_header() {
+    let payload = example();
+    assert_eq!(
+        header(&payload)["title"]["content"],
+        "[FIRING:1] alertname=HighLatency job=api severity=critical"
+    );
+    assert_eq!(header(&payload)["template"], "red");
+}
+
+#[test]
+fn one_section_per_alert_after_the_summary() {
+    let sections = sections(&example());
+    assert_eq!(sections.len(), 3);
+    assert_eq!(
+        sections[0],
+        "API p99 latency is above 500ms\n**1** firing, **1** resolved\n[Alertmanager](http://alertmanager.example.com:9093)"
+    );
+    assert!(sections[1].starts_with(
+        "🔥 FIRING **HighLatency**\np99 latency on api-1 is 742ms\nLabels: instance=api-1:8080\n"
+    ));
+    assert!(!sections[1].contains("Resolved:"));
+    assert!(sections[2].starts_with("✅ RESOLVED **HighLatency**\n"));
+    assert!(sections[2].contains("\nResolved: 2024-05-01T12:05:00.000Z\n"));
+}
+
+#[test]
+fn resolved_group_is_green() {
+    let mut payload = example();
+    payload.status = "resolved".to_string();
+    payload
+        .alerts
+        .iter_mut()
+        .for_each(|alert| alert.status = "resolved".to_string());
+
+    assert_eq!(
+        header(&payload)["title"]["content"],
+        "[RESOLVED:2] alertname=HighLatency job=api severity=critical"
+    );
+    assert_eq!(header(&payload)["template"], "green");
+    assert!(
+        sections(&payload)[1..]
+            .iter()
+            .all(|section| section.starts_with("✅ RESOLVED"))
+    );
+}
+
+#[test]
+fn color_follows_the_most_severe_firing_alert() {
+    let mut payload = example();
+    payload.common_labels.remove("severity");
+    payload.alerts[0]
+        .labels
+        .insert("severity".to_string(), "warning".to_string());
+    assert_eq!(payload.color(), "yellow");
+
+    // The resolved alert's severity no longer counts.
+    payload.alerts[1]
+        .labels
+        .insert("severity".to_string(), "critical".to_string());
+    assert_eq!(payload.color(), "yellow");
+    payload.alerts[1].status = "firing".to_string();
+    assert_eq!(payload.color(), "red");
+}
@@ -0,0 +1,1552 @@
+//! The `lark-notifier` binary against mock Lark servers: request bodies, output and exit statuses.
+
+use std::net::TcpListener;
+use std::process::Output;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use lark_notifier::{SignVersion, generate_sign};
+use serde_json::{Value, json};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use wiremock::matchers::{body_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const HOOK: &str = "/open-apis/bot/v2/hook/test-token";
+
+async fn server(response: ResponseTemplate) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(response)
+        .mount(&server)
+        .await;
+    server
+}
+
+fn ok() -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({ "code": 0, "msg": "success", "data": {} }))
+}
+
+/// Run the binary against `webhook_url` with `args`, isolated from the
+/// environment and any config file.
+async fn run(webhook_url: &str, args: &[&str]) -> Output {
+    run_with_env(webhook_url, &[], args).await
+}
+
+/// [`run`] with only the variables in `vars` set, e.g. those of a CI job.
+async fn run_with_env(webhook_url: &str, vars: &[(&str, &str)], args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lark-notifier"))
+        .env_clear()
+        .env("HOME", env!("CARGO_TARGET_TMPDIR"))
+        .envs(vars.iter().copied())
+        .args([
+            "--webhook-url",
+            webhook_url,
+            "--allow-insecure-url",
+            "--retries",
+            "0",
+        ])
+        .args(args)
+        .output()
+        .await
+        .expect("binary runs")
+}
+
+async fn bodies(server: &MockServer) -> Vec<Value> {
+    let requests = server
+        .received_requests()
+        .await
+        .expect("request recording is enabled");
+    requests
+        .iter()
+        .map(|request| request.body_json().expect("JSON body"))
+        .collect()
+}
+
+#[tokio::test]
+async fn sends_a_post() {
+    let server = server(ok()).await;
+    let output = run(
+        &format!("{}{}", server.uri(), HOOK),
+        &["--title", "Deploy", "--content", "line 1\nline 2"],
+    )
+    .await;
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        bodies(&server).await,
+        [json!({
+            "msg_type": "post",
+            "content": { "post": { "zh_cn": {
+                "title": "Deploy",
+                "content": [[{ "tag": "text", "text": "line 1" }], [{ "tag": "text", "text": "line 2" }]],
+            } } },
+        })]
+    );
+}
+
+#[tokio::test]
+async fn signs_only_with_a_secret() {
+    let server = server(ok()).await;
+    let url = format!("{}{}", server.uri(), HOOK);
+    run(&url, &["--format", "text", "--content", "hi"]).await;
+    run(
+        &url,
+        &["--format", "text", "--content", "hi", "--secret", "secret"],
+    )
+    .await;
+
+    let bodies = bodies(&server).await;
+    assert_eq!(
+        bodies[0],
+        json!({ "msg_type": "text", "content": { "text": "hi" } })
+    );
+    assert!(
+        bodies[1]["sign"].is_string() && bodies[1]["timestamp"].is_string(),
+        "{}",
+        bodies[1]
+    );
+}
+
+#[tokio::test]
+async fn every_sign_version_is_accepted() {
+    let server = server(ok()).await;
+    let url = format!("{}{}", server.uri(), HOOK);
+    for version in SignVersion::ALL {
+        let args = [
+            "--format",
+            "text",
+            "--content",
+            "hi",
+            "--secret",
+            "secret",
+            "--timestamp",
+            "1599360473",
+        ];
+        let output = run(
+            &url,
+            &[&args[..], &["--sign-version", version.name()]].concat(),
+        )
+        .await;
+        assert_eq!(
+            output.status.code(),
+            Some(0),
+            "{}: {}",
+            version,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    let signs: Vec<Value> = bodies(&server)
+        .await
+        .iter()
+        .map(|body| body["sign"].clone())
+        .collect();
+    let expected: Vec<Value> = SignVersion::ALL
+        .iter()
+        .map(|version| version.sign(1599360473, "secret").into())
+        .collect();
+    assert_eq!(signs, expected);
+
+    let output = run(
+        &url,
+        &[
+            "--format",
+            "text",
+            "--content",
+            "hi",
+            "--sign-version",
+            "md5",
+        ],
+    )
+    .await;
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[tokio::test]
+async fn sign_mismatch_exits_rejected() {
+    let server = server(ResponseTemplate::new(200).set_body_json(json!({
+        "code": 19021,
+        "msg": "sign match fail or timestamp is not within one hour from current time",
+    })))
+    .await;
+    let output = run(
+        &format!("{}{}", server.uri(), HOOK),
+        &["--format", "text", "--content", "hi", "--secret", "wrong"],
+    )
+    .await;
+
+    assert_eq!(output.status.code(), Some(5));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("19021"));
+}
+
+#[tokio::test]
+async fn http_error_exits_http_status() {
+    let server = server(ResponseTemplate::new(500).set_body_string("oops")).await;
+    let output = run(
+        &format!("{}{}", server.uri(), HOOK),
+        &["--format", "text", "--content", "hi"],
+    )
+    .await;
+
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[tokio::test]
+async fn failed_sends_never_exit_zero() {
+    let lark_error =
+        ResponseTemplate::new(200).set_body_json(json!({ "code": 9499, "msg": "Bad Request" }));
+    let cases = [
+        (ResponseTemplate::new(500), 4),
+        (ResponseTemplate::new(404), 4),
+        (lark_error, 5),
+    ];
+    for (response, code) in cases {
+        let server = server(response).await;
+        let output = run(
+            &format!("{}{}", server.uri(), HOOK),
+            &["--format", "text", "--content", "hi"],
+        )
+        .await;
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(code), "{}", stderr);
+        assert!(stderr.starts_with("Error: "), "{}", stderr);
+        assert!(output.stdout.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn malformed_response_is_printed_raw() {
+    let server = server(ResponseTemplate::new(200).set_body_string("not json")).await;
+    let output = run(
+        &format!("{}{}", server.uri(), HOOK),
+        &["--format", "text", "--content", "hi"],
+    )
+    .await;
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Unrecognized response: not json"));
+}
+
+#[tokio::test]
+async fn quiet_prints_nothing_on_success_but_keeps_json() {
+    let server = server(ok()).await;
+    let url = format!("{}{}", server.uri(), HOOK);
+    let output = run(&url, &["--format", "text", "--content", "hi", "--quiet"]).await;
+    assert_eq!(output.status.code(), Some(0));
+    assert!(
+        output.stdout.is_empty() && output.stderr.is_empty(),
+        "{:?}",
+        output
+    );
+
+    let output = run(
+        &url,
+        &[
+            "--format",
+            "text",
+            "--content",
+            "hi",
+            "-q",
+            "--output",
+            "json",
+        ],
+    )
+    .await;
+    assert_eq!(output.status.code(), Some(0));
+    let outcome: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(outcome["ok"], true);
+}
+
+#[tokio::test]
+async fn quiet_still_reports_failures() {
+    let server = server(ResponseTemplate::new(500)).await;
+    let output = run(
+        &format!("{}{}", server.uri(), HOOK),
+        &["--format", "text", "--content", "hi", "--quiet"],
+    )
+    .await;
+
+    assert_eq!(output.status.code(), Some(4));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Error: "));
+}
+
+#[tokio::test]
+async fn connection_failure_exits_transport() {
+    // A port that was just free, so nothing is listening on it.
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let output = run(
+        &format!("http://127.0.0.1:{}{}", port, HOOK),
+        &["--format", "text", "--content", "hi"],
+    )
+    .await;
+
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[tokio::test]
+async fn invalid_webhook_url_names_the_flag() {
+    let output = Command::new(env!("CARGO_BIN_EXE_lark-notifier"))
+        .env_clear()
+        .env("HOME", env!("CARGO_TARGET_TMPDIR"))
+        .args([
+            "--webhook-url",
+            "http://example.com/hook",
+            "--format",
+            "text",
+            "--content",
+            "hi",
+        ])
+        .output()
+        .await
+        .expect("binary runs");
+
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("(--allow-insecure-url)") && !stderr.contains("allow_insecure_url"),
+        "{}",
+        stderr
+    );
+}
+
+#[tokio::test]
+async fn missing_title_exits_config() {
+    let server = server(ok()).await;
+    let output = run(&format!("{}{}", server.uri(), HOOK), &["--content", "hi"]).await;
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(bodies(&server).await.is_empty());
+}
+
+#[tokio::test]
+async fn sends_to_a_chat_and_reports_the_message_id() {
+    let server = MockServer::start().await;
+    Mock::given(path("/open-apis/auth/v3/tenant_access_token/internal"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "code": 0,
+            "tenant_access_token": "t-test",
+            "expire": 7200,
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(path("/open-apis/im/v1/messages"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "code": 0,
+            "msg": "success",
+            "data": { "message_id": "om_test" },
+        })))
+        .mount(&server)
+        .await;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lark-notifier"))
+        .env_clear()
+        .env("HOME", env!("CARGO_TARGET_TMPDIR"))
+        .env("LARK_APP_ID", "cli_test")
+        .env("LARK_APP_SECRET", "app-secret")
+        .args([
+            "--chat-id",
+            "oc_test",
+            "--api-base-url",
+            &server.uri(),
+            "--format",
+            "text",
+            "--content",
+            "hi",
+        ])
+        .args(["--output", "json"])
+        .output()
+        .await
+        .expect("binary runs");
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let outcome: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(outcome["message_id"], "om_test");
+    assert_eq!(outcome["target"], "oc_test");
+}
+
+#[tokio::test]
+async fn verbose_logs_redact_the_tenant_access_token() {
+    let server = MockServer::start().await;
+    let token = "t-g1044qeGEDXTB6NDJOGV4JQCYDGHRBARFTGT1234";
+    Mock::given(path("/open-apis/auth/v3/tenant_access_token/internal"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "code": 0,
+            "tenant_access_token": token,
+            "expire": 7200,
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(path("/open-apis/im/v1/messages"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "code": 0,
+            "msg": "success",
+            "data": { "message_id": "om_test" },
+        })))
+        .mount(&server)
+        .await;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lark-notifier"))
+        .env_clear()
+        .env("HOME", env!("CARGO_TARGET_TMPDIR"))
+        .env("LARK_APP_ID", "cli_test")
+        .env("LARK_APP_SECRET", "app-secret")
+        .args(["--chat-id", "oc_test", "--api-base-url", &server.uri()])
+        .args(["--format", "text", "--content", "hi", "-v"])
+        .output()
+        .await
+        .expect("binary runs");
+
+    assert_eq!(output.status.code(), Some(0));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("\"tenant_access_token\":\"<redacted>\""),
+        "{}",
+        stderr
+    );
+    assert!(!stderr.contains(token), "{}", stderr);
+}
+
+#[tokio::test]
+async fn sends_a_card_template() {
+    let server = server(ok()).await;
+    let args = [
+        "--card-template",
+        "AAqk1234",
+        "--card-var",
+        "service=api",
+        "--card-var",
+        "count=3",
+        "--card-var",
+        "hosts=[\"a\",\"b\"]",
+    ];
+    let output = run(&format!("{}{}", server.uri(), HOOK), &args).await;
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        bodies(&server).await,
+        [json!({
+            "msg_type": "interactive",
+            "card": { "type": "template", "data": {
+                "template_id": "AAqk1234",
+                "template_variable": { "service": "api", "count": 3, "hosts": ["a", "b"] },
+            } },
+        })]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn exec_reports_the_command_and_exits_with_its_status() {
+    let server = server(ok()).await;
+    let script = "for i in $(seq 1 1000); do echo line $i; done; echo failed >&2; exit 3";
+    let args = [
+        "exec",
+        "--allow-insecure-url",
+        "-t",
+        "Backup",
+        "--tail-lines",
+        "2",
+        "--",
+        "sh",
+        "-c",
+        script,
+    ];
+    let output = Command::new(env!("CARGO_BIN_EXE_lark-notifier"))
+        .env_clear()
+        .env("HOME", env!("CARGO_TARGET_TMPDIR"))
+        .env("LARK_WEBHOOK_URL", format!("{}{}", server.uri(), HOOK))
+        .args(args)
+        .output()
+        .await
+        .expect("binary runs");
+
+    assert_eq!(output.status.code(), Some(3));
+    // The output is passed through in full.
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| line.starts_with("line "))
+            .count(),
+        1000
+    );
+
+    let body = &bodies(&server).await[0];
+    let post = &body["content"]["post"]["zh_cn"];
+    assert_eq!(post["title"], "❌ Backup");
+    let text: Vec<&str> = post["content"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|row| row[0]["text"].as_str())
+        .collect();
+    assert!(
+        text[0].starts_with("Failed after ") && text[0].ends_with("(exit code 3)"),
+        "{:?}",
+        text
+    );
+    assert_eq!(
+        &text[text.len() - 3..],
+        ["Last 2 of 1001 lines of output:", "line 1000", "failed"]
+    );
+}
+
+#[tokio::test]
+async fn mentions_by_mobile_in_a_card() {
+    let server = MockServer::start().await;
+    Mock::given(path("/open-apis/auth/v3/tenant_access_token/internal"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "code": 0,
+            "tenant_access_token": "t-test",
+            "expire": 7200,
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(path("/open-apis/contact/v3/users/batch_get_id"))
+        .and(body_json(json!({ "mobiles": ["+8613800000000"] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "code": 0,
+            "msg": "success",
+            "data": { "user_list": [{ "mobile": "13800000000", "user_id": "ou_mobile" }] },
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(path(HOOK))
+        .respond_with(ok())
+        .mount(&server)
+        .await;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lark-notifier"))
+        .env_clear()
+        .env("HOME", env!("CARGO_TARGET_TMPDIR"))
+        .env("LARK_TENANT_ACCESS_TOKEN", "t-test")
+        .args([
+            "--webhook-url",
+            &format!("{}{}", server.uri(), HOOK),
+            "--allow-insecure-url",
+        ])
+        .args([
+            "--api-base-url",
+            &server.uri(),
+            "--format",
+            "card",
+            "--title",
+            "Disk full",
+            "--content",
+            "/var",
+        ])
+        .args(["--at", "ou_id", "--at-mobile", "+86 138-0000-0000"])
+        .output()
+        .await
+        .expect("binary runs");
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let requests = server.received_requests().await.unwrap();
+    let card: Value = requests.last().unwrap().body_json().unwrap();
+    assert_eq!(
+        card["card"]["elements"][0]["text"]["content"],
+        "/var\n<at id=ou_id></at> <at id=ou_mobile></at>"
+    );
+}
+
+#[tokio::test]
+async fn dry_run_prints_the_signed_payload() {
+    let url = "https://open.feishu.cn/open-apis/bot/v2/hook/0123456789abcdef";
+    let args = [
+        "--dry-run",
+        "--secret",
+        "secret",
+        "--timestamp",
+        "1599360473",
+        "--title",
+        "Deploy",
+        "--content",
+        "deploy done",
+    ];
+    let output = run(url, &[&args[..], &["--keyword", "done"]].concat()).await;
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        include_str!("fixtures/dry-run-post.json")
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Would POST to https://open.feishu.cn/open-apis/bot/v2/hook/0123************\n"
+    );
+}
+
+/// A webhook whose `Date` header is `skew` seconds behind the local clock.
+async fn skewed_server(skew: u64) -> MockServer {
+    let date = httpdate::fmt_http_date(SystemTime::now() - Duration::from_secs(skew));
+    let server = MockServer::start().await;
+    Mock::given(method("HEAD"))
+        .respond_with(ResponseTemplate::new(405).insert_header("date", date))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ok())
+        .mount(&server)
+        .await;
+    server
+}
+
+/// How many messages `server` was sent, leaving out the clock check.
+async fn posts(server: &MockServer) -> usize {
+    let requests = server
+        .received_requests()
+        .await
+        .expect("request recording is enabled");
+    requests
+        .iter()
+        .filter(|request| request.method == wiremock::http::Method::POST)
+        .count()
+}
+
+#[tokio::test]
+async fn check_clock_fails_when_the_clock_is_hours_off() {
+    let server = skewed_server(2 * 3600).await;
+    let args = [
+        "--format",
+        "text",
+        "--content",
+        "hi",
+        "--secret",
+        "secret",
+        "--check-clock",
+    ];
+    let output = run(&format!("{}{}", server.uri(), HOOK), &args).await;
+
+    assert_eq!(
+        output.status.code(),
+        Some(6),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(posts(&server).await, 0);
+}
+
+#[tokio::test]
+async fn check_clock_warns_about_a_small_skew() {
+    let server = skewed_server(600).await;
+    let args = [
+        "--format",
+        "text",
+        "--content",
+        "hi",
+        "--secret",
+        "secret",
+        "--check-clock",
+    ];
+    let output = run(&format!("{}{}", server.uri(), HOOK), &args).await;
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("ahead of the webhook server"));
+    assert_eq!(posts(&server).await, 1);
+}
+
+const GITLAB_JOB: &[(&str, &str)] = &[
+    ("CI_PROJECT_PATH", "group/app"),
+    (
+        "CI_PIPELINE_URL",
+        "https://gitlab.example.com/group/app/-/pipelines/42",
+    ),
+    ("CI_COMMIT_SHORT_SHA", "abc1234"),
+    ("CI_COMMIT_REF_NAME", "main"),
+    ("CI_JOB_NAME", "deploy"),
+    ("GITLAB_USER_NAME", "alex"),
+];
+
+/// The last row of the post in `body`, written out with links as `[text](href)`.
+fn footer(body: &Value) -> String {
+    let rows = body["content"]["post"]["zh_cn"]["content"]
+        .as_array()
+        .unwrap();
+    let row = rows.last().unwrap().as_array().unwrap();
+    row.iter()
+        .map(|element| match element["href"].as_str() {
+            Some(href) => format!("[{}]({})", element["text"].as_str().unwrap(), href),
+            None => element["text"].as_str().unwrap().to_string(),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn gitlab_footer_links_the_pipeline_and_commit() {
+    let server = server(ok()).await;
+    let url = format!("{}{}", server.uri(), HOOK);
+    let output = run_with_env(
+        &url,
+        GITLAB_JOB,
+        &["--gitlab", "--title", "Deploy", "--content", "done"],
+    )
+    .await;
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        footer(&bodies(&server).await[0]),
+        "Project: [group/app](https://gitlab.example.com/group/app) | Ref: main \
+         | Commit: [abc1234](https://gitlab.example.com/group/app/-/commit/abc1234) | Job: deploy | User: alex \
+         | Pipeline: [#42](https://gitlab.example.com/group/app/-/pipelines/42)"
+    );
+}
+
+#[tokio::test]
+async fn gitlab_outside_ci_names_the_missing_variables() {
+    let server = server(ok()).await;
+    let url = format!("{}{}", server.uri(), HOOK);
+    let output = run_with_env(
+        &url,
+        &GITLAB_JOB[..2],
+        &["--gitlab", "--title", "Deploy", "--content", "done"],
+    )
+    .await;
+
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(
+            "CI_COMMIT_SHORT_SHA, CI_COMMIT_REF_NAME, CI_JOB_NAME, GITLAB_USER_NAME not set"
+        ),
+        "{}",
+        stderr
+    );
+    assert!(bodies(&server).await.is_empty());
+}
+
+#[tokio::test]
+async fn github_footer_links_the_run_and_commit() {
+    let server = server(ok()).await;
+    let url = format!("{}{}", server.uri(), HOOK);
+    let vars = [
+        ("GITHUB_REPOSITORY", "octo/app"),
+        ("GITHUB_REF_NAME", "main"),
+        ("GITHUB_SHA", "0123456789abcdef0123456789abcdef01234567"),
+        ("GITHUB_ACTOR", "octocat"),
+        ("GITHUB_RUN_ID", "7"),
+    ];
+    let output = run_with_env(
+        &url,
+        &vars,
+        &["--github", "--title", "Deploy", "--content", "done"],
+    )
+    .await;
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        footer(&bodies(&server).await[0]),
+        "Repo: [octo/app](https://github.com/octo/app) | Ref: main \
+         | Commit: [0123456](https://github.com/octo/app/commit/0123456789abcdef0123456789abcdef01234567) \
+         | Actor: octocat | Run: [#7](https://github.com/octo/app/actions/runs/7)"
+    );
+}
+
+#[tokio::test]
+async fn template_with_keywords_in_a_dry_run() {
+    let template = format!("{}/release.tmpl", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(
+        &template,
+        "Released {{ version }}\n{{ version }} of {{ env.APP }} failed its smoke test\n",
+    )
+    .unwrap();
+    let url = "https://open.feishu.cn/open-apis/bot/v2/hook/0123456789abcdef";
+    let args = [
+        "--template",
+        &template,
+        "--var",
+        "version=1.2.0",
+        "--keyword",
+        "failed",
+        "--dry-run",
+    ];
+    let output = run_with_env(url, &[("APP", "api")], &args).await;
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let payload: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let post = &payload["content"]["post"]["zh_cn"];
+    assert_eq!(post["title"], "Released 1.2.0");
+    assert_eq!(
+        post["content"],
+        json!([[
+            { "tag": "text", "text": "1.2.0 of api " },
+            { "tag": "text", "text": "failed", "style": ["bold"] },
+            { "tag": "text", "text": " its smoke test" },
+        ]])
+    );
+
+    let output = run(
+        url,
+        &["--template", "deploy", "--var", "service=api", "--dry-run"],
+    )
+    .await;
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("deploy:1: undefined variable 'version'")
+    );
+}
+
+#[tokio::test]
+async fn markdown_file_links_and_bold() {
+    let server = server(ok()).await;
+    let notes = format!("{}/release-notes.md", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(
+        &notes,
+        "# Release 1.2\n\nShips **today**, see [notes](https://example.com/notes).\n",
+    )
+    .unwrap();
+    let output = run(
+        &format!("{}{}", server.uri(), HOOK),
+        &["--title", "Notes", "--markdown-file", &notes],
+    )
+    .await;
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        bodies(&server).await[0]["content"]["post"]["zh_cn"]["content"],
+        json!([
+            [{ "tag": "text", "text": "Release 1.2", "style": ["bold"] }],
+            [
+                { "tag": "text", "text": "Ships " },
+                { "tag": "text", "text": "today", "style": ["bold"] },
+                { "tag": "text", "text": ", see " },
+                { "tag": "a", "text": "notes", "href": "https://example.com/notes" },
+                { "tag": "text", "text": "." },
+            ],
+        ])
+    );
+}
+
+#[tokio::test]
+async fn rejects_a_malformed_mobile() {
+    let server = server(ok()).await;
+    let output = run(
+        &format!("{}{}", server.uri(), HOOK),
+        &[
+            "--format",
+            "text",
+            "--content",
+            "hi",
+            "--at-mobile",
+            "call me",
+        ],
+    )
+    .await;
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(bodies(&server).await.is_empty());
+}
+
+#[tokio::test]
+async fn rejects_a_keyword_link_without_keyword_or_url() {
+    let server = server(ok()).await;
+    let url = format!("{}{}", server.uri(), HOOK);
+    for spec in ["error", "error=", "=https://wiki/err"] {
+        let output = run(
+            &url,
+            &[
+                "--title",
+                "Deploy",
+                "--content",
+                "error",
+                "--keyword-link",
+                spec,
+            ],
+        )
+        .await;
+
+        assert_eq!(output.status.code(), Some(2), "{}", spec);
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("--keyword-link expects KEYWORD=URL")
+        );
+    }
+    assert!(bodies(&server).await.is_empty());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn heartbeat_sends_fresh_content_until_the_max_count() {
+    let server = server(ok()).await;
+    let url = format!("{}{}", server.uri(), HOOK);
+    let args = [
+        "--webhook-url",
+        &url,
+        "--allow-insecure-url",
+        "--format",
+        "text",
+        "--secret",
+        "secret",
+    ];
+    let output = Command::new(env!("CARGO_BIN_EXE_lark-notifier"))
+        .env_clear()
+        .env("HOME", env!("CARGO_TARGET_TMPDIR"))
+        .args([
+            "heartbeat",
+            "--interval",
+            "10ms",
+            "--max-count",
+            "3",
+            "--content-cmd",
+            "echo up",
+        ])
+        .args(args)
+        .output()
+        .await
+        .expect("binary runs");
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let bodies = bodies(&server).await;
+    assert_eq!(bodies.len(), 3);
+    assert!(
+        bodies
+            .iter()
+            .all(|body| body["content"]["text"] == "up" && body["sign"].is_string()),
+        "{:?}",
+        bodies
+    );
+}
+
+#[tokio::test]
+async fn unreachable_webhook_queues_the_message() {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let dir = format!("{}/cli-queue", env!("CARGO_TARGET_TMPDIR"));
+    let _ = std::fs::remove_dir_all(&dir);
+    let url = format!("http://127.0.0.1:{}{}", port, HOOK);
+    let output = run(
+        &url,
+        &[
+            "--format",
+            "text",
+            "--content",
+            "hi",
+            "--queue-dir",
+            &dir,
+            "--output",
+            "json",
+        ],
+    )
+    .await;
+
+    assert_eq!(output.status.code(), Some(3));
+    let outcome: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(outcome["queued"], 1);
+    let queued: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+    assert!(
+        queued
+            .iter()
+            .any(|entry| entry.file_name().to_string_lossy().ends_with(".json"))
+    );
+}
+
+#[tokio::test]
+async fn quiet_flush_still_reports_dropped_messages() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    drop(listener);
+    let dir = format!("{}/quiet-flush-queue", env!("CARGO_TARGET_TMPDIR"));
+    let _ = std::fs::remove_dir_all(&dir);
+    let url = format!("http://127.0.0.1:{}{}", port, HOOK);
+    let output = run(
+        &url,
+        &["--format", "text", "--content", "hi", "--queue-dir", &dir],
+    )
+    .await;
+    assert_eq!(output.status.code(), Some(3));
+
+    let server = MockServer::builder()
+        .listener(TcpListener::bind(("127.0.0.1", port)).unwrap())
+        .start()
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "code": 9499, "msg": "Bad Request" })),
+        )
+        .mount(&server)
+        .await;
+    let output = Command::new(env!("CARGO_BIN_EXE_lark-notifier"))
+        .env_clear()
+        .env("HOME", env!("CARGO_TARGET_TMPDIR"))
+        .args(["flush", "--webhook-url", &url, "--allow-insecure-url"])
+        .args(["--queue-dir", &dir, "--quiet"])
+        .output()
+        .await
+        .expect("binary runs");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(bodies(&server).await.len(), 1);
+    assert!(
+        stderr.contains("Dropped a queued message for") && stderr.contains("9499"),
+        "{}",
+        stderr
+    );
+    assert!(!stderr.contains("Flushed"), "{}", stderr);
+}
+
+#[tokio::test]
+async fn time_offset_moves_the_signature_timestamp() {
+    let server = server(ok()).await;
+    let output = run(
+        &format!("{}{}", server.uri(), HOOK),
+        &[
+            "--format",
+            "text",
+            "--content",
+            "hi",
+            "--secret",
+            "secret",
+            "--time-offset-secs",
+            "-7200",
+        ],
+    )
+    .await;
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let body = &bodies(&server).await[0];
+    let timestamp: u64 = body["timestamp"].as_str().unwrap().parse().unwrap();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    assert!(
+        (now - 7200).abs_diff(timestamp) < 60,
+        "{} vs {}",
+        timestamp,
+        now
+    );
+    assert_eq!(
+        body["sign"],
+        generate_sign(timestamp, "secret", SignVersion::HmacSha256)
+    );
+}
+
+#[tokio::test]
+async fn sign_mismatch_suggests_a_time_offset_for_a_skewed_clock() {
+    let server = MockServer::start().await;
+    let server_time = SystemTime::now() - Duration::from_secs(2 * 3600);
+    Mock::given(method("HEAD"))
+        .respond_with(
+            ResponseTemplate::new(405).insert_header("date", httpdate::fmt_http_date(server_time)),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "code": 19021, "msg": "sign match fail" })),
+        )
+        .mount(&server)
+        .await;
+    let output = run(
+        &format!("{}{}", server.uri(), HOOK),
+        &["--format", "text", "--content", "hi", "--secret", "secret"],
+    )
+    .await;
+
+    assert_eq!(output.status.code(), Some(5));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("clock is within 1 hour of Lark's servers"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("ahead of the webhook server") && stderr.contains("--time-offset-secs -"),
+        "{}",
+        stderr
+    );
+}
+
+#[tokio::test]
+async fn signs_with_a_given_timestamp() {
+    let server = server(ok()).await;
+    let url = format!("{}{}", server.uri(), HOOK);
+    let args = [
+        "--format",
+        "text",
+        "--content",
+        "hi",
+        "--secret",
+        "secret",
+        "--timestamp",
+    ];
+    let output = run(&url, &[&args[..], &["1599360473"]].concat()).await;
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Milliseconds, by mistake.
+    let output = run(&url, &[&args[..], &["1599360473000"]].concat()).await;
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--force"));
+
+    assert_eq!(
+        bodies(&server).await,
+        [json!({
+            "msg_type": "text",
+            "content": { "text": "hi" },
+            "timestamp": "1599360473",
+            "sign": "q4jswNiMy51J5JuQV566yJat0/lQ/c+22kINzUgKsGU=",
+        })]
+    );
+}
+
+const FAN_OUT_TOKENS: [&str; 2] = ["fanout-token-0123456789", "fanout-token-abcdefghij"];
+
+/// The binary sending to two webhooks on `server`, isolated like [`run`].
+fn fan_out(server: &MockServer) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_lark-notifier"));
+    command.env_clear().env("HOME", env!("CARGO_TARGET_TMPDIR"));
+    for token in FAN_OUT_TOKENS {
+        command.args([
+            "--webhook-url",
+            &format!("{}/open-apis/bot/v2/hook/{}", server.uri(), token),
+        ]);
+    }
+    command.args(["--allow-insecure-url", "--retries", "0"]);
+    command
+}
+
+/// Everything `output` printed, asserting that no webhook token is in it.
+fn assert_tokens_masked(output: &Output) -> String {
+    let printed = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        FAN_OUT_TOKENS.iter().all(|token| !printed.contains(token)),
+        "{}",
+        printed
+    );
+    printed
+}
+
+#[tokio::test]
+async fn fan_out_reports_mask_the_webhook_tokens() {
+    let server = server(ok()).await;
+    let output = fan_out(&server)
+        .args(["--format", "text", "--content", "hi"])
+        .output()
+        .await
+        .expect("binary runs");
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(assert_tokens_masked(&output).contains("Sent to 2/2 webhooks"));
+}
+
+#[tokio::test]
+async fn json_array_output_prints_one_array_for_every_webhook() {
+    let server = server(ok()).await;
+    let output = fan_out(&server)
+        .args([
+            "--format",
+            "text",
+            "--content",
+            "hi",
+            "--output",
+            "json-array",
+        ])
+        .output()
+        .await
+        .expect("binary runs");
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_tokens_masked(&output);
+    let outcomes: Vec<Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(outcomes.len(), 2);
+    assert!(
+        outcomes
+            .iter()
+            .all(|outcome| outcome["ok"] == true && outcome["http_status"] == 200),
+        "{:?}",
+        outcomes
+    );
+}
+
+#[tokio::test]
+async fn batch_and_pipe_reports_mask_the_webhook_tokens() {
+    let server = server(ResponseTemplate::new(500)).await;
+    let batch = format!("{}/fan-out-batch.jsonl", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(&batch, "{\"title\": \"Deploy\", \"content\": \"done\"}\n").unwrap();
+    let output = fan_out(&server)
+        .args(["--batch", &batch])
+        .output()
+        .await
+        .expect("binary runs");
+    assert_eq!(output.status.code(), Some(4));
+    assert_tokens_masked(&output);
+
+    let mut pipe = fan_out(&server)
+        .args(["--pipe", "--format", "text"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("binary runs");
+    pipe.stdin
+        .take()
+        .unwrap()
+        .write_all(b"line 1\n")
+        .await
+        .unwrap();
+    let output = pipe.wait_with_output().await.unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    assert_tokens_masked(&output);
+}
+
+/// Start `serve` in front of `lark` with `args`, returning it and its base URL.
+fn serve(lark: &MockServer, args: &[&str]) -> (tokio::process::Child, String) {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let child = Command::new(env!("CARGO_BIN_EXE_lark-notifier"))
+        .env_clear()
+        .env("HOME", env!("CARGO_TARGET_TMPDIR"))
+        .env("LARK_WEBHOOK_URL", format!("{}{}", lark.uri(), HOOK))
+        .args([
+            "serve",
+            "--allow-insecure-url",
+            "--listen",
+            &format!("127.0.0.1:{}", port),
+        ])
+        .args(args)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .expect("binary runs");
+    (child, format!("http://127.0.0.1:{}", port))
+}
+
+/// POST `body` to `url`, retrying while the server is still starting.
+async fn post_when_up(url: &str, body: &'static str) -> reqwest::Response {
+    let client = reqwest::Client::new();
+    for _ in 0..50 {
+        if let Ok(response) = client.post(url).body(body).send().await {
+            return response;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("serve never started at {}", url);
+}
+
+#[tokio::test]
+async fn serve_answers_slack_clients_with_ok() {
+    let lark = server(ok()).await;
+    let (_serve, base) = serve(&lark, &[]);
+    let response = post_when_up(
+        &format!("{}/slack", base),
+        r#"{"text": "*Deploy* of <https://ci.example.com/7|#7> done"}"#,
+    )
+    .await;
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "ok");
+    let bodies = bodies(&lark).await;
+    assert_eq!(bodies[0]["msg_type"], "interactive");
+    assert!(
+        bodies[0]
+            .to_string()
+            .contains("**Deploy** of [#7](https://ci.example.com/7) done"),
+        "{}",
+        bodies[0]
+    );
+}
+
+#[tokio::test]
+async fn serve_logs_with_verbose() {
+    let lark = server(ok()).await;
+    let (mut serve, base) = serve(&lark, &["--verbose"]);
+    let response = post_when_up(&base, r#"{"title": "Deploy", "content": "done"}"#).await;
+    assert_eq!(response.status(), 200);
+
+    serve.start_kill().unwrap();
+    let output = serve.wait_with_output().await.unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Webhook responded with 200"), "{}", stderr);
+    assert!(!stderr.contains("test-token"), "{}", stderr);
+}
+
+#[tokio::test]
+async fn debug_logs_and_errors_mask_the_webhook_token() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ok().set_delay(Duration::from_secs(5)))
+        .mount(&server)
+        .await;
+    let token = "debug-token-0123456789";
+    let dir = format!("{}/debug-queue", env!("CARGO_TARGET_TMPDIR"));
+    let output = Command::new(env!("CARGO_BIN_EXE_lark-notifier"))
+        .env_clear()
+        .env("HOME", env!("CARGO_TARGET_TMPDIR"))
+        .env("RUST_LOG", "debug")
+        .args([
+            "--webhook-url",
+            &format!("{}/open-apis/bot/v2/hook/{}", server.uri(), token),
+            "--allow-insecure-url",
+        ])
+        .args([
+            "--retries",
+            "1",
+            "--retry-base-ms",
+            "1",
+            "--timeout",
+            "1",
+            "--queue-dir",
+            &dir,
+        ])
+        .args(["--format", "text", "--content", "hi", "--secret", "secret"])
+        .output()
+        .await
+        .expect("binary runs");
+
+    assert_eq!(output.status.code(), Some(124));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("timed out") && stderr.contains("Queued message for"),
+        "{}",
+        stderr
+    );
+    assert!(
+        !stderr.contains(token) && !String::from_utf8_lossy(&output.stdout).contains(token),
+        "{}",
+        stderr
+    );
+}
+
+#[tokio::test]
+async fn slow_response_exits_timeout() {
+    let server = server(ok().set_delay(Duration::from_secs(5))).await;
+    let started = std::time::Instant::now();
+    let output = run(
+        &format!("{}{}", server.uri(), HOOK),
+        &["--format", "text", "--content", "hi", "--timeout-secs", "1"],
+    )
+    .await;
+
+    assert_eq!(output.status.code(), Some(124));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("timed out after 1s"),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(started.elapsed() < Duration::from_secs(4));
+}
+
+#[tokio::test]
+async fn stalled_connection_exits_timeout() {
+    // Accepted by the kernel but never answered, so the TLS handshake hangs
+    // and the connect timeout, not the longer request timeout, fires.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("https://{}{}", listener.local_addr().unwrap(), HOOK);
+    let output = run(
+        &url,
+        &[
+            "--format",
+            "text",
+            "--content",
+            "hi",
+            "--connect-timeout",
+            "1",
+            "--timeout-secs",
+            "30",
+        ],
+    )
+    .await;
+    drop(listener);
+
+    assert_eq!(output.status.code(), Some(124));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("timed out after 1s"),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[tokio::test]
+async fn oversized_message_fails_by_default() {
+    let server = server(ok()).await;
+    let url = format!("{}{}", server.uri(), HOOK);
+    let content = "a long log line\n".repeat(100);
+    let args = [
+        "--title",
+        "CI log",
+        "--content",
+        &content,
+        "--max-bytes",
+        "1024",
+    ];
+    let output = run(&url, &args).await;
+
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("bytes, over the limit of 1024 bytes"),
+        "{}",
+        stderr
+    );
+    assert!(bodies(&server).await.is_empty());
+
+    let output = run(&url, &[&args[..], &["--on-oversize", "split"]].concat()).await;
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(bodies(&server).await.len() > 1);
+}
+
+#[tokio::test]
+async fn oversized_post_is_sent_in_numbered_parts() {
+    let server = server(ok()).await;
+    let content: String = (0..60)
+        .map(|i| format!("line {} failed with an error\n", i))
+        .collect();
+    let args = [
+        "--title",
+        "CI log",
+        "--content",
+        &content,
+        "--keyword",
+        "error",
+        "--rate-limit",
+        "0",
+    ];
+    let split = ["--max-bytes", "1024", "--on-oversize", "split"];
+    let output = run(
+        &format!("{}{}", server.uri(), HOOK),
+        &[&args[..], &split].concat(),
+    )
+    .await;
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let bodies = bodies(&server).await;
+    assert!(bodies.len() > 1);
+    let mut lines = Vec::new();
+    for (i, body) in bodies.iter().enumerate() {
+        assert!(body.to_string().len() <= 1024, "{}", body);
+        let post = &body["content"]["post"]["zh_cn"];
+        assert_eq!(
+            post["title"],
+            format!("CI log ({}/{})", i + 1, bodies.len())
+        );
+        for row in post["content"].as_array().unwrap() {
+            let row = row.as_array().unwrap();
+            // Every keyword stays one bold element.
+            assert!(
+                row.iter()
+                    .any(|element| element["text"] == "error" && element["style"][0] == "bold"),
+                "{:?}",
+                row
+            );
+            lines.push(
+                row.iter()
+                    .map(|element| element["text"].as_str().unwrap())
+                    .collect::<String>(),
+            );
+        }
+    }
+    // Sent in order, split only between lines.
+    assert_eq!(lines, content.lines().collect::<Vec<_>>());
+}
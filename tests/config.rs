@@ -0,0 +1,108 @@
+//! [`Config`] files: named profiles, partial fields and unknown profile names.
+
+use std::path::PathBuf;
+
+use lark_notifier::{Config, NotifierError};
+
+const SAMPLE: &str = r#"
+[profiles.prod]
+webhook_url = "https://open.larksuite.com/open-apis/bot/v2/hook/prod-token"
+secret = "prod-secret"
+keywords = ["error", "timeout"]
+title_prefix = "[prod] "
+
+[profiles.prod.levels.critical]
+emoji = "🚨"
+color = "carmine"
+
+[profiles.dev]
+webhook_url = "https://open.feishu.cn/open-apis/bot/v2/hook/dev-token"
+
+[profiles.quiet]
+"#;
+
+/// `text` written to a config file named `name` in the test scratch directory.
+fn write(name: &str, text: &str) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::write(&path, text).unwrap();
+    path
+}
+
+#[test]
+fn parses_every_profile() {
+    let config = Config::load(&write("sample.toml", SAMPLE)).unwrap();
+    assert_eq!(
+        config.profiles.keys().collect::<Vec<_>>(),
+        ["dev", "prod", "quiet"]
+    );
+
+    let prod = config.profile("prod").unwrap();
+    assert_eq!(
+        prod.webhook_url.as_deref(),
+        Some("https://open.larksuite.com/open-apis/bot/v2/hook/prod-token")
+    );
+    assert_eq!(prod.secret.as_deref(), Some("prod-secret"));
+    assert_eq!(
+        prod.keywords.as_deref(),
+        Some(&["error".to_string(), "timeout".to_string()][..])
+    );
+    assert_eq!(prod.title_prefix.as_deref(), Some("[prod] "));
+    let critical = &prod.levels["critical"];
+    assert_eq!(
+        (critical.emoji.as_deref(), critical.color.as_deref()),
+        (Some("🚨"), Some("carmine"))
+    );
+}
+
+#[test]
+fn partial_profiles_leave_the_rest_unset() {
+    let config = Config::load(&write("partial.toml", SAMPLE)).unwrap();
+
+    let dev = config.profile("dev").unwrap();
+    assert!(dev.webhook_url.is_some());
+    assert!(dev.secret.is_none() && dev.keywords.is_none() && dev.title_prefix.is_none());
+    assert!(dev.levels.is_empty());
+
+    let quiet = config.profile("quiet").unwrap();
+    assert!(quiet.webhook_url.is_none());
+}
+
+#[test]
+fn missing_profile_lists_the_available_ones() {
+    let config = Config::load(&write("listed.toml", SAMPLE)).unwrap();
+    let err = config.profile("staging").unwrap_err();
+    assert!(matches!(err, NotifierError::Config(_)), "{:?}", err);
+    assert_eq!(
+        err.to_string(),
+        "Profile 'staging' not found in config (available: dev, prod, quiet)"
+    );
+
+    let empty = Config::load(&write("empty.toml", "")).unwrap();
+    assert!(
+        empty
+            .profile("prod")
+            .unwrap_err()
+            .to_string()
+            .contains("(available: none)")
+    );
+}
+
+#[test]
+fn unreadable_and_malformed_files_name_the_path() {
+    let path = write("malformed.toml", "[profiles.prod\nwebhook_url = 1");
+    let err = Config::load(&path).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains(&format!("Failed to parse config file {}", path.display())),
+        "{}",
+        err
+    );
+
+    let missing = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("no-such-config.toml");
+    assert!(
+        Config::load(&missing)
+            .unwrap_err()
+            .to_string()
+            .contains("Failed to read config file")
+    );
+}
@@ -0,0 +1,285 @@
+//! [`process_content_with_keywords`], the highlighting behind post and card messages.
+
+use lark_notifier::{Highlighter, LarkTextContent, parse_keyword, process_content_with_keywords};
+
+/// `elements` written out, with bold text as `**text**` and links as `[text](href)`.
+fn render(elements: &[LarkTextContent]) -> Vec<String> {
+    elements
+        .iter()
+        .map(|element| match (&element.href, element.has_style("bold")) {
+            (Some(href), _) => format!("[{}]({})", element.text, href),
+            (None, true) => format!("**{}**", element.text),
+            (None, false) => element.text.clone(),
+        })
+        .collect()
+}
+
+fn highlight(content: &str, keywords: &[&str]) -> Vec<String> {
+    let keywords: Vec<String> = keywords.iter().map(|keyword| keyword.to_string()).collect();
+    render(&process_content_with_keywords(content, &keywords))
+}
+
+fn highlight_ignoring_case(content: &str, keywords: &[&str]) -> Vec<String> {
+    render(
+        &Highlighter::parse(keywords)
+            .ignore_case(true)
+            .highlight(content),
+    )
+}
+
+#[test]
+fn no_keywords() {
+    assert_eq!(highlight("deploy finished", &[]), ["deploy finished"]);
+    assert_eq!(highlight("deploy finished", &[""]), ["deploy finished"]);
+}
+
+#[test]
+fn keyword_at_start_middle_and_end() {
+    assert_eq!(
+        highlight("prod is down", &["prod"]),
+        ["**prod**", " is down"]
+    );
+    assert_eq!(
+        highlight("deploy to prod now", &["prod"]),
+        ["deploy to ", "**prod**", " now"]
+    );
+    assert_eq!(
+        highlight("deploy to prod", &["prod"]),
+        ["deploy to ", "**prod**"]
+    );
+}
+
+#[test]
+fn repeated_keywords() {
+    assert_eq!(
+        highlight("error, error and another error", &["error"]),
+        ["**error**", ", ", "**error**", " and another ", "**error**"]
+    );
+    assert_eq!(
+        highlight("errorerror", &["error"]),
+        ["**error**", "**error**"]
+    );
+}
+
+#[test]
+fn several_keywords_in_content_order() {
+    // Order in the keyword list does not matter, only position in the content.
+    assert_eq!(
+        highlight("db-1 failed over to db-2", &["db-2", "db-1"]),
+        ["**db-1**", " failed over to ", "**db-2**"]
+    );
+}
+
+#[test]
+fn overlapping_keywords() {
+    // The earliest match wins, then the longest.
+    assert_eq!(
+        highlight("prod-eu is down", &["prod", "prod-eu"]),
+        ["**prod-eu**", " is down"]
+    );
+    assert_eq!(
+        highlight("prod-eu is down", &["eu is", "prod"]),
+        ["**prod**", "-", "**eu is**", " down"]
+    );
+    assert_eq!(highlight("abcd", &["bcd", "abc"]), ["**abc**", "d"]);
+}
+
+#[test]
+fn content_equal_to_a_keyword() {
+    assert_eq!(highlight("prod", &["prod"]), ["**prod**"]);
+}
+
+#[test]
+fn linked_keywords() {
+    assert_eq!(
+        highlight("see runbook", &["runbook=https://example.com/runbook?a=1"]),
+        ["see ", "[runbook](https://example.com/runbook?a=1)"]
+    );
+}
+
+#[test]
+fn empty_content() {
+    assert!(highlight("", &["prod"]).concat().is_empty());
+}
+
+#[test]
+fn segments_rebuild_the_content_whatever_the_keyword_order() {
+    let input = "alpha failed, then beta, then gamma; alpha again";
+    // Listed in reverse order of appearance.
+    let keywords = ["gamma".to_string(), "beta".to_string(), "alpha".to_string()];
+    let result = process_content_with_keywords(input, &keywords);
+
+    assert_eq!(
+        result.iter().map(|c| c.text.as_str()).collect::<String>(),
+        input
+    );
+    assert_eq!(
+        render(&result),
+        [
+            "**alpha**",
+            " failed, then ",
+            "**beta**",
+            ", then ",
+            "**gamma**",
+            "; ",
+            "**alpha**",
+            " again"
+        ]
+    );
+}
+
+#[test]
+fn ignoring_case_compares_unicode_case_folds() {
+    assert_eq!(
+        highlight_ignoring_case("Straße closed", &["STRASSE"]),
+        ["**Straße**", " closed"]
+    );
+    assert_eq!(
+        highlight_ignoring_case("STRASSE closed", &["Straße"]),
+        ["**STRASSE**", " closed"]
+    );
+    // "ß" folds to "ss", so "s" alone would end inside it and does not match there.
+    assert_eq!(highlight_ignoring_case("ß", &["s"]), ["ß"]);
+}
+
+#[test]
+fn ignoring_case_highlights_every_mixed_case_occurrence() {
+    let input = "Error: disk ERROR, then error again, ÉCHEC and échec";
+    assert_eq!(
+        highlight_ignoring_case(input, &["error", "Échec"]),
+        [
+            "**Error**",
+            ": disk ",
+            "**ERROR**",
+            ", then ",
+            "**error**",
+            " again, ",
+            "**ÉCHEC**",
+            " and ",
+            "**échec**"
+        ]
+    );
+    let result = Highlighter::parse(&["ERROR"])
+        .ignore_case(true)
+        .highlight(input);
+    assert_eq!(
+        result.iter().map(|c| c.text.as_str()).collect::<String>(),
+        input
+    );
+}
+
+#[test]
+fn ignoring_case_skips_keywords_that_do_not_occur() {
+    let input = "Deploy FAILED on Prod";
+    assert_eq!(
+        highlight_ignoring_case(input, &["missing", "failed", "PROD", "nowhere"]),
+        ["Deploy ", "**FAILED**", " on ", "**Prod**"]
+    );
+    // Case-sensitive matching leaves the same content alone.
+    assert_eq!(highlight(input, &["failed", "PROD"]), [input]);
+}
+
+#[test]
+fn one_highlight_per_occurrence() {
+    let count = |content: &str, keywords: &[&str]| {
+        let keywords: Vec<String> = keywords.iter().map(|keyword| keyword.to_string()).collect();
+        process_content_with_keywords(content, &keywords)
+            .iter()
+            .filter(|element| element.has_style("bold"))
+            .count()
+    };
+    assert_eq!(count("timeout ... timeout ... timeout", &["timeout"]), 3);
+    // Adjacent matches, and a keyword overlapping its own next occurrence.
+    assert_eq!(count("timeouttimeouttimeout", &["timeout"]), 3);
+    assert_eq!(count("aaaa", &["aa"]), 2);
+    assert_eq!(
+        count("timeout, retry, timeout, retry", &["retry", "timeout"]),
+        4
+    );
+}
+
+fn highlight_with_patterns(content: &str, keywords: &[&str], patterns: &[&str]) -> Vec<String> {
+    let patterns = patterns
+        .iter()
+        .map(|pattern| pattern.parse().unwrap())
+        .collect();
+    render(
+        &Highlighter::parse(keywords)
+            .patterns(patterns)
+            .highlight(content),
+    )
+}
+
+#[test]
+fn overlapping_regex_and_plain_keywords() {
+    // The earliest match wins, whichever kind it is.
+    assert_eq!(
+        highlight_with_patterns("see JIRA-42 now", &["42 now"], &[r"JIRA-\d+"]),
+        ["see ", "**JIRA-42**", " now"]
+    );
+    assert_eq!(
+        highlight_with_patterns("JIRA-42", &["IRA"], &[r"JIRA-\d+"]),
+        ["**JIRA-42**"]
+    );
+    // At the same start the longest wins, then plain keywords before patterns.
+    assert_eq!(
+        highlight_with_patterns("JIRA-42 done", &["JIRA"], &[r"JIRA-\d+"]),
+        ["**JIRA-42**", " done"]
+    );
+    assert_eq!(
+        highlight_with_patterns(
+            "JIRA-42",
+            &["JIRA-42=https://example.com/kw"],
+            &[r"JIRA-\d+"]
+        ),
+        ["[JIRA-42](https://example.com/kw)"]
+    );
+    // A pattern match inside a keyword is not highlighted separately.
+    assert_eq!(
+        highlight_with_patterns(
+            "JIRA-1 blocks JIRA-2",
+            &["blocks JIRA"],
+            &[r"JIRA-\d+=https://jira.example.com/browse/$0"]
+        ),
+        [
+            "[JIRA-1](https://jira.example.com/browse/JIRA-1)",
+            " ",
+            "**blocks JIRA**",
+            "-2"
+        ]
+    );
+}
+
+#[test]
+fn keyword_specs() {
+    assert_eq!(parse_keyword("error"), ("error", None));
+    assert_eq!(
+        parse_keyword("error=https://wiki/err"),
+        ("error", Some("https://wiki/err"))
+    );
+    assert_eq!(
+        parse_keyword(" error = https://wiki/err "),
+        ("error", Some("https://wiki/err"))
+    );
+    assert_eq!(
+        parse_keyword("error=https://wiki/search?q=err&p=1"),
+        ("error", Some("https://wiki/search?q=err&p=1"))
+    );
+    assert_eq!(parse_keyword("a=b="), ("a", Some("b=")));
+}
+
+#[test]
+fn keyword_specs_without_a_link() {
+    // Nothing after the `=`: the whole spec is a plain keyword.
+    assert_eq!(parse_keyword("error="), ("error=", None));
+    assert_eq!(parse_keyword(""), ("", None));
+    // Nothing before it: a link for no keyword, which highlights nothing.
+    assert_eq!(
+        parse_keyword("=https://wiki/err"),
+        ("", Some("https://wiki/err"))
+    );
+    assert_eq!(
+        highlight("error at =https://wiki/err", &["=https://wiki/err"]),
+        ["error at =https://wiki/err"]
+    );
+}
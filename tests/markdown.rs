@@ -0,0 +1,63 @@
+//! [`markdown_to_rows`], the `--markdown` rendering of release notes and CI summaries.
+
+use lark_notifier::{Highlighter, LarkTextContent, markdown_to_rows};
+
+/// Each row written out, with bold text as `**text**` and links as `[text](href)`.
+fn rows(markdown: &str) -> Vec<String> {
+    let render = |element: &LarkTextContent| match (&element.href, element.has_style("bold")) {
+        (Some(href), _) => format!("[{}]({})", element.text, href),
+        (None, true) => format!("**{}**", element.text),
+        (None, false) => element.text.clone(),
+    };
+    markdown_to_rows(markdown, &Highlighter::new(Vec::new()))
+        .iter()
+        .map(|row| row.iter().map(render).collect())
+        .collect()
+}
+
+#[test]
+fn nested_lists_are_indented_by_depth() {
+    let notes = "- Features\n  - Markdown input\n    1. links\n    2. lists\n- Fixes\n";
+    assert_eq!(
+        rows(notes),
+        [
+            "• Features",
+            "  • Markdown input",
+            "    1. links",
+            "    2. lists",
+            "• Fixes"
+        ]
+    );
+}
+
+#[test]
+fn ordered_lists_keep_their_start() {
+    assert_eq!(rows("3. three\n4. four\n"), ["3. three", "4. four"]);
+}
+
+#[test]
+fn links_keep_their_href_and_drop_their_title() {
+    let notes =
+        "See [the changelog](https://example.com/CHANGELOG.md \"Full changelog\") for more.";
+    assert_eq!(
+        rows(notes),
+        ["See [the changelog](https://example.com/CHANGELOG.md) for more."]
+    );
+}
+
+#[test]
+fn links_inside_nested_list_items() {
+    let notes = "- Docs\n  - [API](https://example.com/api 'API reference') updated\n";
+    assert_eq!(
+        rows(notes),
+        ["• Docs", "  • [API](https://example.com/api) updated"]
+    );
+}
+
+#[test]
+fn headings_and_strong_text_are_bold() {
+    assert_eq!(
+        rows("# Release 1.2\nShips **today**."),
+        ["**Release 1.2**", "Ships **today**."]
+    );
+}
@@ -0,0 +1,76 @@
+//! The message types' JSON, serialized and parsed back as Lark documents it.
+
+use lark_notifier::{LarkContent, LarkMessage, LarkPost, LarkPostContent, LarkTextContent, Locale};
+use serde_json::{Value, json};
+
+/// `message` serialized, parsed back into a [`LarkMessage`] and serialized again.
+fn round_trip(message: &LarkMessage) -> (Value, Value) {
+    let json = serde_json::to_value(message).unwrap();
+    let parsed: LarkMessage = serde_json::from_value(json.clone()).unwrap();
+    (json, serde_json::to_value(&parsed).unwrap())
+}
+
+#[test]
+fn image_message_round_trips() {
+    let (json, again) = round_trip(&LarkMessage::image(
+        "img_ecffc3b9-8f14-400f-a014-05eca1a4310g",
+    ));
+    assert_eq!(
+        json,
+        json!({ "msg_type": "image", "content": { "image_key": "img_ecffc3b9-8f14-400f-a014-05eca1a4310g" } })
+    );
+    assert_eq!(again, json);
+}
+
+#[test]
+fn signed_image_message_from_the_docs_parses() {
+    let documented = json!({
+        "timestamp": "1599360473",
+        "sign": "q4jswNiMy51J5JuQV566yJat0/lQ/c+22kINzUgKsGU=",
+        "msg_type": "image",
+        "content": { "image_key": "img_ecffc3b9-8f14-400f-a014-05eca1a4310g" },
+    });
+    let message: LarkMessage = serde_json::from_value(documented.clone()).unwrap();
+    assert!(
+        matches!(&message.content, Some(LarkContent::Image { image_key }) if image_key.starts_with("img_"))
+    );
+    assert_eq!(message.timestamp.as_deref(), Some("1599360473"));
+    assert!(message.extra.is_empty());
+    assert_eq!(round_trip(&message), (documented.clone(), documented));
+}
+
+fn post_content(title: &str) -> LarkPostContent {
+    LarkPostContent {
+        title: title.to_string(),
+        content: vec![vec![LarkTextContent::text("done")]],
+    }
+}
+
+#[test]
+fn absent_locales_are_omitted() {
+    let zh_cn = LarkMessage::post("部署", vec![vec![LarkTextContent::text("done")]]);
+    let post = &serde_json::to_value(&zh_cn).unwrap()["content"]["post"];
+    assert_eq!(
+        post.as_object().unwrap().keys().collect::<Vec<_>>(),
+        ["zh_cn"]
+    );
+
+    let mut en_us = LarkPost::default();
+    en_us.set(Locale::EnUs, post_content("Deploy"));
+    assert_eq!(
+        serde_json::to_value(&en_us).unwrap(),
+        json!({ "en_us": { "title": "Deploy", "content": [[{ "tag": "text", "text": "done" }]] } })
+    );
+
+    let mut both = en_us.clone();
+    both.set(Locale::ZhCn, post_content("部署"));
+    let both = serde_json::to_value(&both).unwrap();
+    assert_eq!(
+        both.as_object().unwrap().keys().collect::<Vec<_>>(),
+        ["en_us", "zh_cn"]
+    );
+    assert_eq!(
+        serde_json::to_value(LarkPost::default()).unwrap(),
+        json!({})
+    );
+}
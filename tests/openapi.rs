@@ -0,0 +1,341 @@
+//! Image uploads and chat messages through [`AppClient`] against a mock open API.
+
+use std::time::Duration;
+
+use lark_notifier::{
+    AppClient, INVALID_ACCESS_TOKEN, InteractiveCard, LarkMessage, LarkTextContent,
+    MAX_IMAGE_BYTES, NotifierError, Region, RetryPolicy,
+};
+use serde_json::{Value, json};
+use wiremock::matchers::{body_json, header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const TOKEN_PATH: &str = "/open-apis/auth/v3/tenant_access_token/internal";
+const IMAGES_PATH: &str = "/open-apis/im/v1/images";
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+async fn mount_token(server: &MockServer, expected_fetches: u64) {
+    Mock::given(method("POST"))
+        .and(path(TOKEN_PATH))
+        .and(body_json(
+            json!({ "app_id": "cli_test", "app_secret": "app-secret" }),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "code": 0,
+            "msg": "ok",
+            "tenant_access_token": "t-test",
+            "expire": 7200,
+        })))
+        .expect(expected_fetches)
+        .mount(server)
+        .await;
+}
+
+fn app(server: &MockServer) -> AppClient {
+    AppClient::new(
+        reqwest::Client::new(),
+        Region::Intl,
+        "cli_test",
+        "app-secret",
+    )
+    .with_base_url(server.uri())
+}
+
+#[tokio::test]
+async fn uploads_with_a_cached_token() {
+    let server = MockServer::start().await;
+    mount_token(&server, 1).await;
+    Mock::given(method("POST"))
+        .and(path(IMAGES_PATH))
+        .and(header("authorization", "Bearer t-test"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "code": 0,
+            "msg": "success",
+            "data": { "image_key": "img_v2_test" },
+        })))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let app = app(&server);
+    assert_eq!(
+        app.upload_image("a.png", PNG.to_vec()).await.unwrap(),
+        "img_v2_test"
+    );
+    assert_eq!(
+        app.upload_image("b.png", PNG.to_vec()).await.unwrap(),
+        "img_v2_test"
+    );
+
+    let requests = server.received_requests().await.unwrap();
+    let upload = String::from_utf8_lossy(&requests[1].body);
+    assert!(
+        upload.contains("name=\"image_type\"\r\n\r\nmessage"),
+        "{}",
+        upload
+    );
+    assert!(upload.contains("filename=\"a.png\""), "{}", upload);
+}
+
+#[tokio::test]
+async fn token_error_is_reported() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(TOKEN_PATH))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "code": 10014, "msg": "app secret invalid" })),
+        )
+        .mount(&server)
+        .await;
+
+    let err = app(&server)
+        .upload_image("a.png", PNG.to_vec())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, NotifierError::LarkApi { code: 10014, .. }),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn upload_error_is_reported() {
+    let server = MockServer::start().await;
+    mount_token(&server, 1).await;
+    Mock::given(method("POST"))
+        .and(path(IMAGES_PATH))
+        .respond_with(
+            ResponseTemplate::new(400)
+                .set_body_json(json!({ "code": 234001, "msg": "Invalid request param." })),
+        )
+        .mount(&server)
+        .await;
+
+    let err = app(&server)
+        .upload_image("a.png", PNG.to_vec())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, NotifierError::LarkApi { code: 234001, .. }),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn rejects_files_before_uploading() {
+    let server = MockServer::start().await;
+    let app = app(&server);
+
+    let err = app
+        .upload_image("notes.txt", b"hello".to_vec())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, NotifierError::InvalidImage { .. }),
+        "{:?}",
+        err
+    );
+
+    let mut huge = PNG.to_vec();
+    huge.resize(MAX_IMAGE_BYTES + 1, 0);
+    let err = app.upload_image("huge.png", huge).await.unwrap_err();
+    assert!(err.to_string().contains("over the upload limit"), "{}", err);
+
+    assert!(server.received_requests().await.unwrap().is_empty());
+}
+
+const MESSAGES_PATH: &str = "/open-apis/im/v1/messages";
+
+fn sent() -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "code": 0,
+        "msg": "success",
+        "data": { "message_id": "om_test", "chat_id": "oc_test" },
+    }))
+}
+
+#[tokio::test]
+async fn sends_to_a_chat() {
+    let server = MockServer::start().await;
+    mount_token(&server, 1).await;
+    Mock::given(method("POST"))
+        .and(path(MESSAGES_PATH))
+        .and(query_param("receive_id_type", "chat_id"))
+        .and(header("authorization", "Bearer t-test"))
+        .respond_with(sent())
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let app = app(&server);
+    let post = LarkMessage::post("Deploy", vec![vec![LarkTextContent::text("done")]]);
+    assert_eq!(
+        app.send_to_chat("oc_test", &post)
+            .await
+            .unwrap()
+            .message_id(),
+        Some("om_test")
+    );
+    let card = LarkMessage::card(InteractiveCard::new("Deploy", "green", "**done**"));
+    app.send_to_chat("oc_test", &card).await.unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let bodies: Vec<Value> = requests[1..]
+        .iter()
+        .map(|request| request.body_json().unwrap())
+        .collect();
+    assert_eq!(
+        bodies[0],
+        json!({
+            "receive_id": "oc_test",
+            "msg_type": "post",
+            "content": r#"{"zh_cn":{"title":"Deploy","content":[[{"tag":"text","text":"done"}]]}}"#,
+        })
+    );
+    assert_eq!(bodies[1]["msg_type"], "interactive");
+    let card: Value = serde_json::from_str(bodies[1]["content"].as_str().unwrap()).unwrap();
+    assert_eq!(card["header"]["template"], "green");
+}
+
+#[tokio::test]
+async fn bot_not_in_chat_is_explained() {
+    let server = MockServer::start().await;
+    mount_token(&server, 1).await;
+    Mock::given(method("POST"))
+        .and(path(MESSAGES_PATH))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "code": 230002,
+            "msg": "Bot/User can NOT be out of the chat.",
+        })))
+        .mount(&server)
+        .await;
+
+    let err = app(&server)
+        .send_to_chat("oc_test", &LarkMessage::text("hi"))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, NotifierError::LarkApi { code: 230002, .. }),
+        "{:?}",
+        err
+    );
+    assert!(err.to_string().contains("add it to the group"), "{}", err);
+}
+
+#[tokio::test]
+async fn replaces_a_rejected_token_once() {
+    let server = MockServer::start().await;
+    mount_token(&server, 2).await;
+    Mock::given(method("POST"))
+        .and(path(MESSAGES_PATH))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "code": INVALID_ACCESS_TOKEN,
+            "msg": "Invalid access token for authorization.",
+        })))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(MESSAGES_PATH))
+        .respond_with(sent())
+        .mount(&server)
+        .await;
+
+    let response = app(&server)
+        .send_to_chat("oc_test", &LarkMessage::text("hi"))
+        .await
+        .unwrap();
+    assert_eq!(response.message_id(), Some("om_test"));
+}
+
+#[tokio::test]
+async fn retries_server_errors() {
+    let server = MockServer::start().await;
+    mount_token(&server, 1).await;
+    Mock::given(method("POST"))
+        .and(path(MESSAGES_PATH))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(MESSAGES_PATH))
+        .respond_with(sent())
+        .mount(&server)
+        .await;
+
+    let app = app(&server).with_retry_policy(RetryPolicy::new(1, Duration::from_millis(1)));
+    let response = app
+        .send_to_chat("oc_test", &LarkMessage::text("hi"))
+        .await
+        .unwrap();
+    assert_eq!(response.attempts, 2);
+}
+
+#[tokio::test]
+async fn uses_a_given_token_as_is() {
+    let server = MockServer::start().await;
+    mount_token(&server, 0).await;
+    Mock::given(method("POST"))
+        .and(path(MESSAGES_PATH))
+        .and(header("authorization", "Bearer t-given"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "code": INVALID_ACCESS_TOKEN,
+            "msg": "Invalid access token for authorization.",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let app = AppClient::with_tenant_access_token(reqwest::Client::new(), Region::Cn, "t-given")
+        .with_base_url(server.uri());
+    let err = app
+        .send_to_chat("oc_test", &LarkMessage::text("hi"))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            NotifierError::LarkApi {
+                code: INVALID_ACCESS_TOKEN,
+                ..
+            }
+        ),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn mobiles_match_only_the_same_number() {
+    let server = MockServer::start().await;
+    mount_token(&server, 1).await;
+    Mock::given(method("POST"))
+        .and(path("/open-apis/contact/v3/users/batch_get_id"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "code": 0,
+            "msg": "success",
+            "data": { "user_list": [
+                { "mobile": "13800000000", "user_id": "ou_national" },
+                { "mobile": "+8613900000000", "user_id": "ou_international" },
+                { "mobile": "0000", "user_id": "ou_short" },
+            ] },
+        })))
+        .mount(&server)
+        .await;
+
+    let mobiles = ["+8613800000000", "13900000000", "+8613700000000", "00000"].map(String::from);
+    let open_ids = app(&server).open_ids_by_mobile(&mobiles).await.unwrap();
+    assert_eq!(
+        open_ids,
+        [
+            Some("ou_national".to_string()),
+            Some("ou_international".to_string()),
+            None,
+            None
+        ]
+    );
+}
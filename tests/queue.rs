@@ -0,0 +1,210 @@
+//! [`Queue`]: spooling undelivered messages and flushing them later against a mock webhook.
+
+use std::fs::File;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use lark_notifier::{LarkMessage, LarkNotifier, NotifierError, Queue, RetryPolicy};
+use serde_json::{Value, json};
+use wiremock::matchers::method;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const HOOK: &str = "/open-apis/bot/v2/hook/test-token";
+
+/// An empty queue directory of its own for each test.
+fn queue(name: &str) -> Queue {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+        .join("queue")
+        .join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    Queue::new(dir)
+}
+
+fn notifier(server: &MockServer) -> LarkNotifier {
+    LarkNotifier::builder()
+        .webhook_url(format!("{}{}", server.uri(), HOOK))
+        .secret("secret")
+        .retry_policy(RetryPolicy::new(0, Duration::ZERO))
+        .allow_insecure_url(true)
+        .build()
+        .expect("valid notifier")
+}
+
+fn ok() -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({ "code": 0, "msg": "success", "data": {} }))
+}
+
+async fn bodies(server: &MockServer) -> Vec<Value> {
+    let requests = server
+        .received_requests()
+        .await
+        .expect("request recording is enabled");
+    requests
+        .iter()
+        .map(|request| request.body_json().expect("JSON body"))
+        .collect()
+}
+
+#[tokio::test]
+async fn flushes_in_order_with_fresh_signatures() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ok())
+        .mount(&server)
+        .await;
+    let notifier = notifier(&server);
+    let queue = queue("in-order");
+
+    // A message that was signed before it failed is queued without its signature.
+    let signed = notifier.prepare(LarkMessage::text("first")).unwrap();
+    queue.push(notifier.webhook_url(), &signed).unwrap();
+    queue
+        .push(notifier.webhook_url(), &LarkMessage::text("second"))
+        .unwrap();
+    let queued = queue.messages().unwrap();
+    assert_eq!(queued.len(), 2);
+    assert!(queued[0].message.sign.is_none() && queued[0].message.timestamp.is_none());
+
+    let report = queue.flush(&[notifier]).await.unwrap();
+    assert_eq!(
+        (report.flushed, report.expired, report.remaining),
+        (2, 0, 0)
+    );
+    let bodies = bodies(&server).await;
+    assert_eq!(bodies[0]["content"]["text"], "first");
+    assert_eq!(bodies[1]["content"]["text"], "second");
+    assert!(
+        bodies.iter().all(|body| body["sign"].is_string()),
+        "{:?}",
+        bodies
+    );
+    assert!(queue.messages().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn unreachable_webhook_keeps_its_messages() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let notifier = notifier(&server);
+    let queue = queue("stalled");
+    queue
+        .push(notifier.webhook_url(), &LarkMessage::text("first"))
+        .unwrap();
+    queue
+        .push(notifier.webhook_url(), &LarkMessage::text("second"))
+        .unwrap();
+
+    // The second message is not tried once the first fails.
+    let report = queue.flush(&[notifier]).await.unwrap();
+    assert_eq!(
+        (report.flushed, report.remaining, report.stalled.len()),
+        (0, 2, 1)
+    );
+    assert!(report.stalled[0].1.is_transient());
+    assert_eq!(queue.messages().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn rejected_messages_are_dropped() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "code": 9499, "msg": "Bad Request" })),
+        )
+        .mount(&server)
+        .await;
+    let notifier = notifier(&server);
+    let queue = queue("rejected");
+    queue
+        .push(notifier.webhook_url(), &LarkMessage::text("hi"))
+        .unwrap();
+
+    let report = queue.flush(&[notifier]).await.unwrap();
+    assert_eq!(report.rejected.len(), 1);
+    assert!(matches!(
+        report.rejected[0].1,
+        NotifierError::LarkApi { code: 9499, .. }
+    ));
+    assert!(queue.messages().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn messages_for_other_webhooks_stay_queued() {
+    let server = MockServer::start().await;
+    let queue = queue("other-webhooks");
+    queue
+        .push(
+            "https://open.feishu.cn/open-apis/bot/v2/hook/other",
+            &LarkMessage::text("hi"),
+        )
+        .unwrap();
+
+    let report = queue.flush(&[notifier(&server)]).await.unwrap();
+    assert_eq!((report.flushed, report.remaining), (0, 1));
+    assert!(bodies(&server).await.is_empty());
+}
+
+#[test]
+fn oldest_messages_beyond_the_max_count_are_dropped() {
+    let queue = queue("max-count").with_max_count(2);
+    let url = "https://open.feishu.cn/open-apis/bot/v2/hook/x";
+    assert_eq!(queue.push(url, &LarkMessage::text("1")).unwrap(), 0);
+    assert_eq!(queue.push(url, &LarkMessage::text("2")).unwrap(), 0);
+    assert_eq!(queue.push(url, &LarkMessage::text("3")).unwrap(), 1);
+
+    let texts: Vec<String> = queue
+        .messages()
+        .unwrap()
+        .iter()
+        .map(|queued| {
+            serde_json::to_value(&queued.message).unwrap()["content"]["text"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(texts, ["2", "3"]);
+}
+
+#[tokio::test]
+async fn old_messages_expire_unsent() {
+    let server = MockServer::start().await;
+    let notifier = notifier(&server);
+    let queue = queue("max-age");
+    queue
+        .push(notifier.webhook_url(), &LarkMessage::text("hi"))
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(20));
+
+    let report = queue
+        .with_max_age(Duration::from_millis(10))
+        .flush(&[notifier])
+        .await
+        .unwrap();
+    assert_eq!((report.flushed, report.expired), (0, 1));
+    assert!(bodies(&server).await.is_empty());
+}
+
+#[tokio::test]
+async fn one_flush_at_a_time() {
+    let server = MockServer::start().await;
+    let notifier = notifier(&server);
+    let queue = queue("busy");
+    queue
+        .push(notifier.webhook_url(), &LarkMessage::text("hi"))
+        .unwrap();
+
+    // As another process flushing the queue would.
+    let lock = File::create(queue.dir().join(".lock")).unwrap();
+    lock.lock().unwrap();
+    let report = queue.flush(std::slice::from_ref(&notifier)).await.unwrap();
+    assert!(report.busy);
+    assert!(bodies(&server).await.is_empty());
+
+    drop(lock);
+    assert!(!queue.flush(&[notifier]).await.unwrap().busy);
+}
@@ -0,0 +1,78 @@
+//! The `sign` field every [`SignVersion`] computes, pinned for known keys and timestamps.
+
+use lark_notifier::{LarkMessage, LarkNotifier, SignVersion, generate_sign};
+
+/// Known signatures; every version in [`SignVersion::ALL`] needs its own.
+const PINNED: &[(SignVersion, u64, &str, &str)] = &[
+    // The example in Lark's custom bot documentation.
+    (
+        SignVersion::HmacSha256,
+        1599360473,
+        "secret",
+        "q4jswNiMy51J5JuQV566yJat0/lQ/c+22kINzUgKsGU=",
+    ),
+    (
+        SignVersion::HmacSha256,
+        1700000000,
+        "SEC-test-密钥",
+        "DNtXNMLN9aAd1LIdB+J33xY+lIwShcJw6jvcy+XUvTc=",
+    ),
+];
+
+#[test]
+fn every_version_is_pinned() {
+    for version in SignVersion::ALL {
+        assert!(
+            PINNED.iter().any(|(pinned, ..)| *pinned == version),
+            "{} has no pinned signature",
+            version
+        );
+    }
+}
+
+#[test]
+fn pinned_signatures() {
+    for &(version, timestamp, secret, sign) in PINNED {
+        assert_eq!(
+            generate_sign(timestamp, secret, version),
+            sign,
+            "{} at {}",
+            version,
+            timestamp
+        );
+        assert_eq!(version.sign(timestamp, secret), sign);
+    }
+}
+
+#[test]
+fn default_is_hmac_sha256() {
+    assert_eq!(SignVersion::default(), SignVersion::HmacSha256);
+}
+
+#[test]
+fn names_round_trip() {
+    for version in SignVersion::ALL {
+        assert_eq!(version.to_string().parse::<SignVersion>(), Ok(version));
+        assert_eq!(version.to_string(), version.name());
+    }
+    assert_eq!(
+        "sha1".parse::<SignVersion>(),
+        Err("unknown sign version 'sha1' (expected hmac-sha256)".to_string())
+    );
+}
+
+#[test]
+fn notifier_signs_with_its_version() {
+    let notifier = LarkNotifier::builder()
+        .webhook_url("https://open.feishu.cn/open-apis/bot/v2/hook/test-token")
+        .secret("secret")
+        .sign_version(SignVersion::HmacSha256)
+        .build()
+        .unwrap();
+    let message = notifier.prepare(LarkMessage::text("hi")).unwrap();
+    let timestamp: u64 = message.timestamp.as_deref().unwrap().parse().unwrap();
+    assert_eq!(
+        message.sign,
+        Some(generate_sign(timestamp, "secret", notifier.sign_version()))
+    );
+}
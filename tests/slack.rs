@@ -0,0 +1,58 @@
+//! [`mrkdwn_to_lark_md`] and [`SlackPayload`], the `/slack` route's translation.
+
+use lark_notifier::{SlackPayload, mrkdwn_to_lark_md};
+
+#[test]
+fn links_take_their_label() {
+    assert_eq!(
+        mrkdwn_to_lark_md("see <https://ci.example.com/1|build 1>"),
+        "see [build 1](https://ci.example.com/1)"
+    );
+    assert_eq!(
+        mrkdwn_to_lark_md("<https://ci.example.com/1>"),
+        "[https://ci.example.com/1](https://ci.example.com/1)"
+    );
+    assert_eq!(
+        mrkdwn_to_lark_md("<https://x.example.com/?a=1&amp;b=2|a &amp; b>"),
+        "[a & b](https://x.example.com/?a=1&b=2)"
+    );
+}
+
+#[test]
+fn emphasis_is_converted() {
+    assert_eq!(
+        mrkdwn_to_lark_md("*bold* and _italic_ and ~struck~"),
+        "**bold** and *italic* and ~~struck~~"
+    );
+    assert_eq!(
+        mrkdwn_to_lark_md("deploy *failed*: <https://ci.example.com|logs>"),
+        "deploy **failed**: [logs](https://ci.example.com)"
+    );
+    assert_eq!(mrkdwn_to_lark_md("(*note*)"), "(**note**)");
+}
+
+#[test]
+fn markers_inside_words_are_left_alone() {
+    assert_eq!(
+        mrkdwn_to_lark_md("set some_var_name to 1"),
+        "set some_var_name to 1"
+    );
+    assert_eq!(mrkdwn_to_lark_md("2*3*4 = 24"), "2*3*4 = 24");
+    assert_eq!(mrkdwn_to_lark_md("_snake_case_ wins"), "*snake_case* wins");
+    assert_eq!(mrkdwn_to_lark_md("* not bold *"), "* not bold *");
+    assert_eq!(mrkdwn_to_lark_md("*open\nclosed*"), "*open\nclosed*");
+}
+
+#[test]
+fn payload_text_is_translated_into_the_card() {
+    let payload: SlackPayload =
+        serde_json::from_str(r#"{"text": "*Deploy* of <https://ci.example.com/7|#7> done"}"#)
+            .unwrap();
+    let card = serde_json::to_value(payload.to_card()).unwrap();
+    assert!(
+        card.to_string()
+            .contains("**Deploy** of [#7](https://ci.example.com/7) done"),
+        "{}",
+        card
+    );
+}
@@ -0,0 +1,113 @@
+//! [`split_message`] and [`truncate_message`]: fitting posts under the payload limit.
+
+use lark_notifier::{
+    LarkContent, LarkMessage, LarkTextContent, payload_size, split_message, truncate_message,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+const MAX: usize = 2048;
+
+fn rows(message: &LarkMessage) -> (String, Vec<Vec<LarkTextContent>>) {
+    let Some(LarkContent::Post { post }) = &message.content else {
+        panic!("not a post: {:?}", message);
+    };
+    let content = post.zh_cn.as_ref().expect("zh_cn content");
+    (content.title.clone(), content.content.clone())
+}
+
+fn json(value: impl Serialize) -> Value {
+    serde_json::to_value(value).unwrap()
+}
+
+fn text(row: &[LarkTextContent]) -> String {
+    row.iter().map(|element| element.text.as_str()).collect()
+}
+
+#[test]
+fn splits_between_paragraphs() {
+    let paragraphs: Vec<Vec<LarkTextContent>> = (0..40)
+        .map(|i| {
+            vec![LarkTextContent::text(format!(
+                "paragraph {} {}",
+                i,
+                "x".repeat(100)
+            ))]
+        })
+        .collect();
+    let parts = split_message(LarkMessage::post("CI log", paragraphs.clone()), MAX);
+
+    assert!(parts.len() > 1);
+    assert!(parts.iter().all(|part| payload_size(part) <= MAX));
+    let mut sent = Vec::new();
+    for (i, part) in parts.iter().enumerate() {
+        let (title, rows) = rows(part);
+        assert_eq!(title, format!("CI log ({}/{})", i + 1, parts.len()));
+        sent.extend(rows);
+    }
+    // Every paragraph arrives whole, as its own row, in order.
+    assert_eq!(json(sent), json(paragraphs));
+}
+
+#[test]
+fn never_splits_inside_a_character() {
+    let content = "é漢🦀".repeat(500);
+    let parts = split_message(
+        LarkMessage::post("Log", vec![vec![LarkTextContent::text(&content)]]),
+        MAX,
+    );
+
+    assert!(parts.len() > 1);
+    assert!(parts.iter().all(|part| payload_size(part) <= MAX));
+    let pieces: Vec<String> = parts
+        .iter()
+        .flat_map(|part| rows(part).1)
+        .map(|row| text(&row))
+        .collect();
+    assert_eq!(pieces.concat(), content);
+    // Each piece starts where a character starts, so the cuts line up with the original.
+    let mut offset = 0;
+    for piece in &pieces {
+        assert!(content.is_char_boundary(offset));
+        offset += piece.len();
+    }
+}
+
+#[test]
+fn keeps_highlights_whole() {
+    let row: Vec<LarkTextContent> = (0..200)
+        .map(|i| LarkTextContent::bold(format!("keyword-{}", i)))
+        .collect();
+    let parts = split_message(LarkMessage::post("Log", vec![row.clone()]), MAX);
+
+    let sent: Vec<LarkTextContent> = parts
+        .iter()
+        .flat_map(|part| rows(part).1)
+        .flatten()
+        .collect();
+    assert_eq!(json(sent), json(row));
+}
+
+#[test]
+fn small_messages_are_left_alone() {
+    let message = LarkMessage::post("Deploy", vec![vec![LarkTextContent::text("done")]]);
+    assert_eq!(json(split_message(message.clone(), MAX)), json([&message]));
+    assert_eq!(json(truncate_message(message.clone(), MAX)), json(&message));
+}
+
+#[test]
+fn truncates_with_a_marker() {
+    let content = "línea\n".repeat(1000);
+    let truncated = truncate_message(LarkMessage::text(&content), MAX);
+
+    assert!(payload_size(&truncated) <= MAX);
+    let Some(LarkContent::Text { text }) = truncated.content else {
+        panic!("not text");
+    };
+    let (kept, marker) = text.rsplit_once('\n').unwrap();
+    assert!(content.starts_with(kept));
+    assert_eq!(
+        marker,
+        format!("(truncated, {} bytes omitted)", content.len() - kept.len())
+    );
+}
@@ -0,0 +1,66 @@
+//! [`Template`] rendering: variables, built-ins and the errors for what is missing.
+
+use std::collections::BTreeMap;
+
+use lark_notifier::Template;
+
+fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn first_line_is_the_title() {
+    let template = Template::new(
+        "release",
+        "Released {{ version }}\n{{version}} is out.\n\nSee the notes.\n",
+    );
+    let (title, content) = template.render(&vars(&[("version", "1.2.0")])).unwrap();
+    assert_eq!(title, "Released 1.2.0");
+    assert_eq!(content, "1.2.0 is out.\n\nSee the notes.");
+}
+
+#[test]
+fn builtins_render_with_their_variables() {
+    assert_eq!(
+        Template::builtin_names().collect::<Vec<_>>(),
+        ["deploy", "alert"]
+    );
+    let deploy = Template::builtin("deploy").unwrap();
+    let rendered = deploy.render(&vars(&[
+        ("service", "api"),
+        ("version", "1.2.0"),
+        ("environment", "prod"),
+    ]));
+    assert_eq!(
+        rendered.unwrap(),
+        (
+            "Deployed api 1.2.0".to_string(),
+            "api 1.2.0 is live on prod.".to_string()
+        )
+    );
+    assert!(Template::builtin("nope").is_none());
+}
+
+#[test]
+fn undefined_variables_name_the_line() {
+    let template = Template::new(
+        "alert.tmpl",
+        "Alert\nHost: {{ host }}\nSeverity: {{ severity }}\n",
+    );
+    let err = template.render(&vars(&[("host", "db-1")])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "alert.tmpl:3: undefined variable 'severity'"
+    );
+}
+
+#[test]
+fn unclosed_placeholders_name_the_line() {
+    let err = Template::new("t", "Title\n{{ oops")
+        .render(&BTreeMap::new())
+        .unwrap_err();
+    assert!(err.to_string().ends_with("t:2: unclosed '{{'"), "{}", err);
+}
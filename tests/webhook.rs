@@ -0,0 +1,612 @@
+//! The HTTP path of [`LarkNotifier`] and [`send_with_policy`] against a mock webhook.
+
+use std::time::{Duration, Instant};
+
+use lark_notifier::{
+    ALLOW_INSECURE_URL, LarkMessage, LarkNotifier, NotifierError, RateLimiter, RetryPolicy,
+    SendPolicy, SignVersion, generate_sign, send_with_policy, validate_webhook_url,
+};
+use serde_json::{Value, json};
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const HOOK: &str = "/open-apis/bot/v2/hook/test-token";
+
+async fn server(response: ResponseTemplate) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(HOOK))
+        .and(header("content-type", "application/json"))
+        .respond_with(response)
+        .mount(&server)
+        .await;
+    server
+}
+
+fn ok() -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({ "code": 0, "msg": "success", "data": {} }))
+}
+
+fn notifier(server: &MockServer, secret: Option<&str>) -> LarkNotifier {
+    let mut builder = LarkNotifier::builder()
+        .webhook_url(format!("{}{}", server.uri(), HOOK))
+        .retry_policy(RetryPolicy::new(0, Duration::ZERO))
+        .allow_insecure_url(true);
+    if let Some(secret) = secret {
+        builder = builder.secret(secret);
+    }
+    builder.build().expect("valid notifier")
+}
+
+/// The JSON bodies the server received, in order.
+async fn bodies(server: &MockServer) -> Vec<Value> {
+    let requests = server
+        .received_requests()
+        .await
+        .expect("request recording is enabled");
+    requests
+        .iter()
+        .map(|request| request.body_json().expect("JSON body"))
+        .collect()
+}
+
+#[test]
+fn builder_defaults() {
+    let notifier = LarkNotifier::builder()
+        .webhook_url("https://open.feishu.cn/open-apis/bot/v2/hook/test")
+        .build()
+        .expect("valid notifier");
+    assert_eq!(notifier.secret(), None);
+    assert_eq!(notifier.sign_version(), SignVersion::HmacSha256);
+    assert_eq!(notifier.time_offset(), 0);
+    assert_eq!(notifier.sign_timestamp(), None);
+    assert_eq!(notifier.timeout(), None);
+    let retry = notifier.retry_policy();
+    assert_eq!(
+        (retry.retries, retry.base_delay),
+        (0, Duration::from_millis(500))
+    );
+    let http = LarkNotifier::builder()
+        .webhook_url("http://open.feishu.cn/open-apis/bot/v2/hook/test")
+        .build();
+    assert!(matches!(http, Err(NotifierError::InvalidWebhookUrl { .. })));
+}
+
+#[tokio::test]
+async fn default_notifier_sends_once_without_retrying() {
+    let server = server(ResponseTemplate::new(503)).await;
+    let notifier = LarkNotifier::builder()
+        .webhook_url(format!("{}{}", server.uri(), HOOK))
+        .allow_insecure_url(true)
+        .build()
+        .expect("valid notifier");
+
+    let err = notifier.send_post("Deploy", "done", &[]).await.unwrap_err();
+    assert!(
+        matches!(err, NotifierError::Status { status, .. } if status.as_u16() == 503),
+        "{:?}",
+        err
+    );
+    let bodies = bodies(&server).await;
+    assert_eq!(bodies.len(), 1);
+    assert_eq!(bodies[0]["msg_type"], "post");
+    assert!(
+        bodies[0]["content"]["post"]["zh_cn"].is_object(),
+        "{}",
+        bodies[0]
+    );
+}
+
+#[tokio::test]
+async fn post_body() {
+    let server = server(ok()).await;
+    notifier(&server, None)
+        .send_post("Deploy", "deploy finished on prod", &["prod".to_string()])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        bodies(&server).await,
+        [json!({
+            "msg_type": "post",
+            "content": { "post": { "zh_cn": {
+                "title": "Deploy",
+                "content": [[
+                    { "tag": "text", "text": "deploy finished on " },
+                    { "tag": "text", "text": "prod", "style": ["bold"] },
+                ]],
+            } } },
+        })]
+    );
+}
+
+#[tokio::test]
+async fn text_body() {
+    let server = server(ok()).await;
+    notifier(&server, None)
+        .send_text("deploy finished")
+        .await
+        .unwrap();
+
+    assert_eq!(
+        bodies(&server).await,
+        [json!({ "msg_type": "text", "content": { "text": "deploy finished" } })]
+    );
+}
+
+#[tokio::test]
+async fn text_json_is_exact_signed_and_unsigned() {
+    let server = server(ok()).await;
+    notifier(&server, None)
+        .send_text("deploy finished")
+        .await
+        .unwrap();
+    let signed = LarkNotifier::builder()
+        .webhook_url(format!("{}{}", server.uri(), HOOK))
+        .secret("secret")
+        .sign_timestamp(1599360473)
+        .allow_insecure_url(true)
+        .build()
+        .expect("valid notifier");
+    signed.send_text("deploy finished").await.unwrap();
+
+    let requests = server
+        .received_requests()
+        .await
+        .expect("request recording is enabled");
+    let raw: Vec<_> = requests
+        .iter()
+        .map(|request| String::from_utf8_lossy(&request.body).into_owned())
+        .collect();
+    assert_eq!(
+        raw,
+        [
+            r#"{"msg_type":"text","content":{"text":"deploy finished"}}"#,
+            r#"{"msg_type":"text","content":{"text":"deploy finished"},"sign":"q4jswNiMy51J5JuQV566yJat0/lQ/c+22kINzUgKsGU=","timestamp":"1599360473"}"#,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn card_body() {
+    let server = server(ok()).await;
+    notifier(&server, None)
+        .send_card(
+            "DB down",
+            "replica lag on db-1",
+            &["db-1".to_string()],
+            "red",
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        bodies(&server).await,
+        [json!({
+            "msg_type": "interactive",
+            "card": {
+                "header": { "title": { "tag": "plain_text", "content": "DB down" }, "template": "red" },
+                "elements": [{ "tag": "div", "text": { "tag": "lark_md", "content": "replica lag on **db-1**" } }],
+            },
+        })]
+    );
+}
+
+#[tokio::test]
+async fn unsigned_without_secret() {
+    let server = server(ok()).await;
+    notifier(&server, None).send_text("hi").await.unwrap();
+
+    let body = &bodies(&server).await[0];
+    assert!(body.get("sign").is_none(), "{}", body);
+    assert!(body.get("timestamp").is_none(), "{}", body);
+}
+
+#[tokio::test]
+async fn signed_with_secret() {
+    let server = server(ok()).await;
+    notifier(&server, Some("secret"))
+        .send_text("hi")
+        .await
+        .unwrap();
+
+    let body = &bodies(&server).await[0];
+    let timestamp: u64 = body["timestamp"]
+        .as_str()
+        .expect("timestamp is a string")
+        .parse()
+        .unwrap();
+    assert_eq!(
+        body["sign"],
+        generate_sign(timestamp, "secret", SignVersion::HmacSha256)
+    );
+    assert_eq!(body["content"], json!({ "text": "hi" }));
+}
+
+#[tokio::test]
+async fn sign_mismatch_is_an_error() {
+    let server = server(ResponseTemplate::new(200).set_body_json(json!({
+        "code": 19021,
+        "msg": "sign match fail or timestamp is not within one hour from current time",
+    })))
+    .await;
+    let err = notifier(&server, Some("wrong"))
+        .send_text("hi")
+        .await
+        .unwrap_err();
+
+    assert!(
+        matches!(err, NotifierError::LarkApi { code: 19021, .. }),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn malformed_response_keeps_the_raw_body() {
+    let server =
+        server(ResponseTemplate::new(200).set_body_string("<html>gateway says hi</html>")).await;
+    let response = notifier(&server, None).send_text("hi").await.unwrap();
+
+    assert!(response.is_success());
+    assert!(response.response.is_none());
+    assert_eq!(response.body, "<html>gateway says hi</html>");
+}
+
+#[tokio::test]
+async fn malformed_error_response_keeps_the_raw_body() {
+    let server = server(ResponseTemplate::new(502).set_body_string("{\"code\":")).await;
+    let err = notifier(&server, None).send_text("hi").await.unwrap_err();
+
+    assert!(
+        matches!(&err, NotifierError::Status { body, .. } if body == "{\"code\":"),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn send_with_policy_retries_server_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(500).set_body_string("oops"))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ok())
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let policy = SendPolicy {
+        retry: RetryPolicy::new(3, Duration::from_millis(1)),
+        ..SendPolicy::default()
+    };
+    let url = format!("{}{}", server.uri(), HOOK);
+    let response = send_with_policy(
+        &reqwest::Client::new(),
+        &url,
+        Some("secret"),
+        LarkMessage::text("hi"),
+        &policy,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(response.attempts, 3);
+    // Each attempt is signed on its own.
+    assert!(
+        bodies(&server)
+            .await
+            .iter()
+            .all(|body| body["sign"].is_string())
+    );
+}
+
+#[tokio::test]
+async fn send_with_policy_times_out_each_attempt() {
+    let server = server(ok().set_delay(Duration::from_secs(5))).await;
+    let policy = SendPolicy {
+        timeout: Some(Duration::from_millis(200)),
+        retry: RetryPolicy::new(1, Duration::from_millis(1)),
+        ..SendPolicy::default()
+    };
+    let url = format!("{}{}", server.uri(), HOOK);
+    let started = Instant::now();
+    let err = send_with_policy(
+        &reqwest::Client::new(),
+        &url,
+        None,
+        LarkMessage::text("hi"),
+        &policy,
+    )
+    .await
+    .unwrap_err();
+
+    assert!(err.is_timeout(), "{:?}", err);
+    assert_eq!(bodies(&server).await.len(), 2);
+    assert!(
+        started.elapsed() < Duration::from_secs(2),
+        "{:?}",
+        started.elapsed()
+    );
+}
+
+#[tokio::test]
+async fn send_with_policy_spaces_sends_by_the_rate_limit() {
+    let server = server(ok()).await;
+    let policy = SendPolicy {
+        rate_limiter: Some(RateLimiter::per_minute(600)),
+        ..SendPolicy::default()
+    };
+    let url = format!("{}{}", server.uri(), HOOK);
+    let client = reqwest::Client::new();
+    let started = Instant::now();
+    for _ in 0..3 {
+        send_with_policy(&client, &url, None, LarkMessage::text("hi"), &policy)
+            .await
+            .unwrap();
+    }
+
+    // One every 100ms, the first right away.
+    assert!(
+        started.elapsed() >= Duration::from_millis(200),
+        "{:?}",
+        started.elapsed()
+    );
+}
+
+#[tokio::test]
+async fn send_with_policy_reports_the_last_failure() {
+    let server = server(ResponseTemplate::new(500).set_body_string("oops")).await;
+
+    let policy = SendPolicy {
+        retry: RetryPolicy::new(1, Duration::from_millis(1)),
+        ..SendPolicy::default()
+    };
+    let url = format!("{}{}", server.uri(), HOOK);
+    let err = send_with_policy(
+        &reqwest::Client::new(),
+        &url,
+        None,
+        LarkMessage::text("hi"),
+        &policy,
+    )
+    .await
+    .unwrap_err();
+
+    assert!(
+        matches!(err, NotifierError::RetriesExhausted { attempts: 2, .. }),
+        "{:?}",
+        err
+    );
+    assert!(
+        matches!(err.final_error(), NotifierError::Status { .. }),
+        "{:?}",
+        err
+    );
+}
+
+/// A notifier against `server` that retries twice, backing off `base_delay`.
+fn retrying(server: &MockServer, base_delay: Duration) -> LarkNotifier {
+    LarkNotifier::builder()
+        .webhook_url(format!("{}{}", server.uri(), HOOK))
+        .retry_policy(RetryPolicy::new(2, base_delay))
+        .allow_insecure_url(true)
+        .build()
+        .expect("valid notifier")
+}
+
+#[tokio::test]
+async fn rate_limited_waits_as_long_as_retry_after_says() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "1"))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ok())
+        .mount(&server)
+        .await;
+
+    let started = Instant::now();
+    let response = retrying(&server, Duration::from_millis(1))
+        .send_text("hi")
+        .await
+        .unwrap();
+    assert_eq!(response.attempts, 2);
+    assert!(
+        started.elapsed() >= Duration::from_secs(1),
+        "{:?}",
+        started.elapsed()
+    );
+}
+
+#[tokio::test]
+async fn rate_limited_without_retry_after_backs_off() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(429))
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let started = Instant::now();
+    let err = retrying(&server, Duration::from_millis(20))
+        .send_text("hi")
+        .await
+        .unwrap_err();
+    let elapsed = started.elapsed();
+
+    let NotifierError::RetriesExhausted {
+        attempts, waited, ..
+    } = &err
+    else {
+        panic!("unexpected error: {:?}", err);
+    };
+    assert_eq!(*attempts, 3);
+    // 20ms, then 40ms, each with up to 20ms of jitter.
+    assert!(
+        *waited >= Duration::from_millis(60) && *waited < Duration::from_millis(100),
+        "{:?}",
+        waited
+    );
+    assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+    assert!(
+        err.to_string().contains("after 3 attempts, waited"),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn server_error_honors_retry_after() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(503).insert_header("retry-after", "1"))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ok())
+        .mount(&server)
+        .await;
+
+    let started = Instant::now();
+    let response = retrying(&server, Duration::from_millis(1))
+        .send_text("hi")
+        .await
+        .unwrap();
+    assert_eq!(response.attempts, 2);
+    assert!(
+        started.elapsed() >= Duration::from_secs(1),
+        "{:?}",
+        started.elapsed()
+    );
+}
+
+#[tokio::test]
+async fn failures_are_told_apart_by_variant() {
+    let bad_gateway = server(ResponseTemplate::new(502).set_body_string("bad gateway")).await;
+    let err = notifier(&bad_gateway, None)
+        .send_text("hi")
+        .await
+        .unwrap_err();
+    let NotifierError::Status { status, body } = &err else {
+        panic!("unexpected error: {:?}", err);
+    };
+    assert_eq!((status.as_u16(), body.as_str()), (502, "bad gateway"));
+    assert!(err.is_transient());
+
+    let rejecting = server(
+        ResponseTemplate::new(200).set_body_json(json!({ "code": 9499, "msg": "Bad Request" })),
+    )
+    .await;
+    let err = notifier(&rejecting, None)
+        .send_text("hi")
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, NotifierError::LarkApi { code: 9499, msg } if msg == "Bad Request"),
+        "{:?}",
+        err
+    );
+    assert!(!err.is_transient());
+
+    // Nothing listens on a port that was just free.
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let unreachable = LarkNotifier::builder()
+        .webhook_url(format!("http://127.0.0.1:{}{}", port, HOOK))
+        .retry_policy(RetryPolicy::new(0, Duration::ZERO))
+        .allow_insecure_url(true)
+        .build()
+        .unwrap();
+    let err = unreachable.send_text("hi").await.unwrap_err();
+    assert!(matches!(err, NotifierError::Http(_)), "{:?}", err);
+    assert!(err.is_transient());
+}
+
+#[test]
+fn configuration_errors_come_before_sending() {
+    let missing = LarkNotifier::builder().build();
+    assert!(matches!(missing, Err(NotifierError::MissingWebhookUrl)));
+    let invalid = LarkNotifier::builder()
+        .webhook_url("https://example.com/hook")
+        .build();
+    assert!(matches!(
+        invalid,
+        Err(NotifierError::InvalidWebhookUrl { .. })
+    ));
+    let proxy = LarkNotifier::builder()
+        .webhook_url("https://open.feishu.cn/open-apis/bot/v2/hook/test")
+        .proxy("not a proxy")
+        .build();
+    assert!(matches!(proxy, Err(NotifierError::InvalidProxy(_))));
+}
+
+/// What `validate_webhook_url` finds wrong with `url`, without the closing hint.
+fn problems(url: &str) -> Vec<String> {
+    match validate_webhook_url(url, false) {
+        Ok(()) => Vec::new(),
+        Err(NotifierError::InvalidWebhookUrl { mut problems, .. }) => {
+            let hint = problems.pop().unwrap();
+            assert!(hint.contains(ALLOW_INSECURE_URL), "{}", hint);
+            problems
+        }
+        Err(err) => panic!("{:?}", err),
+    }
+}
+
+#[test]
+fn webhook_urls_must_be_lark_bot_hooks() {
+    assert!(problems("https://open.feishu.cn/open-apis/bot/v2/hook/token").is_empty());
+    assert!(problems("https://open.larksuite.com/open-apis/bot/v2/hook/token").is_empty());
+    assert_eq!(
+        problems("http://open.feishu.cn/open-apis/bot/v2/hook/token"),
+        ["scheme must be https, not http"]
+    );
+    assert_eq!(
+        problems("https://hooks.example.com/open-apis/bot/v2/hook/token"),
+        ["host must be open.feishu.cn or open.larksuite.com, not hooks.example.com"]
+    );
+    assert_eq!(
+        problems("https://open.feishu.cn/api/hook/token"),
+        ["path must start with /open-apis/bot/v2/hook/"]
+    );
+    assert_eq!(
+        problems("https://open.feishu.cn/open-apis/bot/v2/hook/"),
+        ["missing the bot token after /open-apis/bot/v2/hook/"]
+    );
+    assert_eq!(problems("http://example.com/hook").len(), 3);
+}
+
+#[test]
+fn insecure_webhook_urls_need_only_a_web_scheme() {
+    assert!(validate_webhook_url("http://127.0.0.1:8080/any/path", true).is_ok());
+    let err = validate_webhook_url("ftp://127.0.0.1/hook", true).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid webhook URL ftp://127.0.0.1/****: scheme must be http or https, not ftp"
+    );
+    assert!(validate_webhook_url("not a url", true).is_err());
+}
+
+#[test]
+fn invalid_webhook_url_errors_mask_the_token() {
+    let token = "SECRETTOKEN1234567";
+    let url = format!("http://example.com/open-apis/bot/v2/hook/{}", token);
+    let err = validate_webhook_url(&url, false).unwrap_err().to_string();
+    assert!(
+        err.starts_with("Invalid webhook URL http://example.com/open-apis/bot/v2/hook/SECR**"),
+        "{}",
+        err
+    );
+    assert!(!err.contains(token), "{}", err);
+}